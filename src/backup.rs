@@ -0,0 +1,164 @@
+//! Backups of a replica's database, and restoring them into a new data
+//! directory.
+//!
+//! A backup is a single SQLite file written with `VACUUM INTO`, which
+//! copies TaskChampion's database as of one read transaction: its
+//! operations, task data, working set and sync state, compacted. Unlike
+//! copying `taskchampion.sqlite3` and its WAL files, it can never capture
+//! half a commit. The binding's own database (undo-point labels and
+//! templates, see [`crate::journal`]) is device-local metadata and is not
+//! part of a backup.
+
+use crate::storage::{self, open_on_disk, DB_FILE};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use uuid::Uuid;
+
+/// What a valid backup holds.
+#[derive(Debug, PartialEq)]
+pub struct BackupSummary {
+    pub tasks: usize,
+    pub operations: usize,
+}
+
+/// `path` with `suffix` appended to its file name.
+fn suffixed(path: &Path, suffix: &str) -> PathBuf {
+    let mut name = path.as_os_str().to_owned();
+    name.push(suffix);
+    PathBuf::from(name)
+}
+
+/// Write a snapshot of the database in `data_dir` to `dest`, which must
+/// not exist yet. Returns the size of the backup in bytes.
+pub fn backup(data_dir: &Path, dest: &Path) -> Result<u64, String> {
+    if dest.exists() {
+        return Err(format!("Backup destination {} already exists", dest.display()));
+    }
+    // Written aside, then renamed into place.
+    let partial = suffixed(dest, ".partial");
+    let partial_str = partial
+        .to_str()
+        .ok_or_else(|| format!("Backup destination {} is not valid UTF-8", dest.display()))?;
+    // Left over from an interrupted backup; VACUUM INTO refuses to
+    // overwrite it.
+    let _ = std::fs::remove_file(&partial);
+
+    let conn = storage::open_read_only(&data_dir.join(DB_FILE))?;
+    conn.execute("VACUUM INTO ?1", [partial_str])
+        .map_err(|e| format!("Failed to write backup: {}", e))?;
+    drop(conn);
+
+    let written = validate(&partial).and_then(|_| {
+        std::fs::rename(&partial, dest).map_err(|e| format!("Failed to move backup into place: {}", e))
+    });
+    if let Err(e) = written {
+        let _ = std::fs::remove_file(&partial);
+        return Err(e);
+    }
+    std::fs::metadata(dest)
+        .map(|m| m.len())
+        .map_err(|e| format!("Failed to read backup size: {}", e))
+}
+
+/// Check that `path` is a readable, intact TaskChampion database: its
+/// schema is one [`storage`] supports, SQLite finds no corruption and
+/// every task row decodes.
+pub fn validate(path: &Path) -> Result<BackupSummary, String> {
+    if !path.is_file() {
+        return Err(format!("Backup {} does not exist", path.display()));
+    }
+    let conn = storage::open_read_only(path)?;
+    let integrity: String = conn
+        .query_row("PRAGMA integrity_check", [], |row| row.get(0))
+        .map_err(|e| format!("{} is not a usable database: {}", path.display(), e))?;
+    if integrity != "ok" {
+        return Err(format!("Backup {} is corrupt: {}", path.display(), integrity));
+    }
+
+    let mut tasks = 0;
+    for (uuid, data) in storage::task_rows(&conn)? {
+        let uuid = uuid.unwrap_or_default();
+        Uuid::parse_str(&uuid).map_err(|_| format!("Backup has a task with invalid uuid '{}'", uuid))?;
+        serde_json::from_str::<HashMap<String, String>>(data.as_deref().unwrap_or(""))
+            .map_err(|e| format!("Backup has unreadable data for task {}: {}", uuid, e))?;
+        tasks += 1;
+    }
+    let operations = storage::operation_count(&conn)?;
+    Ok(BackupSummary { tasks, operations })
+}
+
+/// Validate the backup at `backup_path` and restore it as the database of
+/// `data_dir`, which must not exist or be empty. The restored database is
+/// opened once with TaskChampion before returning, so a backup from an
+/// older minor schema version is upgraded here rather than on first use.
+pub fn restore(data_dir: &Path, backup_path: &Path) -> Result<BackupSummary, String> {
+    if data_dir.exists() {
+        let mut entries = std::fs::read_dir(data_dir)
+            .map_err(|e| format!("Failed to read {}: {}", data_dir.display(), e))?;
+        if entries.next().is_some() {
+            return Err(format!("{} is not empty; restore needs a fresh directory", data_dir.display()));
+        }
+    }
+    let summary = validate(backup_path)?;
+
+    std::fs::create_dir_all(data_dir).map_err(|e| format!("Failed to create {}: {}", data_dir.display(), e))?;
+    let db = data_dir.join(DB_FILE);
+    let partial = suffixed(&db, ".partial");
+    let restored = std::fs::copy(backup_path, &partial)
+        .map_err(|e| format!("Failed to copy backup: {}", e))
+        .and_then(|_| std::fs::rename(&partial, &db).map_err(|e| format!("Failed to move backup into place: {}", e)))
+        .and_then(|_| {
+            open_on_disk(data_dir, false)?
+                .all_task_uuids()
+                .map_err(|e| format!("Failed to read restored database: {}", e))
+        });
+    if let Err(e) = restored {
+        // Leave the directory as fresh as it was given.
+        for file in [partial, suffixed(&db, "-wal"), suffixed(&db, "-shm"), db] {
+            let _ = std::fs::remove_file(file);
+        }
+        return Err(e);
+    }
+    Ok(summary)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use taskchampion::{Operations, Status};
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_backup_and_restore() {
+        let dir = TempDir::new().unwrap();
+        let data_dir = dir.path().join("data");
+        let mut replica = open_on_disk(&data_dir, true).unwrap();
+        let uuid = Uuid::new_v4();
+        let mut ops = Operations::new();
+        let mut task = replica.create_task(uuid, &mut ops).unwrap();
+        task.set_description("Back me up".to_string(), &mut ops).unwrap();
+        task.set_status(Status::Pending, &mut ops).unwrap();
+        replica.commit_operations(ops).unwrap();
+
+        // Taken while the replica is open, as a handle would hold it.
+        let dest = dir.path().join("snapshot.sqlite3");
+        assert!(backup(&data_dir, &dest).unwrap() > 0);
+        assert!(backup(&data_dir, &dest).is_err());
+        let summary = validate(&dest).unwrap();
+        assert_eq!(summary.tasks, 1);
+        assert!(summary.operations > 0);
+
+        let restored_dir = dir.path().join("restored");
+        assert_eq!(restore(&restored_dir, &dest).unwrap(), summary);
+        let mut restored = open_on_disk(&restored_dir, false).unwrap();
+        assert_eq!(restored.get_task(uuid).unwrap().unwrap().get_description(), "Back me up");
+
+        // The target must be fresh, and the backup a database.
+        assert!(restore(&restored_dir, &dest).is_err());
+        let junk = dir.path().join("junk");
+        std::fs::write(&junk, "not a database").unwrap();
+        let fresh = dir.path().join("fresh");
+        assert!(restore(&fresh, &junk).is_err());
+        assert!(!fresh.join(DB_FILE).exists());
+    }
+}
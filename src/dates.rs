@@ -0,0 +1,54 @@
+use chrono::{DateTime, Duration, NaiveDate, NaiveDateTime, Utc};
+
+/// Parse a date supplied by the client into a UTC timestamp.
+///
+/// Accepted forms, tried in order:
+/// - Unix epoch seconds (`1760616000`), the encoding TaskChampion stores
+/// - the named dates `now`, `today`, `yesterday` and `tomorrow`, where the
+///   day-based names mean midnight UTC
+/// - TaskWarrior's compact ISO-8601 form (`20261016T120000Z`)
+/// - RFC 3339 (`2026-10-16T12:00:00Z`, `2026-10-16T14:00:00+02:00`)
+/// - a bare calendar date (`2026-10-16`), meaning midnight UTC
+pub fn parse_date(value: &str) -> Result<DateTime<Utc>, String> {
+    let value = value.trim();
+    if let Ok(secs) = value.parse::<i64>() {
+        return DateTime::from_timestamp(secs, 0)
+            .ok_or_else(|| format!("Date out of range: '{}'", value));
+    }
+
+    let today = Utc::now().date_naive().and_hms_opt(0, 0, 0).unwrap().and_utc();
+    match value {
+        "now" => return Ok(Utc::now()),
+        "today" => return Ok(today),
+        "yesterday" => return Ok(today - Duration::days(1)),
+        "tomorrow" => return Ok(today + Duration::days(1)),
+        _ => {}
+    }
+
+    if let Ok(dt) = NaiveDateTime::parse_from_str(value, "%Y%m%dT%H%M%SZ") {
+        return Ok(dt.and_utc());
+    }
+    if let Ok(dt) = DateTime::parse_from_rfc3339(value) {
+        return Ok(dt.with_timezone(&Utc));
+    }
+    if let Ok(d) = NaiveDate::parse_from_str(value, "%Y-%m-%d") {
+        return Ok(d.and_hms_opt(0, 0, 0).unwrap().and_utc());
+    }
+    Err(format!(
+        "Unrecognised date '{}'; expected epoch seconds, YYYY-MM-DD, RFC 3339 or YYYYMMDDTHHMMSSZ",
+        value
+    ))
+}
+
+/// True if `value` names a whole day rather than an instant: a bare
+/// calendar date or one of `today`, `yesterday` and `tomorrow`.
+pub fn is_calendar_day(value: &str) -> bool {
+    let value = value.trim();
+    matches!(value, "today" | "yesterday" | "tomorrow") || NaiveDate::parse_from_str(value, "%Y-%m-%d").is_ok()
+}
+
+/// Read a TaskChampion timestamp property (epoch seconds as a string).
+/// Returns `None` when the stored value is not a valid timestamp.
+pub fn stored_timestamp(value: &str) -> Option<DateTime<Utc>> {
+    value.parse::<i64>().ok().and_then(|secs| DateTime::from_timestamp(secs, 0))
+}
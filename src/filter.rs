@@ -0,0 +1,469 @@
+//! TaskWarrior-compatible task filters, evaluated in Rust against the
+//! replica's task map.
+//!
+//! The supported grammar is the subset of TaskWarrior's filter language
+//! that makes sense without a `.taskrc`:
+//!
+//! - `+tag` / `-tag`: the task has / does not have the tag. Synthetic tags
+//!   such as `+PENDING` or `+BLOCKED` are supported.
+//! - `name:value`: attribute equality. `project:Home` also matches the
+//!   sub-projects `Home.Garden` etc.; an empty value (`project:`) matches
+//!   tasks without the attribute.
+//! - `name.modifier:value`: attribute comparison with one of the modifiers
+//!   `is`, `isnt`, `before`, `after`, `contains`, `hasnt`, `startswith`,
+//!   `endswith`, `none` and `any` (TaskWarrior's aliases `equals`, `not`,
+//!   `below`, `under`, `above`, `over`, `has`, `left` and `right` are also
+//!   accepted).
+//! - any other word: case-insensitive substring match on the description
+//!   and annotations.
+//! - `and`, `or` and parentheses. Adjacent terms are implicitly joined with
+//!   `and`, which binds tighter than `or`.
+//!
+//! Values containing whitespace may be quoted with `'` or `"`. Date
+//! attributes compare as timestamps, with values parsed by
+//! [`crate::dates::parse_date`]; equality with a bare calendar date
+//! (`due:2026-10-16`, `due:today`) matches the whole UTC day. Other
+//! attributes compare finite numbers numerically and before any other
+//! value, and everything else as strings. Text matching for
+//! `contains`, `hasnt`, `startswith`, `endswith` and bare words ignores
+//! case; `is` and `isnt` are exact.
+
+use crate::dates::{is_calendar_day, parse_date, stored_timestamp};
+use chrono::{DateTime, Utc};
+use std::cmp::Ordering;
+use taskchampion::{Tag, Task};
+
+/// Attributes stored as epoch-second timestamps, compared as dates.
+pub const DATE_ATTRIBUTES: &[&str] = &[
+    "entry", "modified", "end", "due", "wait", "scheduled", "until", "start",
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Modifier {
+    Is,
+    Isnt,
+    Before,
+    After,
+    Contains,
+    Hasnt,
+    StartsWith,
+    EndsWith,
+    None,
+    Any,
+}
+
+impl Modifier {
+    fn from_name(name: &str) -> Option<Modifier> {
+        Some(match name {
+            "is" | "equals" => Modifier::Is,
+            "isnt" | "not" => Modifier::Isnt,
+            "before" | "below" | "under" => Modifier::Before,
+            "after" | "above" | "over" => Modifier::After,
+            "contains" | "has" => Modifier::Contains,
+            "hasnt" => Modifier::Hasnt,
+            "startswith" | "left" => Modifier::StartsWith,
+            "endswith" | "right" => Modifier::EndsWith,
+            "none" => Modifier::None,
+            "any" => Modifier::Any,
+            _ => return None,
+        })
+    }
+}
+
+/// A parsed filter expression.
+#[derive(Debug, Clone)]
+pub enum Filter {
+    /// The empty filter; matches every task.
+    All,
+    And(Box<Filter>, Box<Filter>),
+    Or(Box<Filter>, Box<Filter>),
+    Tag { tag: Tag, present: bool },
+    Attribute {
+        name: String,
+        modifier: Modifier,
+        value: String,
+        /// `value` parsed as a date, for date attributes compared with a
+        /// value-taking modifier.
+        date: Option<DateTime<Utc>>,
+    },
+    Word(String),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    LParen,
+    RParen,
+    And,
+    Or,
+    Term(String),
+}
+
+fn tokenize(input: &str) -> Result<Vec<Token>, String> {
+    fn flush(tokens: &mut Vec<Token>, current: &mut String, quoted: &mut bool) {
+        if !current.is_empty() || *quoted {
+            let word = std::mem::take(current);
+            tokens.push(match word.as_str() {
+                "and" if !*quoted => Token::And,
+                "or" if !*quoted => Token::Or,
+                _ => Token::Term(word),
+            });
+        }
+        *quoted = false;
+    }
+
+    let mut tokens = Vec::new();
+    let mut current = String::new();
+    // True once any part of the current token was quoted, so a quoted
+    // "and" stays a search term rather than an operator.
+    let mut quoted = false;
+    let mut chars = input.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\'' | '"' => {
+                quoted = true;
+                loop {
+                    match chars.next() {
+                        Some(q) if q == c => break,
+                        Some(other) => current.push(other),
+                        None => return Err(format!("Unterminated quote in filter: {}", input)),
+                    }
+                }
+            }
+            '(' | ')' => {
+                flush(&mut tokens, &mut current, &mut quoted);
+                tokens.push(if c == '(' { Token::LParen } else { Token::RParen });
+            }
+            c if c.is_whitespace() => flush(&mut tokens, &mut current, &mut quoted),
+            c => current.push(c),
+        }
+    }
+    flush(&mut tokens, &mut current, &mut quoted);
+    Ok(tokens)
+}
+
+fn is_attribute_name(name: &str) -> bool {
+    let mut chars = name.chars();
+    matches!(chars.next(), Some(c) if c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || matches!(c, '_' | '.' | '-'))
+}
+
+fn parse_term(term: &str) -> Result<Filter, String> {
+    if let Some(rest) = term.strip_prefix('+').or_else(|| term.strip_prefix('-')) {
+        if !rest.is_empty() && !rest.contains(':') {
+            let tag = Tag::try_from(rest).map_err(|e| format!("Invalid tag '{}' in filter: {}", rest, e))?;
+            return Ok(Filter::Tag { tag, present: term.starts_with('+') });
+        }
+    }
+
+    if let Some((lhs, value)) = term.split_once(':') {
+        if is_attribute_name(lhs) {
+            let (name, modifier) = match lhs.rsplit_once('.') {
+                Some((name, m)) => match Modifier::from_name(m) {
+                    Some(modifier) => (name, modifier),
+                    None => (lhs, Modifier::Is),
+                },
+                None => (lhs, Modifier::Is),
+            };
+            let takes_date = !matches!(
+                modifier,
+                Modifier::None | Modifier::Any | Modifier::Contains | Modifier::Hasnt
+                    | Modifier::StartsWith | Modifier::EndsWith
+            );
+            let date = if takes_date && DATE_ATTRIBUTES.contains(&name) && !value.is_empty() {
+                Some(parse_date(value).map_err(|e| format!("Invalid value for '{}': {}", name, e))?)
+            } else {
+                None
+            };
+            return Ok(Filter::Attribute {
+                name: name.to_string(),
+                modifier,
+                value: value.to_string(),
+                date,
+            });
+        }
+    }
+
+    Ok(Filter::Word(term.to_string()))
+}
+
+struct Parser {
+    tokens: Vec<Token>,
+    pos: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos)
+    }
+
+    fn next(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.pos).cloned();
+        self.pos += 1;
+        token
+    }
+
+    // or_expr := and_expr ("or" and_expr)*
+    fn or_expr(&mut self) -> Result<Filter, String> {
+        let mut lhs = self.and_expr()?;
+        while self.peek() == Some(&Token::Or) {
+            self.next();
+            let rhs = self.and_expr()?;
+            lhs = Filter::Or(Box::new(lhs), Box::new(rhs));
+        }
+        Ok(lhs)
+    }
+
+    // and_expr := primary (["and"] primary)*
+    fn and_expr(&mut self) -> Result<Filter, String> {
+        let mut lhs = self.primary()?;
+        loop {
+            match self.peek() {
+                Some(Token::And) => {
+                    self.next();
+                }
+                Some(Token::Term(_)) | Some(Token::LParen) => {}
+                _ => return Ok(lhs),
+            }
+            let rhs = self.primary()?;
+            lhs = Filter::And(Box::new(lhs), Box::new(rhs));
+        }
+    }
+
+    // primary := "(" or_expr ")" | term
+    fn primary(&mut self) -> Result<Filter, String> {
+        match self.next() {
+            Some(Token::LParen) => {
+                let inner = self.or_expr()?;
+                match self.next() {
+                    Some(Token::RParen) => Ok(inner),
+                    _ => Err("Unbalanced '(' in filter".to_string()),
+                }
+            }
+            Some(Token::Term(term)) => parse_term(&term),
+            Some(Token::RParen) => Err("Unexpected ')' in filter".to_string()),
+            Some(Token::And) | Some(Token::Or) => {
+                Err("'and'/'or' must appear between two filter terms".to_string())
+            }
+            None => Err("Filter ends unexpectedly; expected a term".to_string()),
+        }
+    }
+}
+
+/// Parse a filter expression. An empty or all-whitespace input yields
+/// [`Filter::All`].
+pub fn parse(input: &str) -> Result<Filter, String> {
+    let tokens = tokenize(input)?;
+    if tokens.is_empty() {
+        return Ok(Filter::All);
+    }
+    let mut parser = Parser { tokens, pos: 0 };
+    let filter = parser.or_expr()?;
+    match parser.peek() {
+        None => Ok(filter),
+        Some(Token::RParen) => Err("Unexpected ')' in filter".to_string()),
+        Some(token) => Err(format!("Unexpected {:?} in filter", token)),
+    }
+}
+
+/// Read an attribute the way a filter sees it: `status` defaults to
+/// `pending` and `description` to the empty string (TaskChampion's
+/// conventions for absent values), `uuid` is synthesised, and everything
+/// else is the raw stored value.
+pub fn attribute_value(task: &Task, name: &str) -> Option<String> {
+    match name {
+        "uuid" => Some(task.get_uuid().to_string()),
+        "status" => Some(task.get_value("status").unwrap_or("pending").to_string()),
+        "description" => Some(task.get_description().to_string()),
+        _ => task.get_value(name).map(str::to_string),
+    }
+}
+
+/// Compare two attribute values: finite numbers numerically and before
+/// everything else, which compares as strings. A total order, as
+/// `sort_by` requires.
+pub fn compare_values(a: &str, b: &str) -> Ordering {
+    let number = |v: &str| v.parse::<f64>().ok().filter(|x| x.is_finite());
+    match (number(a), number(b)) {
+        (Some(x), Some(y)) => x.total_cmp(&y),
+        (Some(_), None) => Ordering::Less,
+        (None, Some(_)) => Ordering::Greater,
+        (None, None) => a.cmp(b),
+    }
+}
+
+fn contains_ignore_case(haystack: &str, needle: &str) -> bool {
+    haystack.to_lowercase().contains(&needle.to_lowercase())
+}
+
+impl Filter {
+    /// True if the filter accepts no task restriction at all.
+    pub fn is_all(&self) -> bool {
+        matches!(self, Filter::All)
+    }
+
+    pub fn matches(&self, task: &Task) -> bool {
+        match self {
+            Filter::All => true,
+            Filter::And(a, b) => a.matches(task) && b.matches(task),
+            Filter::Or(a, b) => a.matches(task) || b.matches(task),
+            Filter::Tag { tag, present } => task.has_tag(tag) == *present,
+            Filter::Word(word) => {
+                contains_ignore_case(task.get_description(), word)
+                    || task.get_annotations().any(|a| contains_ignore_case(&a.description, word))
+            }
+            Filter::Attribute { name, modifier, value, date } => {
+                let actual = attribute_value(task, name);
+                match modifier {
+                    Modifier::Is => attribute_is(name, actual.as_deref(), value, *date),
+                    Modifier::Isnt => !attribute_is(name, actual.as_deref(), value, *date),
+                    Modifier::Before | Modifier::After => {
+                        let Some(actual) = actual else { return false };
+                        let ordering = match date {
+                            Some(date) => match stored_timestamp(&actual) {
+                                Some(ts) => ts.cmp(date),
+                                None => return false,
+                            },
+                            None => compare_values(&actual, value),
+                        };
+                        if *modifier == Modifier::Before {
+                            ordering == Ordering::Less
+                        } else {
+                            ordering == Ordering::Greater
+                        }
+                    }
+                    Modifier::Contains => actual.is_some_and(|a| contains_ignore_case(&a, value)),
+                    Modifier::Hasnt => !actual.is_some_and(|a| contains_ignore_case(&a, value)),
+                    Modifier::StartsWith => {
+                        actual.is_some_and(|a| a.to_lowercase().starts_with(&value.to_lowercase()))
+                    }
+                    Modifier::EndsWith => {
+                        actual.is_some_and(|a| a.to_lowercase().ends_with(&value.to_lowercase()))
+                    }
+                    Modifier::None => actual.is_none_or(|a| a.is_empty()),
+                    Modifier::Any => actual.is_some_and(|a| !a.is_empty()),
+                }
+            }
+        }
+    }
+}
+
+fn attribute_is(name: &str, actual: Option<&str>, value: &str, date: Option<DateTime<Utc>>) -> bool {
+    if value.is_empty() {
+        return actual.is_none_or(str::is_empty);
+    }
+    let Some(actual) = actual else { return false };
+    if let Some(date) = date {
+        let Some(ts) = stored_timestamp(actual) else { return false };
+        // As in TaskWarrior, `due:2026-10-16` means any time that day.
+        return if is_calendar_day(value) { ts.date_naive() == date.date_naive() } else { ts == date };
+    }
+    if name == "project" {
+        return actual == value
+            || actual.strip_prefix(value).is_some_and(|rest| rest.starts_with('.'));
+    }
+    actual == value
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use taskchampion::{Annotation, Operations, Replica, Status, StorageConfig};
+    use uuid::Uuid;
+
+    fn task_with(replica: &mut Replica, props: &[(&str, &str)], tags: &[&str]) -> Task {
+        let uuid = Uuid::new_v4();
+        let mut ops = Operations::new();
+        let mut task = replica.create_task(uuid, &mut ops).expect("Failed to create task");
+        for (k, v) in props {
+            task.set_value(*k, Some(v.to_string()), &mut ops).expect("Failed to set value");
+        }
+        for t in tags {
+            task.add_tag(&Tag::try_from(*t).unwrap(), &mut ops).expect("Failed to add tag");
+        }
+        replica.commit_operations(ops).expect("Failed to commit operations");
+        replica.get_task(uuid).unwrap().unwrap()
+    }
+
+    fn matches(filter: &str, task: &Task) -> bool {
+        parse(filter).expect("Filter did not parse").matches(task)
+    }
+
+    #[test]
+    fn test_attribute_and_tag_terms() {
+        let mut replica = Replica::new(StorageConfig::InMemory.into_storage().unwrap());
+        let task = task_with(
+            &mut replica,
+            &[("description", "Buy milk"), ("status", "pending"), ("project", "Home.Kitchen")],
+            &["errand"],
+        );
+
+        assert!(matches("", &task));
+        assert!(matches("status:pending", &task));
+        assert!(!matches("status:completed", &task));
+        assert!(matches("project:Home", &task));
+        assert!(matches("project:Home.Kitchen", &task));
+        assert!(!matches("project:Hom", &task));
+        assert!(matches("+errand -work", &task));
+        assert!(!matches("+work", &task));
+        assert!(matches("+PENDING", &task));
+        assert!(matches("description.contains:MILK", &task));
+        assert!(matches("milk", &task));
+        assert!(matches("priority:", &task));
+        assert!(matches("project.any:", &task));
+    }
+
+    #[test]
+    fn test_boolean_structure() {
+        let mut replica = Replica::new(StorageConfig::InMemory.into_storage().unwrap());
+        let task = task_with(&mut replica, &[("description", "x"), ("project", "work")], &["urgent"]);
+
+        assert!(matches("project:home or +urgent", &task));
+        assert!(!matches("project:home and +urgent", &task));
+        assert!(matches("(project:home or project:work) +urgent", &task));
+        assert!(!matches("project:work (+later or +someday)", &task));
+        // `and` binds tighter than `or`.
+        assert!(matches("+urgent or project:home and +later", &task));
+    }
+
+    #[test]
+    fn test_dates_and_annotations() {
+        let mut replica = Replica::new(StorageConfig::InMemory.into_storage().unwrap());
+        let uuid = Uuid::new_v4();
+        let mut ops = Operations::new();
+        let mut task = replica.create_task(uuid, &mut ops).unwrap();
+        task.set_status(Status::Pending, &mut ops).unwrap();
+        // 2026-10-16T12:00:00Z
+        task.set_value("due", Some("1792152000".to_string()), &mut ops).unwrap();
+        task.add_annotation(
+            Annotation { entry: Utc::now(), description: "call the plumber".to_string() },
+            &mut ops,
+        ).unwrap();
+        replica.commit_operations(ops).unwrap();
+        let task = replica.get_task(uuid).unwrap().unwrap();
+
+        assert!(matches("due.before:2026-10-17", &task));
+        assert!(matches("due.after:20261016T000000Z", &task));
+        assert!(!matches("due.before:1792152000", &task));
+        assert!(matches("due:2026-10-16T12:00:00Z", &task));
+        assert!(!matches("due:2026-10-16T00:00:00Z", &task));
+        assert!(matches("due:2026-10-16", &task));
+        assert!(!matches("due:2026-10-17", &task));
+        assert!(matches("due.isnt:2026-10-17", &task));
+        assert!(matches("plumber", &task));
+        assert!(matches("'call the'", &task));
+        assert!(!matches("wait.any:", &task));
+    }
+
+    #[test]
+    fn test_parse_errors() {
+        assert!(parse("(status:pending").is_err());
+        assert!(parse("status:pending)").is_err());
+        assert!(parse("and +work").is_err());
+        assert!(parse("+work or").is_err());
+        assert!(parse("due.before:someday").is_err());
+        assert!(parse("+has space").is_ok());
+        assert!(parse("+ALLCAPS").is_err());
+        assert!(parse("description:'unterminated").is_err());
+        assert!(parse("  ").unwrap().is_all());
+    }
+}
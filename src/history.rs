@@ -0,0 +1,292 @@
+//! JSON views of the operation journal.
+//!
+//! Each task-affecting operation becomes one JSON object with a `type` of
+//! `create`, `update` or `delete` and the task's `uuid`:
+//!
+//! - `update` adds `property`, `old_value` and `value` (either may be
+//!   `null`, meaning the property was absent / removed) and `timestamp`,
+//!   epoch seconds as a string like every other timestamp the binding
+//!   returns.
+//! - `delete` adds `old_task`, the task's key/value map before deletion.
+//! - `create` carries nothing more; TaskChampion does not timestamp
+//!   creations (the task's own `entry` usually records it).
+//!
+//! Undo points are journal markers, not changes, and have no JSON form.
+//!
+//! [`undo_preview`] summarises a batch of operations per task for display
+//! before the batch is undone.
+
+use serde_json::{json, Map, Value};
+use taskchampion::{Operation, Replica};
+use uuid::Uuid;
+
+/// The JSON form of one operation, or `None` for an undo point.
+pub fn operation_to_json(op: &Operation) -> Option<Value> {
+    Some(match op {
+        Operation::Create { uuid } => json!({
+            "type": "create",
+            "uuid": uuid.to_string(),
+        }),
+        Operation::Delete { uuid, old_task } => {
+            let old_task: Map<String, Value> = old_task
+                .iter()
+                .map(|(k, v)| (k.clone(), Value::String(v.clone())))
+                .collect();
+            json!({
+                "type": "delete",
+                "uuid": uuid.to_string(),
+                "old_task": old_task,
+            })
+        }
+        Operation::Update { uuid, property, old_value, value, timestamp } => json!({
+            "type": "update",
+            "uuid": uuid.to_string(),
+            "property": property,
+            "old_value": old_value,
+            "value": value,
+            "timestamp": timestamp.timestamp().to_string(),
+        }),
+        Operation::UndoPoint => return None,
+    })
+}
+
+/// The operations that affected a task, oldest first, as JSON strings.
+/// A UUID with no recorded operations yields an empty list.
+pub fn task_history(replica: &mut Replica, uuid: Uuid) -> Result<Vec<String>, String> {
+    let ops = replica
+        .get_task_operations(uuid)
+        .map_err(|e| format!("Failed to get task operations: {}", e))?;
+    ops.iter()
+        .filter_map(operation_to_json)
+        .map(|v| {
+            serde_json::to_string(&v).map_err(|e| format!("Failed to serialize operation to JSON: {}", e))
+        })
+        .collect()
+}
+
+/// How a property is named in a preview: tags and dependencies by their
+/// target, every annotation alike.
+fn property_label(property: &str) -> String {
+    if let Some(tag) = property.strip_prefix("tag_") {
+        format!("+{}", tag)
+    } else if property.starts_with("annotation_") {
+        "annotation".to_string()
+    } else if property.starts_with("dep_") {
+        "dependency".to_string()
+    } else {
+        property.to_string()
+    }
+}
+
+/// The description to show for a task in a preview: its current one, or
+/// failing that (it no longer exists, or has none) the latest one seen
+/// in the operations.
+fn preview_description(replica: &mut Replica, uuid: Uuid, ops: &[&Operation]) -> Result<String, String> {
+    let task = replica
+        .get_task(uuid)
+        .map_err(|e| format!("Failed to get task: {}", e))?;
+    if let Some(task) = task.filter(|t| !t.get_description().is_empty()) {
+        return Ok(task.get_description().to_string());
+    }
+    let from_ops = ops.iter().rev().find_map(|op| match op {
+        Operation::Update { property, old_value, value, .. } if property == "description" => {
+            value.clone().or_else(|| old_value.clone())
+        }
+        Operation::Delete { old_task, .. } => old_task.get("description").cloned(),
+        _ => None,
+    });
+    Ok(from_ops.unwrap_or_else(|| "untitled task".to_string()))
+}
+
+/// A short phrase for what the operations did to one task, e.g.
+/// `completed 'Buy milk'`.
+fn describe_changes(description: &str, ops: &[&Operation]) -> String {
+    if ops.iter().any(|op| matches!(op, Operation::Create { .. })) {
+        return format!("created '{}'", description);
+    }
+    if ops.iter().any(|op| matches!(op, Operation::Delete { .. })) {
+        return format!("purged '{}'", description);
+    }
+    let updates: Vec<(&String, &Option<String>, &Option<String>)> = ops
+        .iter()
+        .filter_map(|op| match op {
+            Operation::Update { property, old_value, value, .. } if property != "modified" => {
+                Some((property, old_value, value))
+            }
+            _ => None,
+        })
+        .collect();
+    if let Some((_, _, status)) = updates.iter().rev().find(|(p, _, _)| *p == "status") {
+        return match status.as_deref() {
+            Some("completed") => format!("completed '{}'", description),
+            Some("deleted") => format!("deleted '{}'", description),
+            Some("recurring") => format!("made '{}' recurring", description),
+            _ => format!("restored '{}'", description),
+        };
+    }
+    if let [(property, Some(old), _)] = updates.as_slice() {
+        if *property == "description" {
+            return format!("renamed '{}' to '{}'", old, description);
+        }
+    }
+    let mut labels: Vec<String> = Vec::new();
+    for (property, _, _) in &updates {
+        let label = property_label(property);
+        if !labels.contains(&label) {
+            labels.push(label);
+        }
+    }
+    if labels.is_empty() {
+        format!("edited '{}'", description)
+    } else {
+        format!("edited '{}' ({})", description, labels.join(", "))
+    }
+}
+
+/// Summarise the operations an undo would reverse, as a JSON document:
+///
+/// ```json
+/// {"summary": "Undo: completed 'Buy milk'",
+///  "tasks": [{"uuid": "...", "description": "Buy milk",
+///             "action": "completed 'Buy milk'",
+///             "changes": [{"property": "status", "old_value": "pending",
+///                          "value": "completed"}]}]}
+/// ```
+///
+/// Tasks appear in the order they were first touched; `changes` lists
+/// property updates other than `modified`. Returns `None` when the
+/// operations change nothing.
+pub fn undo_preview(replica: &mut Replica, ops: &[Operation]) -> Result<Option<String>, String> {
+    let mut uuids: Vec<Uuid> = Vec::new();
+    for uuid in ops.iter().filter_map(Operation::get_uuid) {
+        if !uuids.contains(&uuid) {
+            uuids.push(uuid);
+        }
+    }
+    if uuids.is_empty() {
+        return Ok(None);
+    }
+
+    let mut tasks = Vec::with_capacity(uuids.len());
+    let mut actions = Vec::with_capacity(uuids.len());
+    for uuid in uuids {
+        let task_ops: Vec<&Operation> = ops.iter().filter(|op| op.get_uuid() == Some(uuid)).collect();
+        let description = preview_description(replica, uuid, &task_ops)?;
+        let action = describe_changes(&description, &task_ops);
+        let changes: Vec<Value> = task_ops
+            .iter()
+            .filter_map(|op| match op {
+                Operation::Update { property, old_value, value, .. } if property != "modified" => Some(json!({
+                    "property": property,
+                    "old_value": old_value,
+                    "value": value,
+                })),
+                _ => None,
+            })
+            .collect();
+        tasks.push(json!({
+            "uuid": uuid.to_string(),
+            "description": description,
+            "action": action,
+            "changes": changes,
+        }));
+        actions.push(action);
+    }
+
+    let summary = match actions.as_slice() {
+        [only] => format!("Undo: {}", only),
+        [first, rest @ ..] => format!(
+            "Undo: {} and changes to {} other task{}",
+            first,
+            rest.len(),
+            if rest.len() == 1 { "" } else { "s" }
+        ),
+        [] => unreachable!("at least one task was touched"),
+    };
+    serde_json::to_string(&json!({ "summary": summary, "tasks": tasks }))
+        .map(Some)
+        .map_err(|e| format!("Failed to serialize undo preview to JSON: {}", e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use taskchampion::{Operations, StorageConfig};
+
+    #[test]
+    fn test_task_history_is_chronological() {
+        let mut replica = Replica::new(StorageConfig::InMemory.into_storage().unwrap());
+        let uuid = Uuid::new_v4();
+        let mut ops = Operations::new();
+        ops.push(Operation::UndoPoint);
+        let mut task = replica.create_task(uuid, &mut ops).unwrap();
+        task.set_description("Buy milk".to_string(), &mut ops).unwrap();
+        replica.commit_operations(ops).unwrap();
+        let mut ops = Operations::new();
+        let mut task = replica.get_task(uuid).unwrap().unwrap();
+        task.set_description("Buy oat milk".to_string(), &mut ops).unwrap();
+        replica.commit_operations(ops).unwrap();
+
+        let history: Vec<Value> = task_history(&mut replica, uuid)
+            .unwrap()
+            .iter()
+            .map(|s| serde_json::from_str(s).unwrap())
+            .collect();
+        assert_eq!(history[0], json!({"type": "create", "uuid": uuid.to_string()}));
+        let descriptions: Vec<(&Value, &Value)> = history
+            .iter()
+            .filter(|e| e["property"] == "description")
+            .map(|e| (&e["old_value"], &e["value"]))
+            .collect();
+        assert_eq!(
+            descriptions,
+            vec![(&Value::Null, &json!("Buy milk")), (&json!("Buy milk"), &json!("Buy oat milk"))]
+        );
+        assert!(history[1]["timestamp"].as_str().unwrap().parse::<i64>().is_ok());
+
+        assert!(task_history(&mut replica, Uuid::new_v4()).unwrap().is_empty());
+    }
+
+    fn preview(replica: &mut Replica) -> Option<Value> {
+        let ops = replica.get_undo_operations().unwrap();
+        undo_preview(replica, &ops).unwrap().map(|s| serde_json::from_str(&s).unwrap())
+    }
+
+    #[test]
+    fn test_undo_preview_summaries() {
+        let mut replica = Replica::new(StorageConfig::InMemory.into_storage().unwrap());
+        assert!(preview(&mut replica).is_none());
+
+        let uuid = Uuid::new_v4();
+        let mut ops = Operations::new();
+        ops.push(Operation::UndoPoint);
+        let mut task = replica.create_task(uuid, &mut ops).unwrap();
+        task.set_description("Buy milk".to_string(), &mut ops).unwrap();
+        replica.commit_operations(ops).unwrap();
+        assert_eq!(preview(&mut replica).unwrap()["summary"], "Undo: created 'Buy milk'");
+
+        let mut ops = Operations::new();
+        ops.push(Operation::UndoPoint);
+        let mut task = replica.get_task(uuid).unwrap().unwrap();
+        task.done(&mut ops).unwrap();
+        replica.commit_operations(ops).unwrap();
+        let p = preview(&mut replica).unwrap();
+        assert_eq!(p["summary"], "Undo: completed 'Buy milk'");
+        let changes = p["tasks"][0]["changes"].as_array().unwrap();
+        assert!(changes.iter().any(|c| c["property"] == "status" && c["value"] == "completed"));
+        assert!(changes.iter().all(|c| c["property"] != "modified"));
+
+        let other = Uuid::new_v4();
+        let mut ops = Operations::new();
+        ops.push(Operation::UndoPoint);
+        let mut task = replica.get_task(uuid).unwrap().unwrap();
+        task.set_description("Buy oat milk".to_string(), &mut ops).unwrap();
+        let mut task = replica.create_task(other, &mut ops).unwrap();
+        task.set_description("Call Sam".to_string(), &mut ops).unwrap();
+        replica.commit_operations(ops).unwrap();
+        assert_eq!(
+            preview(&mut replica).unwrap()["summary"],
+            "Undo: renamed 'Buy milk' to 'Buy oat milk' and changes to 1 other task"
+        );
+    }
+}
@@ -0,0 +1,602 @@
+//! iCalendar (RFC 5545) export and import of tasks as VTODO components.
+//!
+//! Task attributes map onto standard properties where iCalendar has one:
+//!
+//! | Task                     | VTODO                                      |
+//! |--------------------------|--------------------------------------------|
+//! | uuid                     | `UID`                                      |
+//! | description              | `SUMMARY`                                  |
+//! | status                   | `STATUS` (`NEEDS-ACTION`, `IN-PROCESS` once started, `COMPLETED`, `CANCELLED`) |
+//! | entry / modified         | `CREATED` / `LAST-MODIFIED` (and `DTSTAMP`) |
+//! | due / scheduled          | `DUE` / `DTSTART`                          |
+//! | end of a completed task  | `COMPLETED`                                |
+//! | priority H / M / L       | `PRIORITY` 1 / 5 / 9                       |
+//! | tags                     | `CATEGORIES`                               |
+//! | annotations              | `COMMENT;X-ENTRY=<date>`                   |
+//! | dependencies             | `RELATED-TO;RELTYPE=DEPENDS-ON`            |
+//! | recurrence template      | `RRULE`, when the period has an equivalent |
+//!
+//! Every other attribute — project, UDAs, and `recur`, `until` and the
+//! like, whose iCalendar forms are lossy — is written as
+//! `X-TASKCHAMPION-UDA;X-NAME="<key>":<value>` with the stored value, so
+//! importing our own export reproduces each task exactly.
+//!
+//! Import also reads VTODOs written by other applications: a `UID` that
+//! is not a UUID is mapped to a stable name-based UUID, so importing the
+//! same calendar again updates rather than duplicates; `DESCRIPTION`
+//! becomes an annotation, and an `RRULE` on a task with a due date makes
+//! it a recurrence template. Times with a `TZID` or no zone are read as
+//! UTC. Other components (VEVENT, VALARM, ...) are ignored.
+
+use crate::dates::stored_timestamp;
+use crate::filter::Filter;
+use crate::jni_bindings::{exported_tasks, raw_taskmap};
+use crate::recurrence::{parse_period, Period};
+use crate::taskwarrior::{format_date, ImportedTask};
+use chrono::{DateTime, Duration, NaiveDate, NaiveDateTime, Utc};
+use std::collections::HashMap;
+use taskchampion::{Replica, Tag, Task};
+use uuid::Uuid;
+
+/// Longest content line, in octets, before it is folded.
+const MAX_LINE_OCTETS: usize = 75;
+
+/// Stored attributes with a standard VTODO property.
+const MAPPED: &[&str] = &["description", "status", "entry", "modified", "due", "scheduled"];
+
+/// Escape a TEXT value.
+fn escape(value: &str) -> String {
+    let mut out = String::with_capacity(value.len());
+    for c in value.chars() {
+        match c {
+            '\\' => out.push_str("\\\\"),
+            ';' => out.push_str("\\;"),
+            ',' => out.push_str("\\,"),
+            '\n' => out.push_str("\\n"),
+            '\r' => {}
+            c => out.push(c),
+        }
+    }
+    out
+}
+
+/// Undo [`escape`].
+fn unescape(value: &str) -> String {
+    let mut out = String::with_capacity(value.len());
+    let mut chars = value.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            out.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some('n') | Some('N') => out.push('\n'),
+            Some(other) => out.push(other),
+            None => out.push('\\'),
+        }
+    }
+    out
+}
+
+/// Split a list value on its unescaped commas.
+fn split_list(value: &str) -> Vec<String> {
+    let mut items = Vec::new();
+    let mut current = String::new();
+    let mut escaped = false;
+    for c in value.chars() {
+        if escaped {
+            current.push('\\');
+            current.push(c);
+            escaped = false;
+        } else if c == '\\' {
+            escaped = true;
+        } else if c == ',' {
+            items.push(unescape(&current));
+            current.clear();
+        } else {
+            current.push(c);
+        }
+    }
+    items.push(unescape(&current));
+    items.into_iter().filter(|i| !i.is_empty()).collect()
+}
+
+/// Append one content line, folded at [`MAX_LINE_OCTETS`].
+fn push_line(out: &mut String, line: &str) {
+    let mut start = 0;
+    let mut limit = MAX_LINE_OCTETS;
+    while line.len() - start > limit {
+        let mut end = start + limit;
+        while !line.is_char_boundary(end) {
+            end -= 1;
+        }
+        out.push_str(&line[start..end]);
+        out.push_str("\r\n ");
+        start = end;
+        // The leading space of a continuation counts towards its length.
+        limit = MAX_LINE_OCTETS - 1;
+    }
+    out.push_str(&line[start..]);
+    out.push_str("\r\n");
+}
+
+fn stored_date(task: &Task, key: &str) -> Option<DateTime<Utc>> {
+    task.get_value(key).and_then(stored_timestamp)
+}
+
+/// The `RRULE` for a TaskWarrior period, if iCalendar can express it.
+fn rrule(recur: &str, until: Option<DateTime<Utc>>) -> Option<String> {
+    let rule = match parse_period(recur).ok()? {
+        Period::Weekdays => "FREQ=WEEKLY;BYDAY=MO,TU,WE,TH,FR".to_string(),
+        Period::Span { months, seconds: 0 } if months % 12 == 0 => format!("FREQ=YEARLY;INTERVAL={}", months / 12),
+        Period::Span { months, seconds: 0 } => format!("FREQ=MONTHLY;INTERVAL={}", months),
+        Period::Span { months: 0, seconds } => {
+            let (freq, unit) = [("WEEKLY", 604_800), ("DAILY", 86_400), ("HOURLY", 3_600), ("MINUTELY", 60), ("SECONDLY", 1)]
+                .into_iter()
+                .find(|(_, unit)| seconds % unit == 0)?;
+            format!("FREQ={};INTERVAL={}", freq, seconds / unit)
+        }
+        Period::Span { .. } => return None,
+    };
+    Some(match until {
+        Some(until) => format!("{};UNTIL={}", rule, format_date(until)),
+        None => rule,
+    })
+}
+
+/// The TaskWarrior period for an `RRULE`, if it is one this binding can
+/// generate.
+fn recur_from_rrule(rule: &str) -> Option<(String, Option<DateTime<Utc>>)> {
+    let parts: HashMap<String, String> = rule
+        .split(';')
+        .filter_map(|part| part.split_once('='))
+        .map(|(k, v)| (k.to_ascii_uppercase(), v.to_string()))
+        .collect();
+    let interval: u32 = parts.get("INTERVAL").map_or(Some(1), |i| i.parse().ok())?;
+    let until = parts.get("UNTIL").and_then(|u| parse_date(u, false));
+    let weekdays = parts.get("BYDAY").is_some_and(|d| d == "MO,TU,WE,TH,FR");
+    let recur = match parts.get("FREQ")?.as_str() {
+        "WEEKLY" if weekdays && interval == 1 => "weekdays".to_string(),
+        _ if parts.contains_key("BYDAY") || parts.contains_key("COUNT") => return None,
+        "YEARLY" => format!("{}y", interval),
+        "MONTHLY" => format!("{}mo", interval),
+        "WEEKLY" => format!("{}w", interval),
+        "DAILY" => format!("{}d", interval),
+        "HOURLY" => format!("{}h", interval),
+        "MINUTELY" => format!("{}min", interval),
+        "SECONDLY" => format!("{}s", interval),
+        _ => return None,
+    };
+    Some((recur, until))
+}
+
+/// Append the VTODO for one task.
+fn export_vtodo(task: &Task, now: DateTime<Utc>, out: &mut String) {
+    push_line(out, "BEGIN:VTODO");
+    push_line(out, &format!("UID:{}", task.get_uuid()));
+    let stamp = stored_date(task, "modified").or_else(|| stored_date(task, "entry")).unwrap_or(now);
+    push_line(out, &format!("DTSTAMP:{}", format_date(stamp)));
+    push_line(out, &format!("SUMMARY:{}", escape(task.get_description())));
+
+    let status = task.get_value("status").unwrap_or("pending");
+    let ical_status = match status {
+        "completed" => "COMPLETED",
+        "deleted" => "CANCELLED",
+        _ if task.get_value("start").is_some() => "IN-PROCESS",
+        _ => "NEEDS-ACTION",
+    };
+    push_line(out, &format!("STATUS:{}", ical_status));
+    for (key, property) in [("entry", "CREATED"), ("modified", "LAST-MODIFIED"), ("due", "DUE"), ("scheduled", "DTSTART")] {
+        if let Some(date) = stored_date(task, key) {
+            push_line(out, &format!("{}:{}", property, format_date(date)));
+        }
+    }
+    let end = stored_date(task, "end");
+    if let (Some(end), "completed") = (end, status) {
+        push_line(out, &format!("COMPLETED:{}", format_date(end)));
+    }
+    let priority = match task.get_value("priority") {
+        Some("H") => Some(1),
+        Some("M") => Some(5),
+        Some("L") => Some(9),
+        _ => None,
+    };
+    if let Some(priority) = priority {
+        push_line(out, &format!("PRIORITY:{}", priority));
+    }
+    if status == "recurring" {
+        if let Some(rule) = task.get_value("recur").and_then(|r| rrule(r, stored_date(task, "until"))) {
+            push_line(out, &format!("RRULE:{}", rule));
+        }
+    }
+
+    let tags: Vec<String> = task.get_tags().filter(|t| t.is_user()).map(|t| escape(t.as_ref())).collect();
+    if !tags.is_empty() {
+        push_line(out, &format!("CATEGORIES:{}", tags.join(",")));
+    }
+    for annotation in task.get_annotations() {
+        push_line(
+            out,
+            &format!("COMMENT;X-ENTRY={}:{}", format_date(annotation.entry), escape(&annotation.description)),
+        );
+    }
+    for dep in task.get_dependencies() {
+        push_line(out, &format!("RELATED-TO;RELTYPE=DEPENDS-ON:{}", dep));
+    }
+
+    let taskmap = raw_taskmap(task);
+    let mut extra: Vec<(&String, &String)> = taskmap
+        .iter()
+        .filter(|(key, _)| {
+            // iCalendar has no status for a recurrence template.
+            (!MAPPED.contains(&key.as_str()) || (key.as_str() == "status" && status == "recurring"))
+                && !key.starts_with("tag_")
+                && !key.starts_with("annotation_")
+                && !key.starts_with("dep_")
+                && !(key.as_str() == "priority" && priority.is_some())
+                && !(key.as_str() == "end" && status == "completed" && end.is_some())
+        })
+        .collect();
+    extra.sort();
+    for (key, value) in extra {
+        // DQUOTE cannot appear in a quoted parameter value at all.
+        let name = key.replace('"', "'");
+        push_line(out, &format!("X-TASKCHAMPION-UDA;X-NAME=\"{}\":{}", name, escape(value)));
+    }
+    push_line(out, "END:VTODO");
+}
+
+/// Export the tasks matching `filter` as one iCalendar document,
+/// ordered by entry date, then UUID.
+pub fn export(replica: &mut Replica, filter: &Filter) -> Result<String, String> {
+    let tasks = exported_tasks(replica, filter)?;
+    let now = Utc::now();
+    let mut out = String::new();
+    push_line(&mut out, "BEGIN:VCALENDAR");
+    push_line(&mut out, "VERSION:2.0");
+    push_line(&mut out, "PRODID:-//taskchampion-jni//EN");
+    for task in &tasks {
+        export_vtodo(task, now, &mut out);
+    }
+    push_line(&mut out, "END:VCALENDAR");
+    Ok(out)
+}
+
+/// One content line: name (upper-cased), parameters and raw value.
+#[derive(Debug, Clone, PartialEq)]
+struct Property {
+    name: String,
+    params: Vec<(String, String)>,
+    value: String,
+}
+
+impl Property {
+    fn param(&self, name: &str) -> Option<&str> {
+        self.params.iter().find(|(k, _)| k == name).map(|(_, v)| v.as_str())
+    }
+}
+
+fn parse_property(line: &str) -> Option<Property> {
+    // The value starts at the first colon outside a quoted parameter.
+    let mut in_quotes = false;
+    let colon = line.char_indices().find(|&(_, c)| {
+        if c == '"' {
+            in_quotes = !in_quotes;
+        }
+        c == ':' && !in_quotes
+    })?;
+    let (head, value) = (&line[..colon.0], &line[colon.0 + 1..]);
+    let mut parts = Vec::new();
+    let mut current = String::new();
+    in_quotes = false;
+    for c in head.chars() {
+        match c {
+            '"' => in_quotes = !in_quotes,
+            ';' if !in_quotes => parts.push(std::mem::take(&mut current)),
+            c => current.push(c),
+        }
+    }
+    parts.push(current);
+    let mut parts = parts.into_iter();
+    let name = parts.next()?.to_ascii_uppercase();
+    let params = parts
+        .filter_map(|p| p.split_once('=').map(|(k, v)| (k.to_ascii_uppercase(), v.to_string())))
+        .collect();
+    Some(Property { name, params, value: value.to_string() })
+}
+
+/// The properties of each VTODO in a document, nested components
+/// (VALARM) excluded.
+fn parse_vtodos(text: &str) -> Result<Vec<Vec<Property>>, String> {
+    let mut lines: Vec<String> = Vec::new();
+    // Files saved by some Windows tools start with a byte order mark.
+    let text = text.strip_prefix('\u{feff}').unwrap_or(text);
+    for raw in text.split('\n') {
+        let raw = raw.strip_suffix('\r').unwrap_or(raw);
+        match (raw.strip_prefix([' ', '\t']), lines.last_mut()) {
+            (Some(continuation), Some(last)) => last.push_str(continuation),
+            _ if raw.is_empty() => {}
+            _ => lines.push(raw.to_string()),
+        }
+    }
+    if !lines.first().is_some_and(|l| l.eq_ignore_ascii_case("BEGIN:VCALENDAR")) {
+        return Err("Invalid iCalendar document: it does not start with BEGIN:VCALENDAR".to_string());
+    }
+
+    let mut vtodos = Vec::new();
+    let mut current: Option<Vec<Property>> = None;
+    let mut depth_in_vtodo = 0;
+    for line in &lines {
+        let Some(property) = parse_property(line) else { continue };
+        match (property.name.as_str(), property.value.to_ascii_uppercase().as_str()) {
+            ("BEGIN", "VTODO") if current.is_none() => current = Some(Vec::new()),
+            ("BEGIN", _) if current.is_some() => depth_in_vtodo += 1,
+            ("END", "VTODO") if depth_in_vtodo == 0 => vtodos.extend(current.take()),
+            ("END", _) if current.is_some() => depth_in_vtodo -= 1,
+            _ => {
+                if let (Some(vtodo), 0) = (current.as_mut(), depth_in_vtodo) {
+                    vtodo.push(property);
+                }
+            }
+        }
+    }
+    Ok(vtodos)
+}
+
+/// Parse a DATE or DATE-TIME value; a time without `Z` is read as UTC.
+fn parse_date(value: &str, date_only: bool) -> Option<DateTime<Utc>> {
+    let value = value.trim();
+    if date_only || value.len() == 8 {
+        return NaiveDate::parse_from_str(value, "%Y%m%d").ok().map(|d| d.and_hms_opt(0, 0, 0).unwrap().and_utc());
+    }
+    NaiveDateTime::parse_from_str(value.trim_end_matches('Z'), "%Y%m%dT%H%M%S")
+        .ok()
+        .map(|dt| dt.and_utc())
+}
+
+/// The task a UID names: the UID itself if it is a UUID, else a stable
+/// UUID derived from it.
+fn uid_to_uuid(uid: &str) -> Uuid {
+    Uuid::parse_str(uid).unwrap_or_else(|_| Uuid::new_v5(&Uuid::NAMESPACE_URL, format!("ical:{}", uid).as_bytes()))
+}
+
+/// The TaskChampion key/value map for one VTODO.
+fn vtodo_taskmap(properties: &[Property]) -> ImportedTask {
+    let uid = properties.iter().find(|p| p.name == "UID").ok_or("missing UID")?;
+    let uuid = uid_to_uuid(uid.value.trim());
+    let mut taskmap: HashMap<String, String> = HashMap::new();
+    let mut rrule = None;
+    let mut comments = Vec::new();
+
+    for property in properties {
+        let date = || {
+            parse_date(&property.value, property.param("VALUE") == Some("DATE"))
+                .map(|d| d.timestamp().to_string())
+                .ok_or_else(|| format!("invalid {} '{}'", property.name, property.value))
+        };
+        match property.name.as_str() {
+            "SUMMARY" => {
+                taskmap.insert("description".to_string(), unescape(&property.value));
+            }
+            "STATUS" => {
+                let status = match property.value.to_ascii_uppercase().as_str() {
+                    "COMPLETED" => "completed",
+                    "CANCELLED" => "deleted",
+                    _ => "pending",
+                };
+                taskmap.insert("status".to_string(), status.to_string());
+            }
+            "CREATED" => drop(taskmap.insert("entry".to_string(), date()?)),
+            "LAST-MODIFIED" => drop(taskmap.insert("modified".to_string(), date()?)),
+            "DUE" => drop(taskmap.insert("due".to_string(), date()?)),
+            "DTSTART" => drop(taskmap.insert("scheduled".to_string(), date()?)),
+            "COMPLETED" => drop(taskmap.insert("end".to_string(), date()?)),
+            "PRIORITY" => {
+                let priority = match property.value.trim().parse::<u8>() {
+                    Ok(1..=4) => Some("H"),
+                    Ok(5) => Some("M"),
+                    Ok(6..=9) => Some("L"),
+                    _ => None,
+                };
+                if let Some(priority) = priority {
+                    taskmap.insert("priority".to_string(), priority.to_string());
+                }
+            }
+            "CATEGORIES" => {
+                for tag in split_list(&property.value) {
+                    Tag::try_from(tag.as_str()).map_err(|e| format!("invalid tag '{}': {}", tag, e))?;
+                    taskmap.insert(format!("tag_{}", tag), String::new());
+                }
+            }
+            "RELATED-TO" if property.param("RELTYPE").is_some_and(|t| t.eq_ignore_ascii_case("DEPENDS-ON")) => {
+                taskmap.insert(format!("dep_{}", uid_to_uuid(property.value.trim())), String::new());
+            }
+            "COMMENT" | "DESCRIPTION" => {
+                let entry = property.param("X-ENTRY").and_then(|e| parse_date(e, false));
+                comments.push((entry, unescape(&property.value)));
+            }
+            "RRULE" => rrule = Some(property.value.clone()),
+            "X-TASKCHAMPION-UDA" => {
+                let key = property.param("X-NAME").ok_or("X-TASKCHAMPION-UDA without X-NAME")?;
+                taskmap.insert(uda_key(key)?, unescape(&property.value));
+            }
+            _ => {}
+        }
+    }
+    if taskmap.get("description").is_none_or(|d| d.trim().is_empty()) {
+        return Err("missing SUMMARY".to_string());
+    }
+    let status = taskmap.entry("status".to_string()).or_insert_with(|| "pending".to_string());
+    if let Some((recur, until)) = rrule.as_deref().and_then(recur_from_rrule) {
+        if status == "pending" && taskmap.contains_key("due") {
+            taskmap.insert("status".to_string(), "recurring".to_string());
+            taskmap.entry("recur".to_string()).or_insert(recur);
+            if let Some(until) = until {
+                taskmap.entry("until".to_string()).or_insert_with(|| until.timestamp().to_string());
+            }
+        }
+    }
+    // Comments without an entry date (e.g. a DESCRIPTION) are stamped
+    // from the task's creation, a second apart to keep them distinct.
+    let base = taskmap.get("entry").and_then(|e| stored_timestamp(e)).unwrap_or_else(Utc::now);
+    for (i, (entry, text)) in comments.into_iter().enumerate() {
+        let mut entry = entry.unwrap_or(base + Duration::seconds(i as i64));
+        while taskmap.contains_key(&format!("annotation_{}", entry.timestamp())) {
+            entry += Duration::seconds(1);
+        }
+        taskmap.insert(format!("annotation_{}", entry.timestamp()), text);
+    }
+    Ok((uuid, taskmap))
+}
+
+/// The key an `X-TASKCHAMPION-UDA` property stores under. Tags,
+/// dependencies and annotations have properties of their own, so a key
+/// in their namespaces is held to the same checks as those properties.
+fn uda_key(key: &str) -> Result<String, String> {
+    if let Some(tag) = key.strip_prefix("tag_") {
+        Tag::try_from(tag).map_err(|e| format!("invalid tag '{}': {}", tag, e))?;
+    } else if let Some(dep) = key.strip_prefix("dep_") {
+        let dep = Uuid::parse_str(dep).map_err(|_| format!("invalid dependency '{}'", dep))?;
+        return Ok(format!("dep_{}", dep));
+    } else if let Some(entry) = key.strip_prefix("annotation_") {
+        entry
+            .parse::<i64>()
+            .ok()
+            .and_then(|e| DateTime::from_timestamp(e, 0))
+            .ok_or_else(|| format!("invalid annotation key '{}'", key))?;
+    }
+    Ok(key.to_string())
+}
+
+/// Read every VTODO in `text`, each with its UID for the import report,
+/// ready for [`crate::taskwarrior::store_imported`].
+pub fn parse(text: &str) -> Result<Vec<(Option<String>, ImportedTask)>, String> {
+    let vtodos = parse_vtodos(text)?;
+    Ok(vtodos
+        .iter()
+        .map(|properties| {
+            let uid = properties.iter().find(|p| p.name == "UID").map(|p| p.value.clone());
+            (uid, vtodo_taskmap(properties))
+        })
+        .collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::taskwarrior::{store_imported, ImportReport};
+    use taskchampion::{Annotation, Operations, Status, StorageConfig};
+
+    fn import(replica: &mut Replica, text: &str, ops: &mut Operations) -> Result<ImportReport, String> {
+        store_imported(replica, parse(text)?, ops)
+    }
+
+    #[test]
+    fn test_ics_round_trip() {
+        let mut replica = Replica::new(StorageConfig::InMemory.into_storage().unwrap());
+        let blocker = Uuid::new_v4();
+        let uuid = Uuid::new_v4();
+        let template = Uuid::new_v4();
+        let mut ops = Operations::new();
+        let mut task = replica.create_task(blocker, &mut ops).unwrap();
+        task.set_description("Buy paint".to_string(), &mut ops).unwrap();
+        task.done(&mut ops).unwrap();
+        let mut task = replica.create_task(uuid, &mut ops).unwrap();
+        task.set_description("Paint fence; then, rest\nwell".to_string(), &mut ops).unwrap();
+        task.set_status(Status::Pending, &mut ops).unwrap();
+        task.set_value("due", Some("1792454400".to_string()), &mut ops).unwrap();
+        task.set_value("priority", Some("H".to_string()), &mut ops).unwrap();
+        task.set_value("project", Some("Home".to_string()), &mut ops).unwrap();
+        task.set_value("wait", Some("1792000000".to_string()), &mut ops).unwrap();
+        task.add_tag(&Tag::try_from("diy").unwrap(), &mut ops).unwrap();
+        task.add_annotation(
+            Annotation { entry: DateTime::from_timestamp(1_760_619_600, 0).unwrap(), description: "Green ".repeat(20) },
+            &mut ops,
+        )
+        .unwrap();
+        task.add_dependency(blocker, &mut ops).unwrap();
+        let mut task = replica.create_task(template, &mut ops).unwrap();
+        task.set_description("Water plants".to_string(), &mut ops).unwrap();
+        task.set_status(Status::Recurring, &mut ops).unwrap();
+        task.set_value("recur", Some("weekly".to_string()), &mut ops).unwrap();
+        task.set_value("due", Some("1760616000".to_string()), &mut ops).unwrap();
+        replica.commit_operations(ops).unwrap();
+
+        let ics = export(&mut replica, &Filter::All).unwrap();
+        assert!(ics.lines().all(|l| l.len() <= MAX_LINE_OCTETS + 1));
+        assert!(ics.contains("SUMMARY:Paint fence\\; then\\, rest\\nwell\r\n"));
+        assert!(ics.contains("PRIORITY:1\r\n"));
+        assert!(ics.contains("CATEGORIES:diy\r\n"));
+        assert!(ics.contains("RRULE:FREQ=WEEKLY;INTERVAL=1\r\n"));
+        assert!(ics.contains("STATUS:COMPLETED\r\n"));
+        assert!(ics.contains("X-TASKCHAMPION-UDA;X-NAME=\"project\":Home\r\n"));
+
+        let before = replica.all_task_data().unwrap();
+        let mut fresh = Replica::new(StorageConfig::InMemory.into_storage().unwrap());
+        let mut ops = Operations::new();
+        let report = import(&mut fresh, &ics, &mut ops).unwrap();
+        fresh.commit_operations(ops).unwrap();
+        assert_eq!(report.created.len(), 3);
+        for (uuid, data) in before {
+            let imported = fresh.get_task_data(uuid).unwrap().unwrap();
+            let mut expected: Vec<_> = data.iter().collect();
+            let mut actual: Vec<_> = imported.iter().collect();
+            expected.sort();
+            actual.sort();
+            assert_eq!(actual, expected);
+        }
+
+        // Importing our own output again changes nothing.
+        let mut ops = Operations::new();
+        let report = import(&mut fresh, &ics, &mut ops).unwrap();
+        assert_eq!(report.skipped.len(), 3);
+    }
+
+    #[test]
+    fn test_import_foreign_vtodo() {
+        let ics = "\u{feff}BEGIN:VCALENDAR\r\nVERSION:2.0\r\nBEGIN:VTODO\r\nUID:abc@example.com\r\nSUMMARY:File \r\n taxes\r\n\
+                   DUE;VALUE=DATE:20270415\r\nPRIORITY:3\r\nDESCRIPTION:Use the\\nnew form\r\nBEGIN:VALARM\r\n\
+                   SUMMARY:Reminder\r\nEND:VALARM\r\nRRULE:FREQ=YEARLY\r\nEND:VTODO\r\nBEGIN:VTODO\r\n\
+                   UID:no-summary\r\nEND:VTODO\r\nEND:VCALENDAR\r\n";
+        let mut replica = Replica::new(StorageConfig::InMemory.into_storage().unwrap());
+        let mut ops = Operations::new();
+        let report = import(&mut replica, ics, &mut ops).unwrap();
+        replica.commit_operations(ops).unwrap();
+        assert_eq!(report.created, vec![uid_to_uuid("abc@example.com")]);
+        assert_eq!(report.skipped[0].2, "missing SUMMARY");
+
+        let task = replica.get_task_data(report.created[0]).unwrap().unwrap();
+        assert_eq!(task.get("description"), Some("File taxes"));
+        assert_eq!(task.get("due"), Some("1807747200"));
+        assert_eq!(task.get("priority"), Some("H"));
+        assert_eq!(task.get("status"), Some("recurring"));
+        assert_eq!(task.get("recur"), Some("1y"));
+        assert!(task.iter().any(|(k, v)| k.starts_with("annotation_") && v == "Use the\nnew form"));
+    }
+
+    #[test]
+    fn test_import_reserved_uda_keys() {
+        let vtodo = |uid: &str, key: &str| {
+            format!("BEGIN:VTODO\r\nUID:{}\r\nSUMMARY:Paint\r\nX-TASKCHAMPION-UDA;X-NAME=\"{}\":\r\nEND:VTODO\r\n", uid, key)
+        };
+        let dep = Uuid::new_v4();
+        let ics = format!(
+            "BEGIN:VCALENDAR\r\n{}{}{}{}END:VCALENDAR\r\n",
+            vtodo("a", "dep_garbage"),
+            vtodo("b", "tag_two words"),
+            vtodo("c", "annotation_yesterday"),
+            vtodo("d", &format!("dep_{}", dep.simple())),
+        );
+        let mut replica = Replica::new(StorageConfig::InMemory.into_storage().unwrap());
+        let mut ops = Operations::new();
+        let report = import(&mut replica, &ics, &mut ops).unwrap();
+        replica.commit_operations(ops).unwrap();
+        let reasons: Vec<&str> = report.skipped.iter().map(|(_, _, r)| r.as_str()).collect();
+        assert_eq!(reasons.len(), 3);
+        assert_eq!(reasons[0], "invalid dependency 'garbage'");
+        assert!(reasons[1].starts_with("invalid tag 'two words'"));
+        assert_eq!(reasons[2], "invalid annotation key 'annotation_yesterday'");
+        let task = replica.get_task_data(report.created[0]).unwrap().unwrap();
+        assert!(task.has(format!("dep_{}", dep)));
+    }
+}
@@ -0,0 +1,278 @@
+//! Integrity checks, and repairs, of a data directory's database.
+//!
+//! The check reads `taskchampion.sqlite3` through [`crate::storage`]
+//! rather than through TaskChampion, so it works on a database
+//! TaskChampion refuses to open, as long as its schema is one the
+//! binding supports.
+//! It runs SQLite's `PRAGMA integrity_check`, then looks for damage
+//! TaskChampion would trip over:
+//!
+//! - `unreadable_task`: a task row whose UUID or data does not decode
+//! - `invalid_status`: a status other than pending, completed, deleted
+//!   or recurring
+//! - `invalid_tag`, `invalid_annotation`, `invalid_dependency`: a
+//!   `tag_`, `annotation_` or `dep_` key that does not name a valid tag,
+//!   epoch timestamp or UUID
+//! - `orphan_working_set`: a working-set entry whose task does not exist
+//!
+//! Repair fixes what it can in one transaction: unreadable tasks and
+//! orphan working-set entries are deleted, and invalid keys, an invalid
+//! status among them, removed; a task without a status reads as pending.
+//! SQLite-level damage is only ever repaired by `REINDEX`, which rebuilds
+//! corrupt indexes; anything else needs a backup. Repairs edit local
+//! storage directly, bypassing the operation journal, so they are local
+//! to this replica: nothing is synced, and a later sync may bring back
+//! values other replicas still hold.
+
+use crate::storage::{self, DB_FILE};
+use rusqlite::Connection;
+use serde_json::{json, Value};
+use std::collections::{HashMap, HashSet};
+use std::path::Path;
+use taskchampion::Tag;
+use uuid::Uuid;
+
+const STATUSES: &[&str] = &["pending", "completed", "deleted", "recurring"];
+
+/// Most SQLite messages the report carries; `integrity_check` stops there.
+const MAX_SQLITE_ERRORS: u32 = 100;
+
+/// One problem found.
+#[derive(Debug)]
+pub struct Issue {
+    pub kind: &'static str,
+    pub uuid: Option<String>,
+    /// The offending key, or the working-set id of an orphan entry.
+    pub key: Option<String>,
+    pub detail: String,
+    pub repaired: bool,
+}
+
+/// What a check found, and what repair fixed.
+#[derive(Debug, Default)]
+pub struct IntegrityReport {
+    /// Messages from SQLite's integrity check; empty when it passes.
+    pub sqlite: Vec<String>,
+    /// Whether a `REINDEX` cleared the SQLite messages.
+    pub sqlite_repaired: bool,
+    pub issues: Vec<Issue>,
+}
+
+impl IntegrityReport {
+    /// Whether nothing unrepaired remains.
+    pub fn ok(&self) -> bool {
+        (self.sqlite.is_empty() || self.sqlite_repaired) && self.issues.iter().all(|i| i.repaired)
+    }
+
+    /// Whether repair changed the database.
+    pub fn changed(&self) -> bool {
+        self.sqlite_repaired || self.issues.iter().any(|i| i.repaired)
+    }
+
+    /// The report as a JSON document:
+    /// `{"ok": bool, "sqlite": ["..."], "sqliteRepaired": bool,
+    ///   "issues": [{"kind", "uuid", "key", "detail", "repaired"}]}`.
+    pub fn to_json(&self) -> Result<String, String> {
+        let issues: Vec<Value> = self
+            .issues
+            .iter()
+            .map(|i| json!({ "kind": i.kind, "uuid": i.uuid, "key": i.key, "detail": i.detail, "repaired": i.repaired }))
+            .collect();
+        let report = json!({
+            "ok": self.ok(),
+            "sqlite": self.sqlite,
+            "sqliteRepaired": self.sqlite_repaired,
+            "issues": issues,
+        });
+        serde_json::to_string(&report).map_err(|e| format!("Failed to serialize integrity report to JSON: {}", e))
+    }
+}
+
+fn sqlite_errors(conn: &Connection) -> Result<Vec<String>, String> {
+    let mut stmt = conn
+        .prepare(&format!("PRAGMA integrity_check({})", MAX_SQLITE_ERRORS))
+        .map_err(|e| format!("Failed to run integrity check: {}", e))?;
+    let rows = stmt
+        .query_map([], |row| row.get::<_, String>(0))
+        .map_err(|e| format!("Failed to run integrity check: {}", e))?;
+    let messages = rows
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| format!("Failed to run integrity check: {}", e))?;
+    Ok(messages.into_iter().filter(|m| m != "ok").collect())
+}
+
+/// The issues in one task's key/value map.
+fn task_issues(uuid: &str, taskmap: &HashMap<String, String>) -> Vec<Issue> {
+    let issue = |kind, key: &str, detail: String| Issue {
+        kind,
+        uuid: Some(uuid.to_string()),
+        key: Some(key.to_string()),
+        detail,
+        repaired: false,
+    };
+    let mut issues = Vec::new();
+    let mut keys: Vec<&String> = taskmap.keys().collect();
+    keys.sort();
+    for key in keys {
+        if key == "status" && !STATUSES.contains(&taskmap[key].as_str()) {
+            issues.push(issue("invalid_status", key, format!("unknown status '{}'", taskmap[key])));
+        } else if let Some(tag) = key.strip_prefix("tag_") {
+            if let Err(e) = Tag::try_from(tag) {
+                issues.push(issue("invalid_tag", key, format!("invalid tag '{}': {}", tag, e)));
+            }
+        } else if let Some(entry) = key.strip_prefix("annotation_") {
+            if entry.parse::<i64>().is_err() {
+                issues.push(issue("invalid_annotation", key, format!("annotation entry '{}' is not a timestamp", entry)));
+            }
+        } else if let Some(dep) = key.strip_prefix("dep_") {
+            if Uuid::parse_str(dep).is_err() {
+                issues.push(issue("invalid_dependency", key, format!("dependency '{}' is not a UUID", dep)));
+            }
+        }
+    }
+    issues
+}
+
+/// Check the database in `data_dir`, opened read-only.
+pub fn check(data_dir: &Path) -> Result<IntegrityReport, String> {
+    let path = data_dir.join(DB_FILE);
+    if !path.is_file() {
+        return Err(format!("No database in {}", data_dir.display()));
+    }
+    inspect(&storage::open_read_only(&path)?)
+}
+
+fn inspect(conn: &Connection) -> Result<IntegrityReport, String> {
+    let mut report = IntegrityReport { sqlite: sqlite_errors(conn)?, ..Default::default() };
+
+    let mut uuids = HashSet::new();
+    for (uuid, data) in storage::task_rows(conn)? {
+        let unreadable = |detail: String| Issue { kind: "unreadable_task", uuid: uuid.clone(), key: None, detail, repaired: false };
+        let Some(uuid) = uuid.clone().filter(|u| Uuid::parse_str(u).is_ok()) else {
+            report.issues.push(unreadable(format!("'{}' is not a UUID", uuid.clone().unwrap_or_default())));
+            continue;
+        };
+        match serde_json::from_str::<HashMap<String, String>>(data.as_deref().unwrap_or("")) {
+            Ok(taskmap) => report.issues.extend(task_issues(&uuid, &taskmap)),
+            Err(e) => {
+                report.issues.push(unreadable(format!("task data does not decode: {}", e)));
+                continue;
+            }
+        }
+        uuids.insert(uuid);
+    }
+
+    for (id, uuid) in storage::working_set_rows(conn)? {
+        let uuid = uuid.unwrap_or_default();
+        if !uuids.contains(&uuid) {
+            report.issues.push(Issue {
+                kind: "orphan_working_set",
+                uuid: Some(uuid),
+                key: Some(id.to_string()),
+                detail: format!("working-set entry {} names a task that does not exist", id),
+                repaired: false,
+            });
+        }
+    }
+    Ok(report)
+}
+
+/// Check the database in `data_dir` and repair what can be repaired, in
+/// one transaction. No Replica may be open on `data_dir` meanwhile.
+pub fn repair(data_dir: &Path) -> Result<IntegrityReport, String> {
+    let mut conn = storage::open(data_dir)?;
+    let mut report = inspect(&conn)?;
+
+    if !report.sqlite.is_empty() {
+        conn.execute_batch("REINDEX").map_err(|e| format!("Failed to reindex: {}", e))?;
+        report.sqlite_repaired = sqlite_errors(&conn)?.is_empty();
+    }
+
+    let tx = conn.transaction().map_err(|e| format!("Failed to start repair: {}", e))?;
+    let mut taskmaps: HashMap<String, HashMap<String, String>> = HashMap::new();
+    for issue in &mut report.issues {
+        let uuid = issue.uuid.clone().unwrap_or_default();
+        match issue.kind {
+            "unreadable_task" => {
+                storage::delete_task(&tx, issue.uuid.as_deref())?;
+            }
+            "orphan_working_set" => {
+                let id = issue.key.as_deref().and_then(|k| k.parse().ok()).expect("orphan entries carry their id");
+                storage::delete_working_set_entry(&tx, id)?;
+            }
+            _ => {
+                if !taskmaps.contains_key(&uuid) {
+                    let data = storage::task_data(&tx, &uuid)?;
+                    let taskmap = serde_json::from_str(&data).map_err(|e| format!("Failed to decode task {}: {}", uuid, e))?;
+                    taskmaps.insert(uuid.clone(), taskmap);
+                }
+                let taskmap = taskmaps.get_mut(&uuid).expect("inserted above");
+                taskmap.remove(issue.key.as_deref().unwrap_or_default());
+            }
+        }
+        issue.repaired = true;
+    }
+    for (uuid, taskmap) in taskmaps {
+        let data = serde_json::to_string(&taskmap).map_err(|e| format!("Failed to encode task {}: {}", uuid, e))?;
+        storage::set_task_data(&tx, &uuid, &data)?;
+    }
+    tx.commit().map_err(|e| format!("Failed to commit repair: {}", e))?;
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::open_on_disk;
+    use taskchampion::{Operations, Status};
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_check_and_repair() {
+        let dir = TempDir::new().unwrap();
+        let uuid = Uuid::new_v4();
+        {
+            let mut replica = open_on_disk(dir.path(), true).unwrap();
+            let mut ops = Operations::new();
+            let mut task = replica.create_task(uuid, &mut ops).unwrap();
+            task.set_description("Survivor".to_string(), &mut ops).unwrap();
+            task.set_status(Status::Pending, &mut ops).unwrap();
+            replica.commit_operations(ops).unwrap();
+            replica.rebuild_working_set(true).unwrap();
+        }
+        assert!(check(dir.path()).unwrap().ok());
+
+        // Damage the database as a torn write might.
+        let conn = Connection::open(dir.path().join(DB_FILE)).unwrap();
+        let data: String = conn.query_row("SELECT data FROM tasks", [], |row| row.get(0)).unwrap();
+        let mut taskmap: HashMap<String, String> = serde_json::from_str(&data).unwrap();
+        taskmap.insert("status".to_string(), "pendi".to_string());
+        taskmap.insert("tag_9lives".to_string(), String::new());
+        taskmap.insert("annotation_soon".to_string(), "note".to_string());
+        let data = serde_json::to_string(&taskmap).unwrap();
+        conn.execute("UPDATE tasks SET data = ?1", [data]).unwrap();
+        conn.execute("INSERT INTO tasks (uuid, data) VALUES ('not-a-uuid', '{}')", []).unwrap();
+        conn.execute("INSERT INTO working_set (id, uuid) VALUES (7, ?1)", [Uuid::new_v4().to_string()])
+            .unwrap();
+        drop(conn);
+
+        let report = check(dir.path()).unwrap();
+        assert!(!report.ok());
+        let mut kinds: Vec<&str> = report.issues.iter().map(|i| i.kind).collect();
+        kinds.sort();
+        assert_eq!(
+            kinds,
+            ["invalid_annotation", "invalid_status", "invalid_tag", "orphan_working_set", "unreadable_task"]
+        );
+
+        let report = repair(dir.path()).unwrap();
+        assert!(report.ok() && report.changed());
+        assert!(check(dir.path()).unwrap().issues.is_empty());
+
+        let mut replica = open_on_disk(dir.path(), false).unwrap();
+        let task = replica.get_task(uuid).unwrap().unwrap();
+        assert_eq!(task.get_status(), Status::Pending);
+        assert_eq!(replica.get_task_data(uuid).unwrap().unwrap().get("status"), None);
+        assert_eq!(replica.all_tasks().unwrap().len(), 1);
+    }
+}
@@ -1,19 +1,26 @@
-use jni::objects::{JClass, JObject, JString};
+use jni::objects::{JClass, JObject, JObjectArray, JString};
 use jni::sys::{jboolean, jint, jlong, jobjectArray};
 use jni::JNIEnv;
-use taskchampion::{Replica, StorageConfig, Operations, Operation, Status, Tag, Annotation, ServerConfig, Task};
+use taskchampion::{Replica, Operations, Operation, Status, Tag, Annotation, ServerConfig, Task};
 use taskchampion::server::AwsCredentials;
 use uuid::Uuid;
 use chrono::Utc;
 use log::{info, error, warn};
 use serde_json;
+use std::collections::{HashMap, HashSet};
 use std::env;
 use std::panic;
 use std::sync::atomic::{AtomicI64, Ordering};
+use std::path::{Path, PathBuf};
 use std::sync::{Arc, Mutex};
 use dashmap::DashMap;
 use lazy_static::lazy_static;
+use crate::filter::{self, Filter};
+use crate::sort::{self, SortKey};
 use crate::logging::init_android_logger;
+use crate::search::SearchIndex;
+use crate::templates::{self, SpecError, TaskSpec};
+use crate::{backup, dates, history, ical, integrity, journal, migrate, recurrence, stats, storage, tabular, taskwarrior, todotxt};
 
 /// Configure TLS to use bundled certificates instead of native Android certificate store
 /// This prevents SIGABRT crashes when rustls-native-certs fails to find Android certificates
@@ -41,13 +48,48 @@ fn configure_android_tls() {
 // itself is dropped when the last Arc holder (destroy or an in-flight
 // operation) finishes.
 lazy_static! {
-    static ref REPLICAS: DashMap<jlong, Arc<Mutex<SendReplica>>> = DashMap::new();
+    static ref REPLICAS: DashMap<jlong, Arc<Mutex<ReplicaSession>>> = DashMap::new();
 }
 
-/// Newtype marking `Replica` as `Send` so it can live in the global
-/// registry and be used from whichever JVM thread makes the JNI call.
+// Write generations per data directory.
+//
+// Several handles may be open over the same data directory (the
+// documented UI-handle plus sync-handle pattern), each with its own
+// binding-side caches. Every committed change bumps the directory's
+// generation; a session whose cache was built at an older generation
+// knows another handle has written since and discards it.
+lazy_static! {
+    static ref DATA_DIR_GENERATIONS: DashMap<PathBuf, u64> = DashMap::new();
+}
+
+/// Current write generation of a data directory.
+fn data_dir_generation(data_dir: &Path) -> u64 {
+    DATA_DIR_GENERATIONS.get(data_dir).map(|g| *g).unwrap_or(0)
+}
+
+/// Record a write to a data directory, returning the generations before
+/// and after it.
+fn bump_data_dir_generation(data_dir: &Path) -> (u64, u64) {
+    let mut generation = DATA_DIR_GENERATIONS.entry(data_dir.to_path_buf()).or_insert(0);
+    let before = *generation;
+    *generation += 1;
+    (before, *generation)
+}
+
+// Data directories claimed by an operation that needs them to itself
+// (migration, repair). nativeInitialize holds this lock from its check until its
+// handle is registered, and a claim is taken under the same lock before
+// looking for open handles, so no handle can open on a claimed directory
+// between that look and the end of the operation.
+lazy_static! {
+    static ref CLAIMED_DIRS: Mutex<HashSet<PathBuf>> = Mutex::new(HashSet::new());
+}
+
+/// Per-handle state: the Replica plus binding-side caches derived from
+/// it. One lives behind each registry entry's mutex, so its fields are
+/// only ever touched by the thread holding the per-replica lock.
 ///
-/// SAFETY: `Replica` is `!Send` only because taskchampion's
+/// SAFETY (`Send`): `Replica` is `!Send` only because taskchampion's
 /// `Box<dyn Storage>` trait object erases auto traits. Both concrete
 /// storages produced by `StorageConfig::into_storage` are `Send`:
 /// `SqliteStorage` holds a `rusqlite::Connection` (`unsafe impl Send`
@@ -55,48 +97,264 @@ lazy_static! {
 /// per-replica `Mutex` additionally serialises all access, so the
 /// replica is only ever used by one thread at a time. (The previous
 /// raw-pointer scheme relied on the same property implicitly by
-/// dereferencing the replica from arbitrary JVM threads.)
-struct SendReplica(Replica);
-unsafe impl Send for SendReplica {}
+/// dereferencing the replica from arbitrary JVM threads.) The remaining
+/// fields are plain owned data and a `rusqlite::Connection`, which is
+/// `Send` for the same reason.
+struct ReplicaSession {
+    replica: Replica,
+    /// Directory the replica was opened on, as given to nativeInitialize.
+    data_dir: PathBuf,
+    /// Full-text index, built by the first search and then kept current
+    /// by `commit_operations`. `None` until built or after invalidation.
+    search_index: Option<SearchIndex>,
+    /// The data-directory generation `search_index` reflects.
+    search_generation: u64,
+    /// Connection for the binding's journal metadata, opened on first use.
+    journal: Option<rusqlite::Connection>,
+    /// Operations reversed by each undo on this handle, most recent last,
+    /// for redo to replay.
+    redo_stack: Vec<RedoEntry>,
+    /// The data-directory generation `redo_stack` is valid at. Any write
+    /// since (through any handle) invalidates the stack.
+    redo_generation: u64,
+    /// When set, every commit that changes tasks starts a new undo group.
+    auto_undo_points: bool,
+}
+unsafe impl Send for ReplicaSession {}
+
+/// One undo, as redo needs it.
+struct RedoEntry {
+    /// The reversed operations, in their original order.
+    ops: Operations,
+    /// Labels the undone undo points carried.
+    labels: Vec<journal::PositionedLabel>,
+}
+
+impl ReplicaSession {
+    fn new(replica: Replica, data_dir: PathBuf) -> ReplicaSession {
+        ReplicaSession {
+            replica,
+            data_dir,
+            search_index: None,
+            search_generation: 0,
+            journal: None,
+            redo_stack: Vec::new(),
+            redo_generation: 0,
+            auto_undo_points: false,
+        }
+    }
+
+    /// The journal metadata connection, opened first if need be.
+    fn journal(&mut self) -> Result<&rusqlite::Connection, String> {
+        self.replica_and_journal().map(|(_, journal)| journal)
+    }
+
+    /// The replica together with the journal metadata connection, for
+    /// callers that need both at once.
+    fn replica_and_journal(&mut self) -> Result<(&mut Replica, &rusqlite::Connection), String> {
+        let journal = match &mut self.journal {
+            Some(journal) => journal,
+            slot @ None => slot.insert(journal::open(&self.data_dir)?),
+        };
+        Ok((&mut self.replica, journal))
+    }
+
+    /// Reverse `undo_ops`, which must be the newest unsynced operations,
+    /// in one transaction. Returns false (changing nothing) if they are
+    /// not, i.e. the journal changed since they were read.
+    ///
+    /// A successful undo is pushed onto the redo stack.
+    fn undo(&mut self, undo_ops: Operations) -> Result<bool, String> {
+        let touched = touched_uuids(&undo_ops);
+        let labels = journal::tail_labels(self.journal()?, undo_ops.len())?;
+        let redo_ops = undo_ops.clone();
+        let generation_before = data_dir_generation(&self.data_dir);
+        let reversed = self
+            .replica
+            .commit_reversed_operations(undo_ops)
+            .map_err(|e| format!("Failed to commit undo operations: {}", e))?;
+        if reversed {
+            self.tasks_changed(&touched);
+            journal::prune_labels(self.journal()?)?;
+            if self.redo_generation != generation_before {
+                self.redo_stack.clear();
+            }
+            self.redo_stack.push(RedoEntry { ops: redo_ops, labels });
+            self.redo_generation = data_dir_generation(&self.data_dir);
+        }
+        Ok(reversed)
+    }
+
+    /// Replay the operations reversed by the most recent undo on this
+    /// handle. Returns false (changing nothing) if there is nothing to
+    /// redo, or the data has changed since that undo so that replaying
+    /// would no longer restore the state it undid.
+    fn redo(&mut self) -> Result<bool, String> {
+        if self.redo_generation != data_dir_generation(&self.data_dir) {
+            self.redo_stack.clear();
+        }
+        let Some(entry) = self.redo_stack.pop() else {
+            return Ok(false);
+        };
+        let touched = touched_uuids(&entry.ops);
+        let before = storage::last_operation_id(self.journal()?)?;
+        self.replica
+            .commit_operations(entry.ops)
+            .map_err(|e| format!("Failed to commit redo operations: {}", e))?;
+        self.tasks_changed(&touched);
+        self.redo_generation = data_dir_generation(&self.data_dir);
+        journal::restore_labels(self.journal()?, before, &entry.labels)?;
+        Ok(true)
+    }
+
+    /// Commit operations to the replica and bring the binding-side
+    /// caches up to date with the tasks they touch. Every mutating entry
+    /// point commits through here rather than calling
+    /// `Replica::commit_operations` directly.
+    ///
+    /// In auto-undo-point mode an undo point is prepended, in the same
+    /// commit, unless the batch already starts with one or the journal
+    /// already ends with one (so no empty undo groups are created).
+    fn commit_operations(&mut self, mut ops: Operations) -> Result<(), String> {
+        let touched = touched_uuids(&ops);
+        if self.auto_undo_points
+            && !touched.is_empty()
+            && ops.first() != Some(&Operation::UndoPoint)
+            && !storage::ends_with_undo_point(self.journal()?)?
+        {
+            ops.insert(0, Operation::UndoPoint);
+        }
+        self.replica.commit_operations(ops).map_err(|e| e.to_string())?;
+        // A batch of undo points alone changes no task: caches stay
+        // current and redo stays possible.
+        if !touched.is_empty() {
+            self.tasks_changed(&touched);
+            // A new change makes the undone changes unrepeatable.
+            self.redo_stack.clear();
+        }
+        Ok(())
+    }
+
+    /// Note that the given tasks changed (by commit or undo). The search
+    /// index is patched in place if it was current; otherwise it is
+    /// dropped and rebuilt by the next search.
+    fn tasks_changed(&mut self, uuids: &[Uuid]) {
+        let (before, after) = bump_data_dir_generation(&self.data_dir);
+        let Some(mut index) = self.search_index.take() else { return };
+        if self.search_generation != before {
+            return;
+        }
+        for uuid in uuids {
+            match self.replica.get_task(*uuid) {
+                Ok(task) => index.update(*uuid, task.as_ref()),
+                Err(e) => {
+                    warn!("Dropping search index; failed to re-read task {}: {}", uuid, e);
+                    return;
+                }
+            }
+        }
+        self.search_index = Some(index);
+        self.search_generation = after;
+    }
+
+    /// Note that an unknown set of tasks changed (e.g. by sync): drop
+    /// every cache.
+    fn all_tasks_changed(&mut self) {
+        bump_data_dir_generation(&self.data_dir);
+        self.search_index = None;
+    }
+
+    /// The search index, (re)built first if missing or stale.
+    fn search_index(&mut self) -> Result<&SearchIndex, String> {
+        let generation = data_dir_generation(&self.data_dir);
+        if self.search_index.is_none() || self.search_generation != generation {
+            let tasks = self
+                .replica
+                .all_tasks()
+                .map_err(|e| format!("Failed to get all tasks: {}", e))?;
+            let index = SearchIndex::build(tasks.values());
+            info!("Built search index over {} tasks", index.len());
+            self.search_index = Some(index);
+            self.search_generation = generation;
+        }
+        Ok(self.search_index.as_ref().expect("search index was just built"))
+    }
+}
+
+/// The distinct task UUIDs an operation batch touches, in first-seen
+/// order.
+fn touched_uuids(ops: &[Operation]) -> Vec<Uuid> {
+    let mut uuids: Vec<Uuid> = Vec::new();
+    for uuid in ops.iter().filter_map(Operation::get_uuid) {
+        if !uuids.contains(&uuid) {
+            uuids.push(uuid);
+        }
+    }
+    uuids
+}
 
 /// Next handle to allocate. Starts at 1; 0 is reserved as the failure
 /// sentinel returned by nativeInitialize on error.
 static NEXT_HANDLE: AtomicI64 = AtomicI64::new(1);
 
-/// Register a Replica in the registry and return its newly allocated
-/// opaque handle.
-fn register_replica(replica: Replica) -> jlong {
+/// Register a Replica opened on `data_dir` in the registry and return
+/// its newly allocated opaque handle.
+fn register_replica(replica: Replica, data_dir: PathBuf) -> jlong {
     let handle = NEXT_HANDLE.fetch_add(1, Ordering::Relaxed);
-    REPLICAS.insert(handle, Arc::new(Mutex::new(SendReplica(replica))));
+    REPLICAS.insert(handle, Arc::new(Mutex::new(ReplicaSession::new(replica, data_dir))));
     handle
 }
 
-/// Non-JNI core of `run_with_replica`: look up the handle, lock the
+/// Open a Replica on `data_dir`, creating it if need be, and register
+/// it, unless the directory is claimed (see [`DirClaim`]).
+fn open_replica(data_dir: &Path) -> Result<jlong, String> {
+    let claimed = CLAIMED_DIRS.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+    if claimed.contains(&data_dir.canonicalize().unwrap_or(data_dir.to_path_buf())) {
+        return Err(format!("{} is being migrated or repaired", data_dir.display()));
+    }
+    let replica = storage::open_on_disk(data_dir, true)?;
+    // Canonical form, so that handles opened via different spellings
+    // of the same directory share a write generation.
+    let data_dir = data_dir.canonicalize().unwrap_or(data_dir.to_path_buf());
+    Ok(register_replica(replica, data_dir))
+}
+
+/// Non-JNI core of `run_with_session`: look up the handle, lock the
 /// per-replica mutex, and run the closure with exclusive access to the
-/// Replica. Returns `None` if the handle is not registered (never was,
+/// session. Returns `None` if the handle is not registered (never was,
 /// or already destroyed). A poisoned mutex is recovered via
 /// `into_inner`.
-fn with_registered_replica<F, R>(handle: jlong, method_name: &str, f: F) -> Option<R>
+fn with_registered_session<F, R>(handle: jlong, method_name: &str, f: F) -> Option<R>
 where
-    F: FnOnce(&mut Replica) -> R,
+    F: FnOnce(&mut ReplicaSession) -> R,
 {
     // Clone the Arc and drop the DashMap ref guard *before* locking, so
     // no shard guard is held across the (potentially long) mutex
     // acquisition. From this point the cloned Arc alone keeps the
     // Replica alive, even if nativeDestroy removes the entry
     // concurrently.
-    let replica_arc = {
+    let session_arc = {
         let entry = REPLICAS.get(&handle)?;
         Arc::clone(entry.value())
     };
-    let mut guard = match replica_arc.lock() {
+    let mut guard = match session_arc.lock() {
         Ok(guard) => guard,
         Err(poisoned) => {
             warn!("Replica mutex poisoned in {}, recovering", method_name);
             poisoned.into_inner()
         }
     };
-    Some(f(&mut guard.0))
+    Some(f(&mut guard))
+}
+
+/// As `with_registered_session`, for closures that only need the
+/// Replica.
+#[cfg(test)]
+fn with_registered_replica<F, R>(handle: jlong, method_name: &str, f: F) -> Option<R>
+where
+    F: FnOnce(&mut Replica) -> R,
+{
+    with_registered_session(handle, method_name, |session| f(&mut session.replica))
 }
 
 // Fully-qualified names of the Java exception classes thrown by this binding.
@@ -105,6 +363,8 @@ const EXC_INVALID_REPLICA: &str = "com/tasksquire/data/storage/InvalidReplicaExc
 const EXC_INVALID_UUID: &str = "com/tasksquire/data/storage/InvalidUuidException";
 const EXC_INVALID_STATUS: &str = "com/tasksquire/data/storage/InvalidStatusException";
 const EXC_INVALID_TAG: &str = "com/tasksquire/data/storage/InvalidTagException";
+const EXC_INVALID_QUERY: &str = "com/tasksquire/data/storage/InvalidQueryException";
+const EXC_INVALID_ARGUMENT: &str = "com/tasksquire/data/storage/InvalidArgumentException";
 const EXC_REPLICA_INIT: &str = "com/tasksquire/data/storage/ReplicaInitializationException";
 const EXC_SYNC: &str = "com/tasksquire/data/storage/SyncException";
 const EXC_STORAGE: &str = "com/tasksquire/data/storage/TaskChampionStorageException";
@@ -172,6 +432,62 @@ fn read_jstring<'local, 's>(
     }
 }
 
+/// Read a `String[]` parameter into Rust strings, preserving null
+/// elements as `None`. Throws TaskChampionStorageException on JNI
+/// marshalling failure (rare) or if the array itself is null.
+fn read_jstring_array<'local>(
+    env: &mut JNIEnv<'local>,
+    array: &JObjectArray<'local>,
+    param_name: &str,
+) -> Option<Vec<Option<String>>> {
+    if array.is_null() {
+        throw(env, EXC_STORAGE, &format!("Parameter '{}' must not be null", param_name));
+        return None;
+    }
+    let fail = |env: &mut JNIEnv<'local>, e: jni::errors::Error| {
+        error!("Failed to read JNI string array parameter '{}': {:?}", param_name, e);
+        throw(
+            env,
+            EXC_STORAGE,
+            &format!("Failed to read parameter '{}' from JVM: {}", param_name, e),
+        );
+    };
+    let len = match env.get_array_length(array) {
+        Ok(len) => len,
+        Err(e) => {
+            fail(env, e);
+            return None;
+        }
+    };
+    let mut strings = Vec::with_capacity(len as usize);
+    for i in 0..len {
+        let element = match env.get_object_array_element(array, i) {
+            Ok(element) => element,
+            Err(e) => {
+                fail(env, e);
+                return None;
+            }
+        };
+        if element.is_null() {
+            strings.push(None);
+            continue;
+        }
+        let jstr = JString::from(element);
+        let value = match env.get_string(&jstr) {
+            Ok(value) => String::from(value),
+            Err(e) => {
+                fail(env, e);
+                return None;
+            }
+        };
+        // Release each element's local reference as we go so that large
+        // arrays cannot exhaust the JNI local reference table.
+        let _ = env.delete_local_ref(jstr);
+        strings.push(Some(value));
+    }
+    Some(strings)
+}
+
 /// Parse a string as a v4 UUID. Throws InvalidUuidException on failure.
 fn parse_uuid(env: &mut JNIEnv, uuid_str: &str) -> Option<Uuid> {
     match Uuid::parse_str(uuid_str) {
@@ -187,6 +503,49 @@ fn parse_uuid(env: &mut JNIEnv, uuid_str: &str) -> Option<Uuid> {
     }
 }
 
+/// Parse a filter expression. Throws InvalidQueryException on failure.
+fn parse_filter(env: &mut JNIEnv, filter_str: &str) -> Option<Filter> {
+    match filter::parse(filter_str) {
+        Ok(f) => Some(f),
+        Err(e) => {
+            throw(env, EXC_INVALID_QUERY, &format!("Invalid filter '{}': {}", filter_str, e));
+            None
+        }
+    }
+}
+
+/// Parse a sort specification. Throws InvalidQueryException on failure.
+fn parse_sort(env: &mut JNIEnv, sort_str: &str) -> Option<Vec<SortKey>> {
+    match sort::parse(sort_str) {
+        Ok(keys) => Some(keys),
+        Err(e) => {
+            throw(env, EXC_INVALID_QUERY, &format!("Invalid sort '{}': {}", sort_str, e));
+            None
+        }
+    }
+}
+
+/// Report a rejected task spec: InvalidTagException for a bad tag,
+/// InvalidArgumentException otherwise.
+fn throw_spec_error(env: &mut JNIEnv, e: &SpecError) {
+    let class = match e {
+        SpecError::InvalidTag(_) => EXC_INVALID_TAG,
+        SpecError::Invalid(_) => EXC_INVALID_ARGUMENT,
+    };
+    throw(env, class, &e.to_string());
+}
+
+/// Parse a task spec. Throws as throw_spec_error on failure.
+fn parse_spec(env: &mut JNIEnv, json: &str) -> Option<TaskSpec> {
+    match TaskSpec::parse(json) {
+        Ok(spec) => Some(spec),
+        Err(e) => {
+            throw_spec_error(env, &e);
+            None
+        }
+    }
+}
+
 /// Acquire the per-replica mutex and run a closure with exclusive access
 /// to the replica. The lock is released before this function returns.
 ///
@@ -214,6 +573,22 @@ fn run_with_replica<'local, F, R>(
 ) -> Option<R>
 where
     F: FnOnce(&mut Replica) -> Result<R, String>,
+{
+    run_with_session(env, replica_ptr, method_name, |session| f(&mut session.replica))
+}
+
+/// As `run_with_replica`, but the closure receives the whole session.
+/// Entry points that commit changes use this so the commit goes through
+/// `ReplicaSession::commit_operations` and keeps the caches current.
+#[must_use]
+fn run_with_session<'local, F, R>(
+    env: &mut JNIEnv<'local>,
+    replica_ptr: jlong,
+    method_name: &str,
+    f: F,
+) -> Option<R>
+where
+    F: FnOnce(&mut ReplicaSession) -> Result<R, String>,
 {
     if replica_ptr == 0 {
         throw(
@@ -224,7 +599,7 @@ where
         return None;
     }
 
-    let result = match with_registered_replica(replica_ptr, method_name, f) {
+    let result = match with_registered_session(replica_ptr, method_name, f) {
         Some(result) => result,
         None => {
             throw(
@@ -258,6 +633,11 @@ where
 // makes no further env calls — building a fallback array at that point
 // would abort the process.
 fn create_string_array<'local>(env: &mut JNIEnv<'local>, strings: Vec<String>) -> jobjectArray {
+    create_nullable_string_array(env, strings.into_iter().map(Some).collect())
+}
+
+// As create_string_array, but `None` elements are left as Java nulls.
+fn create_nullable_string_array<'local>(env: &mut JNIEnv<'local>, strings: Vec<Option<String>>) -> jobjectArray {
     let string_class = match env.find_class("java/lang/String") {
         Ok(c) => c,
         Err(e) => {
@@ -275,6 +655,8 @@ fn create_string_array<'local>(env: &mut JNIEnv<'local>, strings: Vec<String>) -
         }
     };
     for (i, s) in strings.iter().enumerate() {
+        // The array is created null-filled, so None needs no store.
+        let Some(s) = s else { continue };
         let java_string = match env.new_string(s) {
             Ok(js) => js,
             Err(e) => {
@@ -311,24 +693,15 @@ pub extern "system" fn Java_com_tasksquire_data_storage_TaskChampionJniImpl_nati
 
         info!("Initializing Replica with data directory: {}", data_dir_str);
 
-        let storage_config = StorageConfig::OnDisk {
-            taskdb_dir: data_dir_str.into(),
-            create_if_missing: true,
-            access_mode: taskchampion::storage::AccessMode::ReadWrite,
-        };
-
-        let storage = match storage_config.into_storage() {
-            Ok(s) => s,
+        let handle = match open_replica(Path::new(&data_dir_str)) {
+            Ok(handle) => handle,
             Err(e) => {
-                error!("Failed to create storage: {:?}", e);
-                throw(&mut env, EXC_REPLICA_INIT, &format!("Failed to create storage: {}", e));
+                error!("{}", e);
+                throw(&mut env, EXC_REPLICA_INIT, &e);
                 return 0;
             }
         };
 
-        let replica = Replica::new(storage);
-        let handle = register_replica(replica);
-
         info!("Replica initialized successfully, handle: {}", handle);
         handle
     })
@@ -383,17 +756,18 @@ pub extern "system" fn Java_com_tasksquire_data_storage_TaskChampionJniImpl_nati
             ConcurrentChanges,
         }
 
-        let outcome = run_with_replica(&mut env, replica_ptr, "nativeUndo", |replica| {
-            let undo_ops = replica
+        let outcome = run_with_session(&mut env, replica_ptr, "nativeUndo", |session| {
+            let undo_ops = session
+                .replica
                 .get_undo_operations()
                 .map_err(|e| format!("Failed to get undo operations: {}", e))?;
             if undo_ops.is_empty() {
                 return Ok(UndoOutcome::NoOpsToUndo);
             }
-            match replica.commit_reversed_operations(undo_ops) {
-                Ok(true) => Ok(UndoOutcome::Reversed),
-                Ok(false) => Ok(UndoOutcome::ConcurrentChanges),
-                Err(e) => Err(format!("Failed to commit undo operations: {}", e)),
+            if session.undo(undo_ops)? {
+                Ok(UndoOutcome::Reversed)
+            } else {
+                Ok(UndoOutcome::ConcurrentChanges)
             }
         });
 
@@ -419,6 +793,62 @@ pub extern "system" fn Java_com_tasksquire_data_storage_TaskChampionJniImpl_nati
     })
 }
 
+#[no_mangle]
+pub extern "system" fn Java_com_tasksquire_data_storage_TaskChampionJniImpl_nativeRedo(
+    mut env: JNIEnv,
+    _class: JClass,
+    replica_ptr: jlong,
+) -> jboolean {
+    catch_panics!(&mut env, "nativeRedo", 0, {
+        // On None an exception is pending; 0 is the sentinel.
+        run_with_session(&mut env, replica_ptr, "nativeRedo", |session| {
+            let redone = session.redo()?;
+            if redone {
+                info!("Redo operation completed successfully");
+            } else {
+                info!("Nothing to redo");
+            }
+            Ok(redone as jboolean)
+        })
+        .unwrap_or(0)
+    })
+}
+
+#[no_mangle]
+pub extern "system" fn Java_com_tasksquire_data_storage_TaskChampionJniImpl_nativeGetUndoPreview<'local>(
+    mut env: JNIEnv<'local>,
+    _class: JClass,
+    replica_ptr: jlong,
+) -> JString<'local> {
+    catch_panics!(&mut env, "nativeGetUndoPreview", JObject::null().into(), {
+        // Inner None signals "nothing to undo" — returned to Java as null.
+        // Outer None signals a thrown exception.
+        let preview: Option<Option<String>> = run_with_replica(&mut env, replica_ptr, "nativeGetUndoPreview", |replica| {
+            let undo_ops = replica
+                .get_undo_operations()
+                .map_err(|e| format!("Failed to get undo operations: {}", e))?;
+            history::undo_preview(replica, &undo_ops)
+        });
+
+        let Some(preview) = preview else {
+            // Exception pending; any further env call would abort the process.
+            return JObject::null().into();
+        };
+
+        match preview {
+            Some(json) => match env.new_string(&json) {
+                Ok(java_string) => java_string,
+                Err(e) => {
+                    error!("Failed to create Java string for undo preview: {:?}", e);
+                    throw(&mut env, EXC_STORAGE, &format!("Failed to marshal undo preview: {}", e));
+                    JObject::null().into()
+                }
+            },
+            None => JObject::null().into(),
+        }
+    })
+}
+
 #[no_mangle]
 pub extern "system" fn Java_com_tasksquire_data_storage_TaskChampionJniImpl_nativeAddUndoPoint(
     mut env: JNIEnv,
@@ -427,10 +857,9 @@ pub extern "system" fn Java_com_tasksquire_data_storage_TaskChampionJniImpl_nati
 ) {
     catch_panics!(&mut env, "nativeAddUndoPoint", (), {
         // On None an exception is pending; nothing further touches env.
-        let _ = run_with_replica(&mut env, replica_ptr, "nativeAddUndoPoint", |replica| {
-            let mut ops = Operations::new();
-            ops.push(Operation::UndoPoint);
-            replica
+        let _ = run_with_session(&mut env, replica_ptr, "nativeAddUndoPoint", |session| {
+            let ops = vec![Operation::UndoPoint];
+            session
                 .commit_operations(ops)
                 .map_err(|e| format!("Failed to add undo point: {}", e))?;
             info!("Undo point added");
@@ -439,6 +868,126 @@ pub extern "system" fn Java_com_tasksquire_data_storage_TaskChampionJniImpl_nati
     })
 }
 
+/// Add an undo point carrying `label`.
+fn add_labelled_undo_point(session: &mut ReplicaSession, label: &str) -> Result<(), String> {
+    let before = storage::last_operation_id(session.journal()?)?;
+    session
+        .commit_operations(vec![Operation::UndoPoint])
+        .map_err(|e| format!("Failed to add undo point: {}", e))?;
+    if !journal::label_undo_point_after(session.journal()?, before, label, Utc::now())? {
+        return Err("Failed to label undo point: it is no longer in the journal".to_string());
+    }
+    Ok(())
+}
+
+/// Undo back to and including the newest undo point labelled `label`.
+/// Returns false if there is no such point or the journal changed
+/// concurrently.
+fn undo_to_label(session: &mut ReplicaSession, label: &str) -> Result<bool, String> {
+    let Some(undo_ops) = journal::operations_since_label(session.journal()?, label)? else {
+        return Ok(false);
+    };
+    session.undo(undo_ops)
+}
+
+/// The undoable undo points as JSON documents, newest first.
+fn undo_point_docs(session: &mut ReplicaSession) -> Result<Vec<String>, String> {
+    journal::undo_points(session.journal()?)?
+        .into_iter()
+        .map(|point| {
+            let doc = serde_json::json!({
+                "label": point.label,
+                "timestamp": point.timestamp.map(|t| t.timestamp().to_string()),
+                "changes": point.changes,
+            });
+            serde_json::to_string(&doc).map_err(|e| format!("Failed to serialize undo point to JSON: {}", e))
+        })
+        .collect()
+}
+
+#[no_mangle]
+pub extern "system" fn Java_com_tasksquire_data_storage_TaskChampionJniImpl_nativeAddLabelledUndoPoint<'local>(
+    mut env: JNIEnv<'local>,
+    _class: JClass,
+    replica_ptr: jlong,
+    label: JString<'local>,
+) {
+    catch_panics!(&mut env, "nativeAddLabelledUndoPoint", (), {
+        let label = match read_jstring(&mut env, &label, "label") { Some(s) => s, None => return };
+
+        // On None an exception is pending; nothing further touches env.
+        let _ = run_with_session(&mut env, replica_ptr, "nativeAddLabelledUndoPoint", |session| {
+            add_labelled_undo_point(session, &label)?;
+            info!("Undo point '{}' added", label);
+            Ok(())
+        });
+    })
+}
+
+#[no_mangle]
+pub extern "system" fn Java_com_tasksquire_data_storage_TaskChampionJniImpl_nativeListUndoPoints<'local>(
+    mut env: JNIEnv<'local>,
+    _class: JClass,
+    replica_ptr: jlong,
+) -> jobjectArray {
+    catch_panics!(&mut env, "nativeListUndoPoints", std::ptr::null_mut(), {
+        let docs = run_with_session(&mut env, replica_ptr, "nativeListUndoPoints", |session| {
+            let docs = undo_point_docs(session)?;
+            info!("Found {} undo points", docs.len());
+            Ok(docs)
+        });
+
+        let Some(docs) = docs else {
+            // Exception pending; any further env call would abort the process.
+            return std::ptr::null_mut();
+        };
+
+        create_string_array(&mut env, docs)
+    })
+}
+
+#[no_mangle]
+pub extern "system" fn Java_com_tasksquire_data_storage_TaskChampionJniImpl_nativeUndoTo<'local>(
+    mut env: JNIEnv<'local>,
+    _class: JClass,
+    replica_ptr: jlong,
+    label: JString<'local>,
+) -> jboolean {
+    catch_panics!(&mut env, "nativeUndoTo", 0, {
+        let label = match read_jstring(&mut env, &label, "label") { Some(s) => s, None => return 0 };
+
+        // On None an exception is pending; 0 is the sentinel.
+        run_with_session(&mut env, replica_ptr, "nativeUndoTo", |session| {
+            let reversed = undo_to_label(session, &label)?;
+            if reversed {
+                info!("Undid back to undo point '{}'", label);
+            } else {
+                warn!("Undo to '{}' did nothing - no such undo point, or concurrent changes", label);
+            }
+            Ok(reversed as jboolean)
+        })
+        .unwrap_or(0)
+    })
+}
+
+#[no_mangle]
+pub extern "system" fn Java_com_tasksquire_data_storage_TaskChampionJniImpl_nativeSetAutoUndoPoints(
+    mut env: JNIEnv,
+    _class: JClass,
+    replica_ptr: jlong,
+    enabled: jboolean,
+) {
+    catch_panics!(&mut env, "nativeSetAutoUndoPoints", (), {
+        let enabled = enabled != 0;
+        // On None an exception is pending; nothing further touches env.
+        let _ = run_with_session(&mut env, replica_ptr, "nativeSetAutoUndoPoints", |session| {
+            session.auto_undo_points = enabled;
+            info!("Automatic undo points {}", if enabled { "enabled" } else { "disabled" });
+            Ok(())
+        });
+    })
+}
+
 #[no_mangle]
 pub extern "system" fn Java_com_tasksquire_data_storage_TaskChampionJniImpl_nativeRebuildWorkingSet(
     mut env: JNIEnv,
@@ -473,12 +1022,12 @@ pub extern "system" fn Java_com_tasksquire_data_storage_TaskChampionJniImpl_nati
         let task_uuid = match parse_uuid(&mut env, &uuid_str) { Some(u) => u, None => return };
 
         // On None an exception is pending; nothing further touches env.
-        let _ = run_with_replica(&mut env, replica_ptr, "nativeCreateTask", |replica| {
+        let _ = run_with_session(&mut env, replica_ptr, "nativeCreateTask", |session| {
             let mut ops = Operations::new();
-            replica
+            session.replica
                 .create_task(task_uuid, &mut ops)
                 .map_err(|e| format!("Failed to create task: {}", e))?;
-            replica
+            session
                 .commit_operations(ops)
                 .map_err(|e| format!("Failed to commit create task operations: {}", e))?;
             info!("Task created successfully: {}", uuid_str);
@@ -501,9 +1050,9 @@ pub extern "system" fn Java_com_tasksquire_data_storage_TaskChampionJniImpl_nati
         let description = match read_jstring(&mut env, &desc, "description") { Some(s) => s, None => return };
 
         // On None an exception is pending; nothing further touches env.
-        let _ = run_with_replica(&mut env, replica_ptr, "nativeTaskSetDescription", |replica| {
+        let _ = run_with_session(&mut env, replica_ptr, "nativeTaskSetDescription", |session| {
             let mut ops = Operations::new();
-            let mut task = replica
+            let mut task = session.replica
                 .get_task(task_uuid)
                 .map_err(|e| format!("Failed to get task: {}", e))?
                 .ok_or_else(|| format!("Task not found: {}", uuid_str))?;
@@ -513,7 +1062,7 @@ pub extern "system" fn Java_com_tasksquire_data_storage_TaskChampionJniImpl_nati
             task.set_value("modified", Some(now), &mut ops)
                 .map_err(|e| format!("Failed to set modified timestamp: {}", e))?;
             drop(task);
-            replica
+            session
                 .commit_operations(ops)
                 .map_err(|e| format!("Failed to commit set description operations: {}", e))?;
             info!("Task description updated successfully: {}", uuid_str);
@@ -522,6 +1071,85 @@ pub extern "system" fn Java_com_tasksquire_data_storage_TaskChampionJniImpl_nati
     })
 }
 
+/// The status named by a status argument, or `None` if it names none.
+fn parse_status(name: &str) -> Option<Status> {
+    match name {
+        "pending" => Some(Status::Pending),
+        "completed" => Some(Status::Completed),
+        "deleted" => Some(Status::Deleted),
+        "recurring" => Some(Status::Recurring),
+        _ => None,
+    }
+}
+
+/// Set a task's status and stamp `modified`. `Task::set_status` manages
+/// `end`: it is set on completion or deletion unless already present, and
+/// removed on a return to pending or recurring.
+fn set_task_status(task: &mut Task, status: Status, ops: &mut Operations) -> Result<(), String> {
+    task.set_status(status, ops)
+        .map_err(|e| format!("Failed to set task status: {}", e))?;
+    let now = Utc::now().timestamp().to_string();
+    task.set_value("modified", Some(now), ops)
+        .map_err(|e| format!("Failed to set modified timestamp: {}", e))?;
+    Ok(())
+}
+
+/// The pending tasks that depend on `uuid`, each with whether it is
+/// currently blocked.
+fn dependent_blocked_states(replica: &mut Replica, uuid: Uuid) -> Result<Vec<(Uuid, bool)>, String> {
+    let pending = replica
+        .pending_tasks()
+        .map_err(|e| format!("Failed to get pending tasks: {}", e))?;
+    Ok(pending
+        .iter()
+        .filter(|t| t.get_dependencies().any(|d| d == uuid))
+        .map(|t| (t.get_uuid(), t.is_blocked()))
+        .collect())
+}
+
+/// Complete (`done`) or reopen a task, for nativeTaskDone and
+/// nativeTaskUndone. Returns the pending dependents whose blocked state
+/// the change flipped: those completing it unblocked, or those reopening
+/// it blocks again. A task already in the requested state is untouched.
+fn set_task_done(session: &mut ReplicaSession, uuid: Uuid, done: bool) -> Result<Vec<Uuid>, String> {
+    let mut task = session.replica
+        .get_task(uuid)
+        .map_err(|e| format!("Failed to get task: {}", e))?
+        .ok_or_else(|| format!("Task not found: {}", uuid))?;
+    let target = match (done, task.get_status()) {
+        (true, Status::Completed) | (false, Status::Pending) => return Ok(Vec::new()),
+        (true, Status::Pending) => Status::Completed,
+        // Deleted tasks stay deleted; undone only reverses done.
+        (false, Status::Completed) => Status::Pending,
+        (_, status) => {
+            return Err(format!(
+                "Cannot mark task {} {}; its status is {:?}",
+                uuid,
+                if done { "done" } else { "undone" },
+                status
+            ))
+        }
+    };
+
+    let before = dependent_blocked_states(&mut session.replica, uuid)?;
+    let mut ops = Operations::new();
+    set_task_status(&mut task, target, &mut ops)?;
+    drop(task);
+    session
+        .commit_operations(ops)
+        .map_err(|e| format!("Failed to commit status operations: {}", e))?;
+    let after = dependent_blocked_states(&mut session.replica, uuid)?;
+
+    // Completing unblocks dependents; reopening blocks them.
+    Ok(after
+        .into_iter()
+        .filter(|(dependent, blocked)| {
+            *blocked != done && before.iter().any(|(u, b)| u == dependent && *b == done)
+        })
+        .map(|(dependent, _)| dependent)
+        .collect())
+}
+
 #[no_mangle]
 pub extern "system" fn Java_com_tasksquire_data_storage_TaskChampionJniImpl_nativeTaskSetStatus(
     mut env: JNIEnv,
@@ -535,34 +1163,31 @@ pub extern "system" fn Java_com_tasksquire_data_storage_TaskChampionJniImpl_nati
         let task_uuid = match parse_uuid(&mut env, &uuid_str) { Some(u) => u, None => return };
         let status_str = match read_jstring(&mut env, &status, "status") { Some(s) => s, None => return };
 
-        let task_status = match status_str.as_str() {
-            "pending" => Status::Pending,
-            "completed" => Status::Completed,
-            "deleted" => Status::Deleted,
-            _ => {
+        let task_status = match parse_status(&status_str) {
+            Some(s) => s,
+            None => {
                 throw(
                     &mut env,
                     EXC_INVALID_STATUS,
-                    &format!("Invalid status '{}'; expected one of: pending, completed, deleted", status_str),
+                    &format!(
+                        "Invalid status '{}'; expected one of: pending, completed, deleted, recurring",
+                        status_str
+                    ),
                 );
                 return;
             }
         };
 
         // On None an exception is pending; nothing further touches env.
-        let _ = run_with_replica(&mut env, replica_ptr, "nativeTaskSetStatus", |replica| {
+        let _ = run_with_session(&mut env, replica_ptr, "nativeTaskSetStatus", |session| {
             let mut ops = Operations::new();
-            let mut task = replica
+            let mut task = session.replica
                 .get_task(task_uuid)
                 .map_err(|e| format!("Failed to get task: {}", e))?
                 .ok_or_else(|| format!("Task not found: {}", uuid_str))?;
-            task.set_status(task_status, &mut ops)
-                .map_err(|e| format!("Failed to set task status: {}", e))?;
-            let now = Utc::now().timestamp().to_string();
-            task.set_value("modified", Some(now), &mut ops)
-                .map_err(|e| format!("Failed to set modified timestamp: {}", e))?;
+            set_task_status(&mut task, task_status, &mut ops)?;
             drop(task);
-            replica
+            session
                 .commit_operations(ops)
                 .map_err(|e| format!("Failed to commit set status operations: {}", e))?;
             info!("Task status updated successfully: {} -> {}", uuid_str, status_str);
@@ -571,47 +1196,223 @@ pub extern "system" fn Java_com_tasksquire_data_storage_TaskChampionJniImpl_nati
     })
 }
 
-// Task property management
-
 #[no_mangle]
-pub extern "system" fn Java_com_tasksquire_data_storage_TaskChampionJniImpl_nativeTaskSetValue(
-    mut env: JNIEnv,
+pub extern "system" fn Java_com_tasksquire_data_storage_TaskChampionJniImpl_nativeTaskDone<'local>(
+    mut env: JNIEnv<'local>,
     _class: JClass,
     replica_ptr: jlong,
-    uuid: JString,
-    key: JString,
-    value: JString,
-) {
-    catch_panics!(&mut env, "nativeTaskSetValue", (), {
-        let uuid_str = match read_jstring(&mut env, &uuid, "uuid") { Some(s) => s, None => return };
-        let task_uuid = match parse_uuid(&mut env, &uuid_str) { Some(u) => u, None => return };
-        let key_str = match read_jstring(&mut env, &key, "key") { Some(s) => s, None => return };
+    uuid: JString<'local>,
+) -> jobjectArray {
+    catch_panics!(&mut env, "nativeTaskDone", std::ptr::null_mut(), {
+        let uuid_str = match read_jstring(&mut env, &uuid, "uuid") { Some(s) => s, None => return std::ptr::null_mut() };
+        let task_uuid = match parse_uuid(&mut env, &uuid_str) { Some(u) => u, None => return std::ptr::null_mut() };
+
+        let flipped = run_with_session(&mut env, replica_ptr, "nativeTaskDone", |session| {
+            let flipped = set_task_done(session, task_uuid, true)?;
+            info!("Task marked done: {} ({} dependents unblocked)", uuid_str, flipped.len());
+            Ok(flipped.iter().map(Uuid::to_string).collect())
+        });
 
-        let value_opt: Option<String> = if value.is_null() {
-            None
-        } else {
-            match read_jstring(&mut env, &value, "value") { Some(s) => Some(s), None => return }
-        };
+        let Some(flipped) = flipped else {
+            // Exception pending; any further env call would abort the process.
+            return std::ptr::null_mut();
+        };
+
+        create_string_array(&mut env, flipped)
+    })
+}
+
+#[no_mangle]
+pub extern "system" fn Java_com_tasksquire_data_storage_TaskChampionJniImpl_nativeTaskUndone<'local>(
+    mut env: JNIEnv<'local>,
+    _class: JClass,
+    replica_ptr: jlong,
+    uuid: JString<'local>,
+) -> jobjectArray {
+    catch_panics!(&mut env, "nativeTaskUndone", std::ptr::null_mut(), {
+        let uuid_str = match read_jstring(&mut env, &uuid, "uuid") { Some(s) => s, None => return std::ptr::null_mut() };
+        let task_uuid = match parse_uuid(&mut env, &uuid_str) { Some(u) => u, None => return std::ptr::null_mut() };
+
+        let flipped = run_with_session(&mut env, replica_ptr, "nativeTaskUndone", |session| {
+            let flipped = set_task_done(session, task_uuid, false)?;
+            info!("Task marked undone: {} ({} dependents blocked again)", uuid_str, flipped.len());
+            Ok(flipped.iter().map(Uuid::to_string).collect())
+        });
+
+        let Some(flipped) = flipped else {
+            // Exception pending; any further env call would abort the process.
+            return std::ptr::null_mut();
+        };
+
+        create_string_array(&mut env, flipped)
+    })
+}
+
+#[no_mangle]
+pub extern "system" fn Java_com_tasksquire_data_storage_TaskChampionJniImpl_nativeTaskPurge(
+    mut env: JNIEnv,
+    _class: JClass,
+    replica_ptr: jlong,
+    uuid: JString,
+) {
+    catch_panics!(&mut env, "nativeTaskPurge", (), {
+        let uuid_str = match read_jstring(&mut env, &uuid, "uuid") { Some(s) => s, None => return };
+        let task_uuid = match parse_uuid(&mut env, &uuid_str) { Some(u) => u, None => return };
+
+        // On None an exception is pending; nothing further touches env.
+        let _ = run_with_session(&mut env, replica_ptr, "nativeTaskPurge", |session| {
+            let mut ops = Operations::new();
+            let mut data = session.replica
+                .get_task_data(task_uuid)
+                .map_err(|e| format!("Failed to get task: {}", e))?
+                .ok_or_else(|| format!("Task not found: {}", uuid_str))?;
+            data.delete(&mut ops);
+            session
+                .commit_operations(ops)
+                .map_err(|e| format!("Failed to commit purge operations: {}", e))?;
+            info!("Task purged successfully: {}", uuid_str);
+            Ok(())
+        });
+    })
+}
+
+/// Properties a duplicate never copies: those describing the source's
+/// own life and place in a recurrence series.
+const DUPLICATE_SKIPPED: &[&str] = &["entry", "modified", "status", "end", "start", "mask", "parent", "imask"];
+
+/// Copy task `source` to a new task, as TaskWarrior's `task duplicate`
+/// does, then apply `overrides`. The copy is pending, or recurring if the
+/// source is a recurrence template; a copy of a recurrence instance is
+/// an ordinary task. Returns the new task's UUID.
+fn duplicate_task(session: &mut ReplicaSession, source: Uuid, overrides: Option<&TaskSpec>) -> Result<Uuid, String> {
+    let data = session.replica
+        .get_task_data(source)
+        .map_err(|e| format!("Failed to get task: {}", e))?
+        .ok_or_else(|| format!("Task not found: {}", source))?;
+    let is_instance = data.has("parent");
+    let status = if data.get("status") == Some("recurring") { Status::Recurring } else { Status::Pending };
+
+    let uuid = Uuid::new_v4();
+    let now = Utc::now();
+    let mut ops = Operations::new();
+    let mut task = session.replica
+        .create_task(uuid, &mut ops)
+        .map_err(|e| format!("Failed to create task: {}", e))?;
+    for key in data.properties() {
+        let recurrence_key = matches!(key.as_str(), "recur" | "until" | "rtype");
+        if DUPLICATE_SKIPPED.contains(&key.as_str()) || (is_instance && recurrence_key) {
+            continue;
+        }
+        task.set_value(key, data.get(key).map(str::to_string), &mut ops)
+            .map_err(|e| format!("Failed to copy task value: {}", e))?;
+    }
+    task.set_value("entry", Some(now.timestamp().to_string()), &mut ops)
+        .map_err(|e| format!("Failed to set entry timestamp: {}", e))?;
+    set_task_status(&mut task, status, &mut ops)?;
+    if let Some(overrides) = overrides {
+        overrides.apply(&mut task, now, &mut ops)?;
+    }
+    drop(task);
+    session
+        .commit_operations(ops)
+        .map_err(|e| format!("Failed to commit duplicate task operations: {}", e))?;
+    Ok(uuid)
+}
+
+#[no_mangle]
+pub extern "system" fn Java_com_tasksquire_data_storage_TaskChampionJniImpl_nativeTaskDuplicate<'local>(
+    mut env: JNIEnv<'local>,
+    _class: JClass,
+    replica_ptr: jlong,
+    source_uuid: JString<'local>,
+    overrides_json: JString<'local>,
+) -> JString<'local> {
+    catch_panics!(&mut env, "nativeTaskDuplicate", JObject::null().into(), {
+        let uuid_str = match read_jstring(&mut env, &source_uuid, "sourceUuid") { Some(s) => s, None => return JObject::null().into() };
+        let source = match parse_uuid(&mut env, &uuid_str) { Some(u) => u, None => return JObject::null().into() };
+        let overrides = if overrides_json.is_null() {
+            None
+        } else {
+            let json = match read_jstring(&mut env, &overrides_json, "overridesJson") { Some(s) => s, None => return JObject::null().into() };
+            match parse_spec(&mut env, &json) {
+                Some(spec) => Some(spec),
+                None => return JObject::null().into(),
+            }
+        };
+
+        let created = run_with_session(&mut env, replica_ptr, "nativeTaskDuplicate", |session| {
+            let created = duplicate_task(session, source, overrides.as_ref())?;
+            info!("Task {} duplicated as {}", uuid_str, created);
+            Ok(created)
+        });
+
+        let Some(created) = created else {
+            // Exception pending; any further env call would abort the process.
+            return JObject::null().into();
+        };
+
+        match env.new_string(created.to_string()) {
+            Ok(java_string) => java_string,
+            Err(e) => {
+                error!("Failed to create Java string for task UUID: {:?}", e);
+                throw(&mut env, EXC_STORAGE, &format!("Failed to marshal task UUID: {}", e));
+                JObject::null().into()
+            }
+        }
+    })
+}
+
+// Task property management
+
+#[no_mangle]
+pub extern "system" fn Java_com_tasksquire_data_storage_TaskChampionJniImpl_nativeTaskSetValue(
+    mut env: JNIEnv,
+    _class: JClass,
+    replica_ptr: jlong,
+    uuid: JString,
+    key: JString,
+    value: JString,
+) {
+    catch_panics!(&mut env, "nativeTaskSetValue", (), {
+        let uuid_str = match read_jstring(&mut env, &uuid, "uuid") { Some(s) => s, None => return };
+        let task_uuid = match parse_uuid(&mut env, &uuid_str) { Some(u) => u, None => return };
+        let key_str = match read_jstring(&mut env, &key, "key") { Some(s) => s, None => return };
+
+        let value_opt: Option<String> = if value.is_null() {
+            None
+        } else {
+            match read_jstring(&mut env, &value, "value") { Some(s) => Some(s), None => return }
+        };
 
         let value_present = value_opt.is_some();
         let key_for_log = key_str.clone();
 
         // On None an exception is pending; nothing further touches env.
-        let _ = run_with_replica(&mut env, replica_ptr, "nativeTaskSetValue", |replica| {
+        let _ = run_with_session(&mut env, replica_ptr, "nativeTaskSetValue", |session| {
             let mut ops = Operations::new();
-            let mut task = replica
+            let mut task = session.replica
                 .get_task(task_uuid)
                 .map_err(|e| format!("Failed to get task: {}", e))?
                 .ok_or_else(|| format!("Task not found: {}", uuid_str))?;
-            task.set_value(&key_str, value_opt, &mut ops)
-                .map_err(|e| format!("Failed to set task value: {}", e))?;
-            if key_str != "modified" {
-                let now = Utc::now().timestamp().to_string();
-                task.set_value("modified", Some(now), &mut ops)
-                    .map_err(|e| format!("Failed to set modified timestamp: {}", e))?;
+            // A known status written as a plain value goes through
+            // Task::set_status, so "end" is kept as for nativeTaskSetStatus.
+            let status = match key_str.as_str() {
+                "status" => value_opt.as_deref().and_then(parse_status),
+                _ => None,
+            };
+            if let Some(status) = status {
+                set_task_status(&mut task, status, &mut ops)?;
+            } else {
+                task.set_value(&key_str, value_opt, &mut ops)
+                    .map_err(|e| format!("Failed to set task value: {}", e))?;
+                if key_str != "modified" {
+                    let now = Utc::now().timestamp().to_string();
+                    task.set_value("modified", Some(now), &mut ops)
+                        .map_err(|e| format!("Failed to set modified timestamp: {}", e))?;
+                }
             }
             drop(task);
-            replica
+            session
                 .commit_operations(ops)
                 .map_err(|e| format!("Failed to commit set value operations: {}", e))?;
             info!(
@@ -647,9 +1448,9 @@ pub extern "system" fn Java_com_tasksquire_data_storage_TaskChampionJniImpl_nati
         };
 
         // On None an exception is pending; nothing further touches env.
-        let _ = run_with_replica(&mut env, replica_ptr, "nativeTaskAddTag", |replica| {
+        let _ = run_with_session(&mut env, replica_ptr, "nativeTaskAddTag", |session| {
             let mut ops = Operations::new();
-            let mut task = replica
+            let mut task = session.replica
                 .get_task(task_uuid)
                 .map_err(|e| format!("Failed to get task: {}", e))?
                 .ok_or_else(|| format!("Task not found: {}", uuid_str))?;
@@ -659,7 +1460,7 @@ pub extern "system" fn Java_com_tasksquire_data_storage_TaskChampionJniImpl_nati
             task.set_value("modified", Some(now), &mut ops)
                 .map_err(|e| format!("Failed to set modified timestamp: {}", e))?;
             drop(task);
-            replica
+            session
                 .commit_operations(ops)
                 .map_err(|e| format!("Failed to commit add tag operations: {}", e))?;
             info!("Tag added successfully: {} -> {}", uuid_str, tag_str);
@@ -690,9 +1491,9 @@ pub extern "system" fn Java_com_tasksquire_data_storage_TaskChampionJniImpl_nati
         };
 
         // On None an exception is pending; nothing further touches env.
-        let _ = run_with_replica(&mut env, replica_ptr, "nativeTaskRemoveTag", |replica| {
+        let _ = run_with_session(&mut env, replica_ptr, "nativeTaskRemoveTag", |session| {
             let mut ops = Operations::new();
-            let mut task = replica
+            let mut task = session.replica
                 .get_task(task_uuid)
                 .map_err(|e| format!("Failed to get task: {}", e))?
                 .ok_or_else(|| format!("Task not found: {}", uuid_str))?;
@@ -702,7 +1503,7 @@ pub extern "system" fn Java_com_tasksquire_data_storage_TaskChampionJniImpl_nati
             task.set_value("modified", Some(now), &mut ops)
                 .map_err(|e| format!("Failed to set modified timestamp: {}", e))?;
             drop(task);
-            replica
+            session
                 .commit_operations(ops)
                 .map_err(|e| format!("Failed to commit remove tag operations: {}", e))?;
             info!("Tag removed successfully: {} -> {}", uuid_str, tag_str);
@@ -727,9 +1528,9 @@ pub extern "system" fn Java_com_tasksquire_data_storage_TaskChampionJniImpl_nati
         let description = match read_jstring(&mut env, &desc, "description") { Some(s) => s, None => return };
 
         // On None an exception is pending; nothing further touches env.
-        let _ = run_with_replica(&mut env, replica_ptr, "nativeTaskAddAnnotation", |replica| {
+        let _ = run_with_session(&mut env, replica_ptr, "nativeTaskAddAnnotation", |session| {
             let mut ops = Operations::new();
-            let mut task = replica
+            let mut task = session.replica
                 .get_task(task_uuid)
                 .map_err(|e| format!("Failed to get task: {}", e))?
                 .ok_or_else(|| format!("Task not found: {}", uuid_str))?;
@@ -743,7 +1544,7 @@ pub extern "system" fn Java_com_tasksquire_data_storage_TaskChampionJniImpl_nati
             task.set_value("modified", Some(now), &mut ops)
                 .map_err(|e| format!("Failed to set modified timestamp: {}", e))?;
             drop(task);
-            replica
+            session
                 .commit_operations(ops)
                 .map_err(|e| format!("Failed to commit add annotation operations: {}", e))?;
             info!("Annotation added successfully to task: {}", uuid_str);
@@ -777,9 +1578,9 @@ pub extern "system" fn Java_com_tasksquire_data_storage_TaskChampionJniImpl_nati
         };
 
         // On None an exception is pending; nothing further touches env.
-        let _ = run_with_replica(&mut env, replica_ptr, "nativeTaskRemoveAnnotation", |replica| {
+        let _ = run_with_session(&mut env, replica_ptr, "nativeTaskRemoveAnnotation", |session| {
             let mut ops = Operations::new();
-            let mut task = replica
+            let mut task = session.replica
                 .get_task(task_uuid)
                 .map_err(|e| format!("Failed to get task: {}", e))?
                 .ok_or_else(|| format!("Task not found: {}", uuid_str))?;
@@ -789,7 +1590,7 @@ pub extern "system" fn Java_com_tasksquire_data_storage_TaskChampionJniImpl_nati
             task.set_value("modified", Some(now), &mut ops)
                 .map_err(|e| format!("Failed to set modified timestamp: {}", e))?;
             drop(task);
-            replica
+            session
                 .commit_operations(ops)
                 .map_err(|e| format!("Failed to commit remove annotation operations: {}", e))?;
             info!("Annotation removed successfully from task: {} at timestamp {}", uuid_str, entry_timestamp);
@@ -798,17 +1599,191 @@ pub extern "system" fn Java_com_tasksquire_data_storage_TaskChampionJniImpl_nati
     })
 }
 
+// Templates
+
+/// Create a pending task from a template spec whose placeholders have
+/// been substituted. Returns the new task's UUID.
+fn create_task_from_spec(session: &mut ReplicaSession, spec: &TaskSpec) -> Result<Uuid, String> {
+    let uuid = Uuid::new_v4();
+    let now = Utc::now();
+    let mut ops = Operations::new();
+    let mut task = session.replica
+        .create_task(uuid, &mut ops)
+        .map_err(|e| format!("Failed to create task: {}", e))?;
+    task.set_value("entry", Some(now.timestamp().to_string()), &mut ops)
+        .map_err(|e| format!("Failed to set entry timestamp: {}", e))?;
+    set_task_status(&mut task, Status::Pending, &mut ops)?;
+    spec.apply(&mut task, now, &mut ops)?;
+    drop(task);
+    session
+        .commit_operations(ops)
+        .map_err(|e| format!("Failed to commit template task operations: {}", e))?;
+    Ok(uuid)
+}
+
+/// Every stored template as a JSON document `{"name": ..., "template": {...}}`.
+fn template_docs(session: &mut ReplicaSession) -> Result<Vec<String>, String> {
+    templates::list(session.journal()?)?
+        .into_iter()
+        .map(|(name, spec)| {
+            serde_json::to_string(&serde_json::json!({ "name": name, "template": spec }))
+                .map_err(|e| format!("Failed to serialize template to JSON: {}", e))
+        })
+        .collect()
+}
+
+#[no_mangle]
+pub extern "system" fn Java_com_tasksquire_data_storage_TaskChampionJniImpl_nativeSaveTemplate<'local>(
+    mut env: JNIEnv<'local>,
+    _class: JClass,
+    replica_ptr: jlong,
+    name: JString<'local>,
+    template_json: JString<'local>,
+) {
+    catch_panics!(&mut env, "nativeSaveTemplate", (), {
+        let name = match read_jstring(&mut env, &name, "name") { Some(s) => s, None => return };
+        let json = match read_jstring(&mut env, &template_json, "templateJson") { Some(s) => s, None => return };
+        if name.trim().is_empty() {
+            throw(&mut env, EXC_INVALID_ARGUMENT, "Template name must not be empty");
+            return;
+        }
+        let spec = match parse_spec(&mut env, &json) { Some(spec) => spec, None => return };
+
+        // On None an exception is pending; nothing further touches env.
+        let _ = run_with_session(&mut env, replica_ptr, "nativeSaveTemplate", |session| {
+            templates::save(session.journal()?, &name, &spec)?;
+            info!("Template saved: '{}'", name);
+            Ok(())
+        });
+    })
+}
+
+#[no_mangle]
+pub extern "system" fn Java_com_tasksquire_data_storage_TaskChampionJniImpl_nativeDeleteTemplate<'local>(
+    mut env: JNIEnv<'local>,
+    _class: JClass,
+    replica_ptr: jlong,
+    name: JString<'local>,
+) -> jboolean {
+    catch_panics!(&mut env, "nativeDeleteTemplate", 0, {
+        let name = match read_jstring(&mut env, &name, "name") { Some(s) => s, None => return 0 };
+
+        // On None an exception is pending; 0 is the sentinel.
+        run_with_session(&mut env, replica_ptr, "nativeDeleteTemplate", |session| {
+            let removed = templates::delete(session.journal()?, &name)?;
+            info!("Template '{}' {}", name, if removed { "deleted" } else { "not found" });
+            Ok(removed as jboolean)
+        })
+        .unwrap_or(0)
+    })
+}
+
+#[no_mangle]
+pub extern "system" fn Java_com_tasksquire_data_storage_TaskChampionJniImpl_nativeListTemplates<'local>(
+    mut env: JNIEnv<'local>,
+    _class: JClass,
+    replica_ptr: jlong,
+) -> jobjectArray {
+    catch_panics!(&mut env, "nativeListTemplates", std::ptr::null_mut(), {
+        let docs = run_with_session(&mut env, replica_ptr, "nativeListTemplates", template_docs);
+
+        let Some(docs) = docs else {
+            // Exception pending; any further env call would abort the process.
+            return std::ptr::null_mut();
+        };
+
+        create_string_array(&mut env, docs)
+    })
+}
+
+#[no_mangle]
+pub extern "system" fn Java_com_tasksquire_data_storage_TaskChampionJniImpl_nativeCreateFromTemplate<'local>(
+    mut env: JNIEnv<'local>,
+    _class: JClass,
+    replica_ptr: jlong,
+    name: JString<'local>,
+    params_json: JString<'local>,
+) -> JString<'local> {
+    catch_panics!(&mut env, "nativeCreateFromTemplate", JObject::null().into(), {
+        let name = match read_jstring(&mut env, &name, "name") { Some(s) => s, None => return JObject::null().into() };
+        let params = if params_json.is_null() {
+            Default::default()
+        } else {
+            let json = match read_jstring(&mut env, &params_json, "paramsJson") { Some(s) => s, None => return JObject::null().into() };
+            match templates::parse_params(&json) {
+                Ok(params) => params,
+                Err(e) => {
+                    throw(&mut env, EXC_INVALID_ARGUMENT, &e);
+                    return JObject::null().into();
+                }
+            }
+        };
+
+        // Inner None signals "no such template" — returned to Java as
+        // null; an inner Err is a placeholder without a value or a tag
+        // that is invalid once substituted. Outer None signals a thrown
+        // exception.
+        let created: Option<Option<Result<Uuid, SpecError>>> =
+            run_with_session(&mut env, replica_ptr, "nativeCreateFromTemplate", |session| {
+                let Some(spec) = templates::get(session.journal()?, &name)? else {
+                    warn!("No template named '{}'", name);
+                    return Ok(None);
+                };
+                let spec = match spec.substitute(&params, Utc::now()) {
+                    Ok(spec) => spec,
+                    Err(e) => return Ok(Some(Err(e))),
+                };
+                let created = create_task_from_spec(session, &spec)?;
+                info!("Task {} created from template '{}'", created, name);
+                Ok(Some(Ok(created)))
+            });
+
+        let Some(created) = created else {
+            // Exception pending; any further env call would abort the process.
+            return JObject::null().into();
+        };
+
+        match created {
+            Some(Ok(uuid)) => match env.new_string(uuid.to_string()) {
+                Ok(java_string) => java_string,
+                Err(e) => {
+                    error!("Failed to create Java string for task UUID: {:?}", e);
+                    throw(&mut env, EXC_STORAGE, &format!("Failed to marshal task UUID: {}", e));
+                    JObject::null().into()
+                }
+            },
+            Some(Err(e)) => {
+                throw_spec_error(&mut env, &e);
+                JObject::null().into()
+            }
+            None => JObject::null().into(),
+        }
+    })
+}
+
 // Data retrieval
 
+/// The task's raw key/value map.
+///
+/// Task::get_taskmap is deprecated in favour of TaskData::properties, but
+/// TaskData is only reachable by consuming the Task (into_task_data), and
+/// no other &Task accessor enumerates the raw key/value map. Its content
+/// is identical to what Replica::get_task_data returns for the same task.
+pub(crate) fn raw_taskmap(task: &Task) -> &HashMap<String, String> {
+    #[allow(deprecated)]
+    task.get_taskmap()
+}
+
 /// Build the JSON document for a single task, in the schema documented on
-/// nativeGetTaskData: uuid, description?, status?, entry?, modified?,
+/// nativeGetTaskData: uuid, id?, description?, status?, entry?, modified?,
 /// tags[], annotations[{entry, description}], udas{}.
 ///
 /// Well-known fields are plucked from the raw key/value map; keys starting
 /// with `tag_` and `annotation_` (taskchampion's structural encoding) are
 /// skipped in favour of the tags/annotations arrays; everything else is
-/// routed to udas.
-fn task_to_json(uuid_str: &str, task: &Task) -> Result<String, String> {
+/// routed to udas. `working_set_id` is the task's 1-based working-set
+/// index, emitted as the numeric "id" field when present.
+fn task_to_json(uuid_str: &str, task: &Task, working_set_id: Option<usize>) -> Result<String, String> {
     use serde_json::{json, Map, Value};
 
     let mut udas = Map::new();
@@ -817,14 +1792,7 @@ fn task_to_json(uuid_str: &str, task: &Task) -> Result<String, String> {
     let mut entry: Option<Value> = None;
     let mut modified: Option<Value> = None;
 
-    // Task::get_taskmap is deprecated in favour of TaskData::properties,
-    // but TaskData is only reachable by consuming the Task
-    // (into_task_data), and no other &Task accessor enumerates the raw
-    // key/value map. Its content is identical to what
-    // Replica::get_task_data returns for the same task.
-    #[allow(deprecated)]
-    let taskmap = task.get_taskmap();
-    for (key, value) in taskmap.iter() {
+    for (key, value) in raw_taskmap(task).iter() {
         match key.as_str() {
             "description" => description = Some(Value::String(value.clone())),
             "status" => status = Some(Value::String(value.clone())),
@@ -853,6 +1821,7 @@ fn task_to_json(uuid_str: &str, task: &Task) -> Result<String, String> {
 
     let mut root = Map::new();
     root.insert("uuid".to_string(), Value::String(uuid_str.to_string()));
+    if let Some(id) = working_set_id { root.insert("id".to_string(), json!(id)); }
     if let Some(v) = description { root.insert("description".to_string(), v); }
     if let Some(v) = status { root.insert("status".to_string(), v); }
     if let Some(v) = entry { root.insert("entry".to_string(), v); }
@@ -900,115 +1869,1297 @@ pub extern "system" fn Java_com_tasksquire_data_storage_TaskChampionJniImpl_nati
             let tasks = replica
                 .all_tasks()
                 .map_err(|e| format!("Failed to get all tasks: {}", e))?;
+            let working_set = replica
+                .working_set()
+                .map_err(|e| format!("Failed to get working set: {}", e))?;
             let mut docs = Vec::with_capacity(tasks.len());
             for (uuid, task) in tasks.iter() {
-                docs.push(task_to_json(&uuid.to_string(), task)?);
+                docs.push(task_to_json(&uuid.to_string(), task, working_set.by_uuid(*uuid))?);
             }
             info!("Retrieved {} tasks", docs.len());
             Ok(docs)
         });
 
-        let Some(task_docs) = task_docs else {
+        let Some(task_docs) = task_docs else {
+            // Exception pending; any further env call would abort the process.
+            return std::ptr::null_mut();
+        };
+
+        create_string_array(&mut env, task_docs)
+    })
+}
+
+/// Every task accepted by `filter`, in unspecified order.
+fn matching_tasks(replica: &mut Replica, filter: &Filter) -> Result<Vec<Task>, String> {
+    let tasks = replica
+        .all_tasks()
+        .map_err(|e| format!("Failed to get all tasks: {}", e))?;
+    Ok(tasks.into_values().filter(|task| filter.matches(task)).collect())
+}
+
+/// Every task accepted by `filter`, in the order the exporters write
+/// them: by entry date (tasks without one first), then UUID.
+pub(crate) fn exported_tasks(replica: &mut Replica, filter: &Filter) -> Result<Vec<Task>, String> {
+    let mut tasks = matching_tasks(replica, filter)?;
+    tasks.sort_by_key(|task| (task.get_entry(), task.get_uuid()));
+    Ok(tasks)
+}
+
+/// Build the JSON document of every task accepted by `filter`, in the
+/// same schema as task_to_json. Element order is unspecified.
+fn query_task_docs(replica: &mut Replica, filter: &Filter) -> Result<Vec<String>, String> {
+    let tasks = matching_tasks(replica, filter)?;
+    let working_set = replica
+        .working_set()
+        .map_err(|e| format!("Failed to get working set: {}", e))?;
+    tasks
+        .iter()
+        .map(|task| task_to_json(&task.get_uuid().to_string(), task, working_set.by_uuid(task.get_uuid())))
+        .collect()
+}
+
+/// Build the JSON documents for one page of the tasks accepted by
+/// `filter`, ordered by `sort_keys`. Only the requested page is
+/// serialised.
+fn query_task_page(
+    replica: &mut Replica,
+    filter: &Filter,
+    sort_keys: &[SortKey],
+    offset: usize,
+    limit: usize,
+) -> Result<Vec<String>, String> {
+    let mut tasks = matching_tasks(replica, filter)?;
+    tasks.sort_by(|a, b| sort::compare(a, b, sort_keys));
+    let working_set = replica
+        .working_set()
+        .map_err(|e| format!("Failed to get working set: {}", e))?;
+    tasks
+        .iter()
+        .skip(offset)
+        .take(limit)
+        .map(|task| task_to_json(&task.get_uuid().to_string(), task, working_set.by_uuid(task.get_uuid())))
+        .collect()
+}
+
+#[no_mangle]
+pub extern "system" fn Java_com_tasksquire_data_storage_TaskChampionJniImpl_nativeQueryTasks<'local>(
+    mut env: JNIEnv<'local>,
+    _class: JClass,
+    replica_ptr: jlong,
+    filter: JString<'local>,
+) -> jobjectArray {
+    catch_panics!(&mut env, "nativeQueryTasks", std::ptr::null_mut(), {
+        let filter_str = if filter.is_null() {
+            String::new()
+        } else {
+            match read_jstring(&mut env, &filter, "filter") { Some(s) => s, None => return std::ptr::null_mut() }
+        };
+        let parsed = match parse_filter(&mut env, &filter_str) { Some(f) => f, None => return std::ptr::null_mut() };
+
+        let task_docs = run_with_replica(&mut env, replica_ptr, "nativeQueryTasks", |replica| {
+            let docs = query_task_docs(replica, &parsed)?;
+            info!("Filter '{}' matched {} tasks", filter_str, docs.len());
+            Ok(docs)
+        });
+
+        let Some(task_docs) = task_docs else {
+            // Exception pending; any further env call would abort the process.
+            return std::ptr::null_mut();
+        };
+
+        create_string_array(&mut env, task_docs)
+    })
+}
+
+#[no_mangle]
+pub extern "system" fn Java_com_tasksquire_data_storage_TaskChampionJniImpl_nativeQueryTasksPage<'local>(
+    mut env: JNIEnv<'local>,
+    _class: JClass,
+    replica_ptr: jlong,
+    filter: JString<'local>,
+    sort: JString<'local>,
+    offset: jint,
+    limit: jint,
+) -> jobjectArray {
+    catch_panics!(&mut env, "nativeQueryTasksPage", std::ptr::null_mut(), {
+        let filter_str = if filter.is_null() {
+            String::new()
+        } else {
+            match read_jstring(&mut env, &filter, "filter") { Some(s) => s, None => return std::ptr::null_mut() }
+        };
+        let sort_str = if sort.is_null() {
+            String::new()
+        } else {
+            match read_jstring(&mut env, &sort, "sort") { Some(s) => s, None => return std::ptr::null_mut() }
+        };
+        if offset < 0 || limit <= 0 {
+            throw(
+                &mut env,
+                EXC_INVALID_QUERY,
+                &format!("Invalid page (offset={}, limit={}); offset must be >= 0 and limit > 0", offset, limit),
+            );
+            return std::ptr::null_mut();
+        }
+        let parsed = match parse_filter(&mut env, &filter_str) { Some(f) => f, None => return std::ptr::null_mut() };
+        let sort_keys = match parse_sort(&mut env, &sort_str) { Some(k) => k, None => return std::ptr::null_mut() };
+
+        let task_docs = run_with_replica(&mut env, replica_ptr, "nativeQueryTasksPage", |replica| {
+            let docs = query_task_page(replica, &parsed, &sort_keys, offset as usize, limit as usize)?;
+            info!("Filter '{}' page at offset {} returned {} tasks", filter_str, offset, docs.len());
+            Ok(docs)
+        });
+
+        let Some(task_docs) = task_docs else {
+            // Exception pending; any further env call would abort the process.
+            return std::ptr::null_mut();
+        };
+
+        create_string_array(&mut env, task_docs)
+    })
+}
+
+#[no_mangle]
+pub extern "system" fn Java_com_tasksquire_data_storage_TaskChampionJniImpl_nativeCountTasks<'local>(
+    mut env: JNIEnv<'local>,
+    _class: JClass,
+    replica_ptr: jlong,
+    filter: JString<'local>,
+) -> jint {
+    catch_panics!(&mut env, "nativeCountTasks", 0, {
+        let filter_str = if filter.is_null() {
+            String::new()
+        } else {
+            match read_jstring(&mut env, &filter, "filter") { Some(s) => s, None => return 0 }
+        };
+        let parsed = match parse_filter(&mut env, &filter_str) { Some(f) => f, None => return 0 };
+
+        // On None an exception is pending; 0 is the sentinel.
+        run_with_replica(&mut env, replica_ptr, "nativeCountTasks", |replica| {
+            let count = matching_tasks(replica, &parsed)?.len();
+            info!("Filter '{}' matched {} tasks", filter_str, count);
+            Ok(count as jint)
+        })
+        .unwrap_or(0)
+    })
+}
+
+/// Build the JSON documents of the best `limit` tasks matching the
+/// full-text `query` and accepted by `filter`, in rank order.
+fn search_task_docs(
+    session: &mut ReplicaSession,
+    query: &str,
+    filter: &Filter,
+    limit: usize,
+) -> Result<Vec<String>, String> {
+    let hits = session.search_index()?.search(query);
+    let working_set = session
+        .replica
+        .working_set()
+        .map_err(|e| format!("Failed to get working set: {}", e))?;
+    let mut docs = Vec::new();
+    for (uuid, _score) in hits {
+        if docs.len() == limit {
+            break;
+        }
+        let task = session
+            .replica
+            .get_task(uuid)
+            .map_err(|e| format!("Failed to get task: {}", e))?;
+        if let Some(task) = task.filter(|t| filter.matches(t)) {
+            docs.push(task_to_json(&uuid.to_string(), &task, working_set.by_uuid(uuid))?);
+        }
+    }
+    Ok(docs)
+}
+
+#[no_mangle]
+pub extern "system" fn Java_com_tasksquire_data_storage_TaskChampionJniImpl_nativeSearchTasks<'local>(
+    mut env: JNIEnv<'local>,
+    _class: JClass,
+    replica_ptr: jlong,
+    query: JString<'local>,
+    filter: JString<'local>,
+    limit: jint,
+) -> jobjectArray {
+    catch_panics!(&mut env, "nativeSearchTasks", std::ptr::null_mut(), {
+        let query_str = match read_jstring(&mut env, &query, "query") { Some(s) => s, None => return std::ptr::null_mut() };
+        let filter_str = if filter.is_null() {
+            String::new()
+        } else {
+            match read_jstring(&mut env, &filter, "filter") { Some(s) => s, None => return std::ptr::null_mut() }
+        };
+        if limit <= 0 {
+            throw(&mut env, EXC_INVALID_QUERY, &format!("Invalid limit {}; limit must be > 0", limit));
+            return std::ptr::null_mut();
+        }
+        let parsed = match parse_filter(&mut env, &filter_str) { Some(f) => f, None => return std::ptr::null_mut() };
+
+        let task_docs = run_with_session(&mut env, replica_ptr, "nativeSearchTasks", |session| {
+            let docs = search_task_docs(session, &query_str, &parsed, limit as usize)?;
+            info!("Search '{}' returned {} tasks", query_str, docs.len());
+            Ok(docs)
+        });
+
+        let Some(task_docs) = task_docs else {
+            // Exception pending; any further env call would abort the process.
+            return std::ptr::null_mut();
+        };
+
+        create_string_array(&mut env, task_docs)
+    })
+}
+
+/// Build the JSON document of each requested task, in input order, with
+/// `None` for UUIDs that name no task.
+fn task_docs_by_uuid(replica: &mut Replica, uuids: &[Uuid]) -> Result<Vec<Option<String>>, String> {
+    let working_set = replica
+        .working_set()
+        .map_err(|e| format!("Failed to get working set: {}", e))?;
+    let mut docs = Vec::with_capacity(uuids.len());
+    for uuid in uuids {
+        let task = replica
+            .get_task(*uuid)
+            .map_err(|e| format!("Failed to get task: {}", e))?;
+        docs.push(match task {
+            Some(task) => Some(task_to_json(&uuid.to_string(), &task, working_set.by_uuid(*uuid))?),
+            None => None,
+        });
+    }
+    Ok(docs)
+}
+
+#[no_mangle]
+pub extern "system" fn Java_com_tasksquire_data_storage_TaskChampionJniImpl_nativeGetTasks<'local>(
+    mut env: JNIEnv<'local>,
+    _class: JClass,
+    replica_ptr: jlong,
+    uuids: JObjectArray<'local>,
+) -> jobjectArray {
+    catch_panics!(&mut env, "nativeGetTasks", std::ptr::null_mut(), {
+        let uuid_strs = match read_jstring_array(&mut env, &uuids, "uuids") { Some(v) => v, None => return std::ptr::null_mut() };
+
+        // Validate every UUID before taking the lock, so a bad element
+        // fails the whole call without touching the replica.
+        let mut task_uuids = Vec::with_capacity(uuid_strs.len());
+        for (i, uuid_str) in uuid_strs.iter().enumerate() {
+            let Some(uuid_str) = uuid_str else {
+                throw(&mut env, EXC_INVALID_UUID, &format!("Null UUID at index {}", i));
+                return std::ptr::null_mut();
+            };
+            match parse_uuid(&mut env, uuid_str) { Some(u) => task_uuids.push(u), None => return std::ptr::null_mut() }
+        }
+
+        let task_docs = run_with_replica(&mut env, replica_ptr, "nativeGetTasks", |replica| {
+            let docs = task_docs_by_uuid(replica, &task_uuids)?;
+            info!(
+                "Retrieved {} of {} requested tasks",
+                docs.iter().filter(|d| d.is_some()).count(),
+                docs.len()
+            );
+            Ok(docs)
+        });
+
+        let Some(task_docs) = task_docs else {
+            // Exception pending; any further env call would abort the process.
+            return std::ptr::null_mut();
+        };
+
+        create_nullable_string_array(&mut env, task_docs)
+    })
+}
+
+#[no_mangle]
+pub extern "system" fn Java_com_tasksquire_data_storage_TaskChampionJniImpl_nativeGetTaskData<'local>(
+    mut env: JNIEnv<'local>,
+    _class: JClass,
+    replica_ptr: jlong,
+    uuid: JString,
+) -> JString<'local> {
+    catch_panics!(&mut env, "nativeGetTaskData", JObject::null().into(), {
+    let uuid_str = match read_jstring(&mut env, &uuid, "uuid") { Some(s) => s, None => return JObject::null().into() };
+    let task_uuid = match parse_uuid(&mut env, &uuid_str) { Some(u) => u, None => return JObject::null().into() };
+
+    // Inner None signals "task not found" — returned to Java as null.
+    // Outer None signals a thrown exception. Storage errors throw.
+    let json_result: Option<Option<String>> = run_with_replica(&mut env, replica_ptr, "nativeGetTaskData", |replica| {
+        let task = match replica
+            .get_task(task_uuid)
+            .map_err(|e| format!("Failed to get task: {}", e))?
+        {
+            Some(t) => t,
+            None => {
+                info!("Task not found: {}", uuid_str);
+                return Ok(None);
+            }
+        };
+
+        let working_set = replica
+            .working_set()
+            .map_err(|e| format!("Failed to get working set: {}", e))?;
+        let json = task_to_json(&uuid_str, &task, working_set.by_uuid(task_uuid))?;
+        info!("Retrieved task data for: {}", uuid_str);
+        Ok(Some(json))
+    });
+
+    let Some(json_result) = json_result else {
+        // Exception pending; any further env call would abort the process.
+        return JObject::null().into();
+    };
+
+    match json_result {
+        Some(json) => match env.new_string(&json) {
+            Ok(java_string) => java_string,
+            Err(e) => {
+                error!("Failed to create Java string for task data: {:?}", e);
+                throw(&mut env, EXC_STORAGE, &format!("Failed to marshal task data: {}", e));
+                JObject::null().into()
+            }
+        },
+        None => JObject::null().into(),
+    }
+    })
+}
+
+/// The task at working-set `index`, if any.
+fn uuid_for_index(replica: &mut Replica, index: jint) -> Result<Option<Uuid>, String> {
+    let working_set = replica
+        .working_set()
+        .map_err(|e| format!("Failed to get working set: {}", e))?;
+    // WorkingSet::by_index takes the same 1-based TaskWarrior ID that
+    // nativeGetWorkingSet reports; 0 and negatives are never valid.
+    Ok((index > 0)
+        .then(|| working_set.by_index(index as usize))
+        .flatten()
+        .filter(|uuid| !uuid.is_nil()))
+}
+
+#[no_mangle]
+pub extern "system" fn Java_com_tasksquire_data_storage_TaskChampionJniImpl_nativeGetUuidForIndex<'local>(
+    mut env: JNIEnv<'local>,
+    _class: JClass,
+    replica_ptr: jlong,
+    index: jint,
+) -> JString<'local> {
+    catch_panics!(&mut env, "nativeGetUuidForIndex", JObject::null().into(), {
+    // Inner None signals "no task at this index" — returned to Java as
+    // null. Outer None signals a thrown exception.
+    let uuid_string: Option<Option<String>> = run_with_replica(&mut env, replica_ptr, "nativeGetUuidForIndex", |replica| {
+        let result = uuid_for_index(replica, index)?.map(|uuid| uuid.to_string());
+        match result.as_ref() {
+            Some(s) => info!("Found UUID {} for index {}", s, index),
+            None => info!("No task found at index {}", index),
+        }
+        Ok(result)
+    });
+
+    let Some(uuid_string) = uuid_string else {
+        // Exception pending; any further env call would abort the process.
+        return JObject::null().into();
+    };
+
+    match uuid_string {
+        Some(s) => match env.new_string(s) {
+            Ok(jstr) => jstr,
+            Err(e) => {
+                error!("Failed to create JString for UUID: {:?}", e);
+                throw(&mut env, EXC_STORAGE, &format!("Failed to marshal UUID: {}", e));
+                JObject::null().into()
+            }
+        },
+        None => JObject::null().into(),
+    }
+    })
+}
+
+#[no_mangle]
+pub extern "system" fn Java_com_tasksquire_data_storage_TaskChampionJniImpl_nativeGetIndexForUuid(
+    mut env: JNIEnv,
+    _class: JClass,
+    replica_ptr: jlong,
+    uuid: JString,
+) -> jint {
+    catch_panics!(&mut env, "nativeGetIndexForUuid", 0, {
+        let uuid_str = match read_jstring(&mut env, &uuid, "uuid") { Some(s) => s, None => return 0 };
+        let task_uuid = match parse_uuid(&mut env, &uuid_str) { Some(u) => u, None => return 0 };
+
+        // 0 doubles as "not in the working set" (indices are 1-based) and
+        // as the sentinel when an exception is pending.
+        run_with_replica(&mut env, replica_ptr, "nativeGetIndexForUuid", |replica| {
+            let working_set = replica
+                .working_set()
+                .map_err(|e| format!("Failed to get working set: {}", e))?;
+            let index = working_set.by_uuid(task_uuid).unwrap_or(0);
+            match index {
+                0 => info!("Task {} is not in the working set", uuid_str),
+                i => info!("Found index {} for UUID {}", i, uuid_str),
+            }
+            Ok(index as jint)
+        })
+        .unwrap_or(0)
+    })
+}
+
+/// Build one JSON document per working-set entry, in index order:
+/// `{"id": <1-based index>, "uuid": "..."}`. Vacant indices are omitted.
+fn working_set_docs(replica: &mut Replica) -> Result<Vec<String>, String> {
+    let working_set = replica
+        .working_set()
+        .map_err(|e| format!("Failed to get working set: {}", e))?;
+    working_set
+        .iter()
+        .map(|(id, uuid)| {
+            serde_json::to_string(&serde_json::json!({ "id": id, "uuid": uuid.to_string() }))
+                .map_err(|e| format!("Failed to serialize working set entry to JSON: {}", e))
+        })
+        .collect()
+}
+
+/// Build the JSON document of every pending task in the working set, in
+/// index order, each carrying its "id". Working-set entries whose task is
+/// no longer pending (completed or deleted since the last rebuild) are
+/// skipped.
+fn pending_task_docs(replica: &mut Replica) -> Result<Vec<String>, String> {
+    let working_set = replica
+        .working_set()
+        .map_err(|e| format!("Failed to get working set: {}", e))?;
+    let mut pending: std::collections::HashMap<Uuid, Task> = replica
+        .pending_tasks()
+        .map_err(|e| format!("Failed to get pending tasks: {}", e))?
+        .into_iter()
+        .map(|task| (task.get_uuid(), task))
+        .collect();
+    let mut docs = Vec::with_capacity(pending.len());
+    for (id, uuid) in working_set.iter() {
+        let Some(task) = pending.remove(&uuid) else { continue };
+        if task.get_status() == Status::Pending {
+            docs.push(task_to_json(&uuid.to_string(), &task, Some(id))?);
+        }
+    }
+    Ok(docs)
+}
+
+#[no_mangle]
+pub extern "system" fn Java_com_tasksquire_data_storage_TaskChampionJniImpl_nativeGetWorkingSet<'local>(
+    mut env: JNIEnv<'local>,
+    _class: JClass,
+    replica_ptr: jlong,
+) -> jobjectArray {
+    catch_panics!(&mut env, "nativeGetWorkingSet", std::ptr::null_mut(), {
+        let entries = run_with_replica(&mut env, replica_ptr, "nativeGetWorkingSet", |replica| {
+            let docs = working_set_docs(replica)?;
+            info!("Working set has {} entries", docs.len());
+            Ok(docs)
+        });
+
+        let Some(entries) = entries else {
+            // Exception pending; any further env call would abort the process.
+            return std::ptr::null_mut();
+        };
+
+        create_string_array(&mut env, entries)
+    })
+}
+
+#[no_mangle]
+pub extern "system" fn Java_com_tasksquire_data_storage_TaskChampionJniImpl_nativeGetPendingTasks<'local>(
+    mut env: JNIEnv<'local>,
+    _class: JClass,
+    replica_ptr: jlong,
+) -> jobjectArray {
+    catch_panics!(&mut env, "nativeGetPendingTasks", std::ptr::null_mut(), {
+        let task_docs = run_with_replica(&mut env, replica_ptr, "nativeGetPendingTasks", |replica| {
+            let docs = pending_task_docs(replica)?;
+            info!("Retrieved {} pending tasks", docs.len());
+            Ok(docs)
+        });
+
+        let Some(task_docs) = task_docs else {
+            // Exception pending; any further env call would abort the process.
+            return std::ptr::null_mut();
+        };
+
+        create_string_array(&mut env, task_docs)
+    })
+}
+
+// History
+
+#[no_mangle]
+pub extern "system" fn Java_com_tasksquire_data_storage_TaskChampionJniImpl_nativeGetTaskHistory<'local>(
+    mut env: JNIEnv<'local>,
+    _class: JClass,
+    replica_ptr: jlong,
+    uuid: JString<'local>,
+) -> jobjectArray {
+    catch_panics!(&mut env, "nativeGetTaskHistory", std::ptr::null_mut(), {
+        let uuid_str = match read_jstring(&mut env, &uuid, "uuid") { Some(s) => s, None => return std::ptr::null_mut() };
+        let task_uuid = match parse_uuid(&mut env, &uuid_str) { Some(u) => u, None => return std::ptr::null_mut() };
+
+        let entries = run_with_replica(&mut env, replica_ptr, "nativeGetTaskHistory", |replica| {
+            let entries = history::task_history(replica, task_uuid)?;
+            info!("Task {} has {} history entries", uuid_str, entries.len());
+            Ok(entries)
+        });
+
+        let Some(entries) = entries else {
+            // Exception pending; any further env call would abort the process.
+            return std::ptr::null_mut();
+        };
+
+        create_string_array(&mut env, entries)
+    })
+}
+
+// Import and export
+
+#[no_mangle]
+pub extern "system" fn Java_com_tasksquire_data_storage_TaskChampionJniImpl_nativeExportTaskwarriorJson<'local>(
+    mut env: JNIEnv<'local>,
+    _class: JClass,
+    replica_ptr: jlong,
+    filter: JString<'local>,
+) -> JString<'local> {
+    catch_panics!(&mut env, "nativeExportTaskwarriorJson", JObject::null().into(), {
+        let filter_str = if filter.is_null() {
+            String::new()
+        } else {
+            match read_jstring(&mut env, &filter, "filter") { Some(s) => s, None => return JObject::null().into() }
+        };
+        let parsed = match parse_filter(&mut env, &filter_str) { Some(f) => f, None => return JObject::null().into() };
+
+        let exported = run_with_replica(&mut env, replica_ptr, "nativeExportTaskwarriorJson", |replica| {
+            let exported = taskwarrior::export(replica, &parsed)?;
+            info!("Exported TaskWarrior JSON for filter '{}' ({} bytes)", filter_str, exported.len());
+            Ok(exported)
+        });
+
+        let Some(exported) = exported else {
+            // Exception pending; any further env call would abort the process.
+            return JObject::null().into();
+        };
+
+        match env.new_string(&exported) {
+            Ok(java_string) => java_string,
+            Err(e) => {
+                error!("Failed to create Java string for export: {:?}", e);
+                throw(&mut env, EXC_STORAGE, &format!("Failed to marshal export: {}", e));
+                JObject::null().into()
+            }
+        }
+    })
+}
+
+#[no_mangle]
+pub extern "system" fn Java_com_tasksquire_data_storage_TaskChampionJniImpl_nativeImportTaskwarriorJson<'local>(
+    mut env: JNIEnv<'local>,
+    _class: JClass,
+    replica_ptr: jlong,
+    json_or_path: JString<'local>,
+) -> JString<'local> {
+    catch_panics!(&mut env, "nativeImportTaskwarriorJson", JObject::null().into(), {
+        let input = match read_jstring(&mut env, &json_or_path, "jsonOrPath") { Some(s) => s, None => return JObject::null().into() };
+        // A document starts with an array or object; anything else is a path.
+        let text = if input.trim_start().starts_with(['[', '{']) {
+            input
+        } else {
+            match std::fs::read_to_string(&input) {
+                Ok(text) => text,
+                Err(e) => {
+                    throw(&mut env, EXC_STORAGE, &format!("Failed to read import file {}: {}", input, e));
+                    return JObject::null().into();
+                }
+            }
+        };
+        let tasks = match taskwarrior::parse_export(&text) {
+            Ok(tasks) => tasks,
+            Err(e) => {
+                throw(&mut env, EXC_INVALID_QUERY, &e);
+                return JObject::null().into();
+            }
+        };
+
+        let report = run_with_session(&mut env, replica_ptr, "nativeImportTaskwarriorJson", |session| {
+            let mut ops = Operations::new();
+            let report = taskwarrior::import(&mut session.replica, &tasks, &mut ops)?;
+            session
+                .commit_operations(ops)
+                .map_err(|e| format!("Failed to commit import operations: {}", e))?;
+            info!(
+                "Imported TaskWarrior JSON: {} created, {} updated, {} skipped",
+                report.created.len(),
+                report.updated.len(),
+                report.skipped.len()
+            );
+            report.to_json()
+        });
+
+        let Some(report) = report else {
+            // Exception pending; any further env call would abort the process.
+            return JObject::null().into();
+        };
+
+        match env.new_string(&report) {
+            Ok(java_string) => java_string,
+            Err(e) => {
+                error!("Failed to create Java string for import report: {:?}", e);
+                throw(&mut env, EXC_STORAGE, &format!("Failed to marshal import report: {}", e));
+                JObject::null().into()
+            }
+        }
+    })
+}
+
+#[no_mangle]
+pub extern "system" fn Java_com_tasksquire_data_storage_TaskChampionJniImpl_nativeExportIcs<'local>(
+    mut env: JNIEnv<'local>,
+    _class: JClass,
+    replica_ptr: jlong,
+    filter: JString<'local>,
+) -> JString<'local> {
+    catch_panics!(&mut env, "nativeExportIcs", JObject::null().into(), {
+        let filter_str = if filter.is_null() {
+            String::new()
+        } else {
+            match read_jstring(&mut env, &filter, "filter") { Some(s) => s, None => return JObject::null().into() }
+        };
+        let parsed = match parse_filter(&mut env, &filter_str) { Some(f) => f, None => return JObject::null().into() };
+
+        let exported = run_with_replica(&mut env, replica_ptr, "nativeExportIcs", |replica| {
+            let exported = ical::export(replica, &parsed)?;
+            info!("Exported iCalendar for filter '{}' ({} bytes)", filter_str, exported.len());
+            Ok(exported)
+        });
+
+        let Some(exported) = exported else {
+            // Exception pending; any further env call would abort the process.
+            return JObject::null().into();
+        };
+
+        match env.new_string(&exported) {
+            Ok(java_string) => java_string,
+            Err(e) => {
+                error!("Failed to create Java string for export: {:?}", e);
+                throw(&mut env, EXC_STORAGE, &format!("Failed to marshal export: {}", e));
+                JObject::null().into()
+            }
+        }
+    })
+}
+
+#[no_mangle]
+pub extern "system" fn Java_com_tasksquire_data_storage_TaskChampionJniImpl_nativeImportIcs<'local>(
+    mut env: JNIEnv<'local>,
+    _class: JClass,
+    replica_ptr: jlong,
+    ics: JString<'local>,
+) -> JString<'local> {
+    catch_panics!(&mut env, "nativeImportIcs", JObject::null().into(), {
+        let text = match read_jstring(&mut env, &ics, "ics") { Some(s) => s, None => return JObject::null().into() };
+        let tasks = match ical::parse(&text) {
+            Ok(tasks) => tasks,
+            Err(e) => {
+                throw(&mut env, EXC_INVALID_QUERY, &e);
+                return JObject::null().into();
+            }
+        };
+
+        let report = run_with_session(&mut env, replica_ptr, "nativeImportIcs", |session| {
+            let mut ops = Operations::new();
+            let report = taskwarrior::store_imported(&mut session.replica, tasks, &mut ops)?;
+            session
+                .commit_operations(ops)
+                .map_err(|e| format!("Failed to commit import operations: {}", e))?;
+            info!(
+                "Imported iCalendar: {} created, {} updated, {} skipped",
+                report.created.len(),
+                report.updated.len(),
+                report.skipped.len()
+            );
+            report.to_json()
+        });
+
+        let Some(report) = report else {
+            // Exception pending; any further env call would abort the process.
+            return JObject::null().into();
+        };
+
+        match env.new_string(&report) {
+            Ok(java_string) => java_string,
+            Err(e) => {
+                error!("Failed to create Java string for import report: {:?}", e);
+                throw(&mut env, EXC_STORAGE, &format!("Failed to marshal import report: {}", e));
+                JObject::null().into()
+            }
+        }
+    })
+}
+
+#[no_mangle]
+pub extern "system" fn Java_com_tasksquire_data_storage_TaskChampionJniImpl_nativeExportCsv<'local>(
+    mut env: JNIEnv<'local>,
+    _class: JClass,
+    replica_ptr: jlong,
+    filter: JString<'local>,
+    columns: JObjectArray<'local>,
+    timezone: JString<'local>,
+) -> JString<'local> {
+    catch_panics!(&mut env, "nativeExportCsv", JObject::null().into(), {
+        let filter_str = if filter.is_null() {
+            String::new()
+        } else {
+            match read_jstring(&mut env, &filter, "filter") { Some(s) => s, None => return JObject::null().into() }
+        };
+        let parsed = match parse_filter(&mut env, &filter_str) { Some(f) => f, None => return JObject::null().into() };
+        let columns = if columns.is_null() {
+            tabular::DEFAULT_COLUMNS.iter().map(|c| c.to_string()).collect()
+        } else {
+            match read_jstring_array(&mut env, &columns, "columns") {
+                // A null element is as unusable as an empty name.
+                Some(columns) => columns.into_iter().map(Option::unwrap_or_default).collect(),
+                None => return JObject::null().into(),
+            }
+        };
+        let timezone_str = if timezone.is_null() {
+            None
+        } else {
+            match read_jstring(&mut env, &timezone, "timezone") { Some(s) => Some(s), None => return JObject::null().into() }
+        };
+        let (columns, tz) = match (tabular::parse_columns(columns), tabular::parse_timezone(timezone_str.as_deref())) {
+            (Ok(columns), Ok(tz)) => (columns, tz),
+            (Err(e), _) | (_, Err(e)) => {
+                throw(&mut env, EXC_INVALID_ARGUMENT, &e);
+                return JObject::null().into();
+            }
+        };
+
+        let exported = run_with_replica(&mut env, replica_ptr, "nativeExportCsv", |replica| {
+            let exported = tabular::export_csv(replica, &parsed, &columns, tz)?;
+            info!("Exported CSV for filter '{}' ({} columns, {} bytes)", filter_str, columns.len(), exported.len());
+            Ok(exported)
+        });
+
+        let Some(exported) = exported else {
+            // Exception pending; any further env call would abort the process.
+            return JObject::null().into();
+        };
+
+        match env.new_string(&exported) {
+            Ok(java_string) => java_string,
+            Err(e) => {
+                error!("Failed to create Java string for export: {:?}", e);
+                throw(&mut env, EXC_STORAGE, &format!("Failed to marshal export: {}", e));
+                JObject::null().into()
+            }
+        }
+    })
+}
+
+#[no_mangle]
+pub extern "system" fn Java_com_tasksquire_data_storage_TaskChampionJniImpl_nativeExportMarkdown<'local>(
+    mut env: JNIEnv<'local>,
+    _class: JClass,
+    replica_ptr: jlong,
+    filter: JString<'local>,
+    timezone: JString<'local>,
+) -> JString<'local> {
+    catch_panics!(&mut env, "nativeExportMarkdown", JObject::null().into(), {
+        let filter_str = if filter.is_null() {
+            String::new()
+        } else {
+            match read_jstring(&mut env, &filter, "filter") { Some(s) => s, None => return JObject::null().into() }
+        };
+        let parsed = match parse_filter(&mut env, &filter_str) { Some(f) => f, None => return JObject::null().into() };
+        let timezone_str = if timezone.is_null() {
+            None
+        } else {
+            match read_jstring(&mut env, &timezone, "timezone") { Some(s) => Some(s), None => return JObject::null().into() }
+        };
+        let tz = match tabular::parse_timezone(timezone_str.as_deref()) {
+            Ok(tz) => tz,
+            Err(e) => {
+                throw(&mut env, EXC_INVALID_ARGUMENT, &e);
+                return JObject::null().into();
+            }
+        };
+
+        let exported = run_with_replica(&mut env, replica_ptr, "nativeExportMarkdown", |replica| {
+            let exported = tabular::export_markdown(replica, &parsed, tz)?;
+            info!("Exported Markdown checklist for filter '{}' ({} bytes)", filter_str, exported.len());
+            Ok(exported)
+        });
+
+        let Some(exported) = exported else {
+            // Exception pending; any further env call would abort the process.
+            return JObject::null().into();
+        };
+
+        match env.new_string(&exported) {
+            Ok(java_string) => java_string,
+            Err(e) => {
+                error!("Failed to create Java string for export: {:?}", e);
+                throw(&mut env, EXC_STORAGE, &format!("Failed to marshal export: {}", e));
+                JObject::null().into()
+            }
+        }
+    })
+}
+
+#[no_mangle]
+pub extern "system" fn Java_com_tasksquire_data_storage_TaskChampionJniImpl_nativeExportTodoTxt<'local>(
+    mut env: JNIEnv<'local>,
+    _class: JClass,
+    replica_ptr: jlong,
+    filter: JString<'local>,
+) -> JString<'local> {
+    catch_panics!(&mut env, "nativeExportTodoTxt", JObject::null().into(), {
+        let filter_str = if filter.is_null() {
+            String::new()
+        } else {
+            match read_jstring(&mut env, &filter, "filter") { Some(s) => s, None => return JObject::null().into() }
+        };
+        let parsed = match parse_filter(&mut env, &filter_str) { Some(f) => f, None => return JObject::null().into() };
+
+        let exported = run_with_replica(&mut env, replica_ptr, "nativeExportTodoTxt", |replica| {
+            let exported = todotxt::export(replica, &parsed)?;
+            info!("Exported todo.txt for filter '{}' ({} bytes)", filter_str, exported.len());
+            Ok(exported)
+        });
+
+        let Some(exported) = exported else {
+            // Exception pending; any further env call would abort the process.
+            return JObject::null().into();
+        };
+
+        match env.new_string(&exported) {
+            Ok(java_string) => java_string,
+            Err(e) => {
+                error!("Failed to create Java string for export: {:?}", e);
+                throw(&mut env, EXC_STORAGE, &format!("Failed to marshal export: {}", e));
+                JObject::null().into()
+            }
+        }
+    })
+}
+
+#[no_mangle]
+pub extern "system" fn Java_com_tasksquire_data_storage_TaskChampionJniImpl_nativeImportTodoTxt<'local>(
+    mut env: JNIEnv<'local>,
+    _class: JClass,
+    replica_ptr: jlong,
+    text: JString<'local>,
+) -> JString<'local> {
+    catch_panics!(&mut env, "nativeImportTodoTxt", JObject::null().into(), {
+        // Always the document itself: any line of text is a valid todo.txt
+        // document, so a path could not be told apart from a task.
+        let text = match read_jstring(&mut env, &text, "text") { Some(s) => s, None => return JObject::null().into() };
+        let tasks = todotxt::parse(&text, Utc::now());
+
+        let report = run_with_session(&mut env, replica_ptr, "nativeImportTodoTxt", |session| {
+            let mut ops = Operations::new();
+            let report = todotxt::import(&mut session.replica, tasks, &mut ops)?;
+            session
+                .commit_operations(ops)
+                .map_err(|e| format!("Failed to commit import operations: {}", e))?;
+            info!(
+                "Imported todo.txt: {} created, {} skipped",
+                report.created.len(),
+                report.skipped.len()
+            );
+            report.to_json()
+        });
+
+        let Some(report) = report else {
+            // Exception pending; any further env call would abort the process.
+            return JObject::null().into();
+        };
+
+        match env.new_string(&report) {
+            Ok(java_string) => java_string,
+            Err(e) => {
+                error!("Failed to create Java string for import report: {:?}", e);
+                throw(&mut env, EXC_STORAGE, &format!("Failed to marshal import report: {}", e));
+                JObject::null().into()
+            }
+        }
+    })
+}
+
+// Recurrence
+
+#[no_mangle]
+pub extern "system" fn Java_com_tasksquire_data_storage_TaskChampionJniImpl_nativeGenerateRecurrences<'local>(
+    mut env: JNIEnv<'local>,
+    _class: JClass,
+    replica_ptr: jlong,
+    horizon: JString<'local>,
+) -> jint {
+    catch_panics!(&mut env, "nativeGenerateRecurrences", 0, {
+        let horizon_str = match read_jstring(&mut env, &horizon, "horizon") { Some(s) => s, None => return 0 };
+        let horizon = match dates::parse_date(&horizon_str) {
+            Ok(d) => d,
+            Err(e) => {
+                throw(&mut env, EXC_INVALID_ARGUMENT, &format!("Invalid horizon: {}", e));
+                return 0;
+            }
+        };
+
+        // On None an exception is pending; 0 is the sentinel.
+        run_with_session(&mut env, replica_ptr, "nativeGenerateRecurrences", |session| {
+            let mut ops = Operations::new();
+            let created = recurrence::generate(&mut session.replica, horizon, Utc::now(), &mut ops)?;
+            session
+                .commit_operations(ops)
+                .map_err(|e| format!("Failed to commit recurrence operations: {}", e))?;
+            info!("Generated {} recurrence instances up to {}", created.len(), horizon);
+            Ok(created.len() as jint)
+        })
+        .unwrap_or(0)
+    })
+}
+
+// Maintenance
+
+/// How long nativeCompact keeps deleted tasks, as TaskWarrior does.
+const COMPACT_EXPIRE_DAYS: i64 = 180;
+
+/// Purge every deleted task last modified before `cutoff`, as
+/// `Replica::expire_tasks` does for its fixed 180 days. A deleted task
+/// without a `modified` timestamp falls back to its `end`; one with
+/// neither is kept. Returns the number of tasks purged.
+fn expire_deleted_tasks(
+    replica: &mut Replica,
+    cutoff: chrono::DateTime<Utc>,
+    ops: &mut Operations,
+) -> Result<usize, String> {
+    let mut expired = 0;
+    let all = replica
+        .all_task_data()
+        .map_err(|e| format!("Failed to get all tasks: {}", e))?;
+    for (_, mut data) in all {
+        if data.get("status") != Some("deleted") {
+            continue;
+        }
+        let last_change = data
+            .get("modified")
+            .or_else(|| data.get("end"))
+            .and_then(|t| t.parse::<i64>().ok())
+            .and_then(|t| chrono::DateTime::from_timestamp(t, 0));
+        if last_change.is_some_and(|t| t < cutoff) {
+            data.delete(ops);
+            expired += 1;
+        }
+    }
+    Ok(expired)
+}
+
+#[no_mangle]
+pub extern "system" fn Java_com_tasksquire_data_storage_TaskChampionJniImpl_nativeExpireTasks(
+    mut env: JNIEnv,
+    _class: JClass,
+    replica_ptr: jlong,
+    older_than_seconds: jlong,
+) -> jint {
+    catch_panics!(&mut env, "nativeExpireTasks", 0, {
+        if older_than_seconds < 0 {
+            throw(
+                &mut env,
+                EXC_INVALID_ARGUMENT,
+                &format!("Invalid age {}; olderThanSeconds must not be negative", older_than_seconds),
+            );
+            return 0;
+        }
+        let cutoff = chrono::TimeDelta::try_seconds(older_than_seconds)
+            .and_then(|age| Utc::now().checked_sub_signed(age))
+            .unwrap_or(chrono::DateTime::<Utc>::MIN_UTC);
+
+        // On None an exception is pending; 0 is the sentinel.
+        run_with_session(&mut env, replica_ptr, "nativeExpireTasks", |session| {
+            let mut ops = Operations::new();
+            let expired = expire_deleted_tasks(&mut session.replica, cutoff, &mut ops)?;
+            session
+                .commit_operations(ops)
+                .map_err(|e| format!("Failed to commit expire operations: {}", e))?;
+            info!("Expired {} deleted tasks older than {}s", expired, older_than_seconds);
+            Ok(expired as jint)
+        })
+        .unwrap_or(0)
+    })
+}
+
+#[no_mangle]
+pub extern "system" fn Java_com_tasksquire_data_storage_TaskChampionJniImpl_nativeBackup<'local>(
+    mut env: JNIEnv<'local>,
+    _class: JClass,
+    replica_ptr: jlong,
+    dest_path: JString<'local>,
+) -> jlong {
+    catch_panics!(&mut env, "nativeBackup", 0, {
+        let dest_str = match read_jstring(&mut env, &dest_path, "destPath") { Some(s) => s, None => return 0 };
+
+        // Holding the session lock keeps this handle's writes out for the
+        // duration; the snapshot's read transaction covers other handles.
+        // On None an exception is pending; 0 is the sentinel.
+        run_with_session(&mut env, replica_ptr, "nativeBackup", |session| {
+            let bytes = backup::backup(&session.data_dir, Path::new(&dest_str))?;
+            info!("Backed up {} to {} ({} bytes)", session.data_dir.display(), dest_str, bytes);
+            Ok(bytes as jlong)
+        })
+        .unwrap_or(0)
+    })
+}
+
+#[no_mangle]
+pub extern "system" fn Java_com_tasksquire_data_storage_TaskChampionJniImpl_nativeRestore<'local>(
+    mut env: JNIEnv<'local>,
+    _class: JClass,
+    data_dir: JString<'local>,
+    backup_path: JString<'local>,
+) -> jint {
+    catch_panics!(&mut env, "nativeRestore", 0, {
+        let data_dir_str = match read_jstring(&mut env, &data_dir, "dataDir") { Some(s) => s, None => return 0 };
+        let backup_str = match read_jstring(&mut env, &backup_path, "backupPath") { Some(s) => s, None => return 0 };
+
+        match backup::restore(Path::new(&data_dir_str), Path::new(&backup_str)) {
+            Ok(summary) => {
+                info!(
+                    "Restored {} into {} ({} tasks, {} operations)",
+                    backup_str, data_dir_str, summary.tasks, summary.operations
+                );
+                summary.tasks as jint
+            }
+            Err(e) => {
+                error!("Failed to restore {}: {}", backup_str, e);
+                throw(&mut env, EXC_STORAGE, &e);
+                0
+            }
+        }
+    })
+}
+
+#[no_mangle]
+pub extern "system" fn Java_com_tasksquire_data_storage_TaskChampionJniImpl_nativeCheckIntegrity<'local>(
+    mut env: JNIEnv<'local>,
+    _class: JClass,
+    data_dir: JString<'local>,
+    repair: jboolean,
+) -> JString<'local> {
+    catch_panics!(&mut env, "nativeCheckIntegrity", JObject::null().into(), {
+        let data_dir_str = match read_jstring(&mut env, &data_dir, "dataDir") { Some(s) => s, None => return JObject::null().into() };
+        let data_dir_path = PathBuf::from(&data_dir_str);
+
+        let result = if repair != 0 {
+            // Repair writes behind TaskChampion's back, so no handle may
+            // be open on the directory, or open on it, meanwhile.
+            DirClaim::take(&data_dir_path)
+                .map_err(|e| format!("Cannot repair {}: {}", data_dir_str, e))
+                .and_then(|_claim| integrity::repair(&data_dir_path))
+        } else {
+            integrity::check(&data_dir_path)
+        };
+        let report = match result.and_then(|report| {
+            info!(
+                "Integrity {} of {}: {} SQLite errors, {} issues, ok: {}",
+                if repair != 0 { "repair" } else { "check" },
+                data_dir_str,
+                report.sqlite.len(),
+                report.issues.len(),
+                report.ok()
+            );
+            report.to_json()
+        }) {
+            Ok(report) => report,
+            Err(e) => {
+                error!("Integrity check of {} failed: {}", data_dir_str, e);
+                throw(&mut env, EXC_STORAGE, &e);
+                return JObject::null().into();
+            }
+        };
+
+        match env.new_string(&report) {
+            Ok(java_string) => java_string,
+            Err(e) => {
+                error!("Failed to create Java string for integrity report: {:?}", e);
+                throw(&mut env, EXC_STORAGE, &format!("Failed to marshal integrity report: {}", e));
+                JObject::null().into()
+            }
+        }
+    })
+}
+
+#[no_mangle]
+pub extern "system" fn Java_com_tasksquire_data_storage_TaskChampionJniImpl_nativeGetReplicaStats<'local>(
+    mut env: JNIEnv<'local>,
+    _class: JClass,
+    replica_ptr: jlong,
+) -> JString<'local> {
+    catch_panics!(&mut env, "nativeGetReplicaStats", JObject::null().into(), {
+        let stats = run_with_session(&mut env, replica_ptr, "nativeGetReplicaStats", |session| {
+            let data_dir = session.data_dir.clone();
+            let (replica, conn) = session.replica_and_journal()?;
+            let stats = stats::collect(replica, conn, &data_dir)?;
+            stats.to_json()
+        });
+
+        let Some(stats) = stats else {
             // Exception pending; any further env call would abort the process.
-            return std::ptr::null_mut();
+            return JObject::null().into();
         };
 
-        create_string_array(&mut env, task_docs)
+        match env.new_string(&stats) {
+            Ok(java_string) => java_string,
+            Err(e) => {
+                error!("Failed to create Java string for replica stats: {:?}", e);
+                throw(&mut env, EXC_STORAGE, &format!("Failed to marshal replica stats: {}", e));
+                JObject::null().into()
+            }
+        }
     })
 }
 
 #[no_mangle]
-pub extern "system" fn Java_com_tasksquire_data_storage_TaskChampionJniImpl_nativeGetTaskData<'local>(
+pub extern "system" fn Java_com_tasksquire_data_storage_TaskChampionJniImpl_nativeCompact<'local>(
     mut env: JNIEnv<'local>,
     _class: JClass,
     replica_ptr: jlong,
-    uuid: JString,
 ) -> JString<'local> {
-    catch_panics!(&mut env, "nativeGetTaskData", JObject::null().into(), {
-    let uuid_str = match read_jstring(&mut env, &uuid, "uuid") { Some(s) => s, None => return JObject::null().into() };
-    let task_uuid = match parse_uuid(&mut env, &uuid_str) { Some(u) => u, None => return JObject::null().into() };
+    catch_panics!(&mut env, "nativeCompact", JObject::null().into(), {
+        let report = run_with_session(&mut env, replica_ptr, "nativeCompact", |session| {
+            let bytes_before = stats::directory_size(&session.data_dir)?;
+
+            // Trimmed before expiring, whose deletions are themselves
+            // unsynced operations.
+            let (trimmed, trim_skipped) = match journal::trim_synced_operations(session.journal()?)? {
+                Some(trimmed) => (trimmed, None),
+                None => (0, Some("unsynced operations; sync first")),
+            };
 
-    // Inner None signals "task not found" — returned to Java as null.
-    // Outer None signals a thrown exception. Storage errors throw.
-    let json_result: Option<Option<String>> = run_with_replica(&mut env, replica_ptr, "nativeGetTaskData", |replica| {
-        let task = match replica
-            .get_task(task_uuid)
-            .map_err(|e| format!("Failed to get task: {}", e))?
-        {
-            Some(t) => t,
-            None => {
-                info!("Task not found: {}", uuid_str);
-                return Ok(None);
-            }
-        };
+            let cutoff = Utc::now() - chrono::TimeDelta::days(COMPACT_EXPIRE_DAYS);
+            let mut ops = Operations::new();
+            let expired = expire_deleted_tasks(&mut session.replica, cutoff, &mut ops)?;
+            session
+                .commit_operations(ops)
+                .map_err(|e| format!("Failed to commit expire operations: {}", e))?;
+            journal::vacuum(session.journal()?)?;
 
-        let json = task_to_json(&uuid_str, &task)?;
-        info!("Retrieved task data for: {}", uuid_str);
-        Ok(Some(json))
-    });
+            let bytes_after = stats::directory_size(&session.data_dir)?;
+            let reclaimed = bytes_before.saturating_sub(bytes_after);
+            info!(
+                "Compacted {}: expired {} tasks, trimmed {} synced operations, reclaimed {} bytes",
+                session.data_dir.display(),
+                expired,
+                trimmed,
+                reclaimed
+            );
+            let report = serde_json::json!({
+                "tasksExpired": expired,
+                "operationsTrimmed": trimmed,
+                "trimSkipped": trim_skipped,
+                "bytesBefore": bytes_before,
+                "bytesAfter": bytes_after,
+                "bytesReclaimed": reclaimed,
+            });
+            serde_json::to_string(&report).map_err(|e| format!("Failed to serialize compact report to JSON: {}", e))
+        });
 
-    let Some(json_result) = json_result else {
-        // Exception pending; any further env call would abort the process.
-        return JObject::null().into();
-    };
+        let Some(report) = report else {
+            // Exception pending; any further env call would abort the process.
+            return JObject::null().into();
+        };
 
-    match json_result {
-        Some(json) => match env.new_string(&json) {
+        match env.new_string(&report) {
             Ok(java_string) => java_string,
             Err(e) => {
-                error!("Failed to create Java string for task data: {:?}", e);
-                throw(&mut env, EXC_STORAGE, &format!("Failed to marshal task data: {}", e));
+                error!("Failed to create Java string for compact report: {:?}", e);
+                throw(&mut env, EXC_STORAGE, &format!("Failed to marshal compact report: {}", e));
                 JObject::null().into()
             }
-        },
-        None => JObject::null().into(),
-    }
+        }
     })
 }
 
+/// Handles currently registered on `data_dir`. Waits for each session's
+/// lock in turn, so a call in flight on any handle delays the answer.
+fn handles_open_on(data_dir: &Path) -> Vec<jlong> {
+    let data_dir = data_dir.canonicalize().unwrap_or(data_dir.to_path_buf());
+    // As in with_registered_session: no shard guard is held while locking.
+    let sessions: Vec<(jlong, Arc<Mutex<ReplicaSession>>)> =
+        REPLICAS.iter().map(|entry| (*entry.key(), Arc::clone(entry.value()))).collect();
+    sessions
+        .into_iter()
+        .filter(|(_, session)| {
+            let guard = session.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+            guard.data_dir == data_dir
+        })
+        .map(|(handle, _)| handle)
+        .collect()
+}
+
+/// A data directory claimed for an operation that needs it to itself:
+/// while the claim lives, nativeInitialize refuses to open the
+/// directory. Released on drop.
+struct DirClaim(PathBuf);
+
+impl DirClaim {
+    /// Claim `data_dir`, failing if another claim holds it or any handle
+    /// is open on it.
+    fn take(data_dir: &Path) -> Result<DirClaim, String> {
+        let canonical = data_dir.canonicalize().unwrap_or(data_dir.to_path_buf());
+        let mut claimed = CLAIMED_DIRS.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        if !claimed.insert(canonical.clone()) {
+            return Err(format!("{} is already being migrated or repaired", data_dir.display()));
+        }
+        drop(claimed);
+        let claim = DirClaim(canonical);
+        let open = handles_open_on(data_dir);
+        if !open.is_empty() {
+            return Err(format!("Handles {:?} are open on {}", open, data_dir.display()));
+        }
+        Ok(claim)
+    }
+}
+
+impl Drop for DirClaim {
+    fn drop(&mut self) {
+        CLAIMED_DIRS.lock().unwrap_or_else(|poisoned| poisoned.into_inner()).remove(&self.0);
+    }
+}
+
 #[no_mangle]
-pub extern "system" fn Java_com_tasksquire_data_storage_TaskChampionJniImpl_nativeGetUuidForIndex<'local>(
+pub extern "system" fn Java_com_tasksquire_data_storage_TaskChampionJniImpl_nativeMigrateDataDir<'local>(
     mut env: JNIEnv<'local>,
     _class: JClass,
-    replica_ptr: jlong,
-    index: jint,
-) -> JString<'local> {
-    catch_panics!(&mut env, "nativeGetUuidForIndex", JObject::null().into(), {
-    // Inner None signals "no task at this index" — returned to Java as
-    // null. Outer None signals a thrown exception.
-    let uuid_string: Option<Option<String>> = run_with_replica(&mut env, replica_ptr, "nativeGetUuidForIndex", |replica| {
-        let working_set = replica
-            .working_set()
-            .map_err(|e| format!("Failed to get working set: {}", e))?;
-        // TaskWarrior IDs are 1-based, so subtract 1 for 0-based index.
-        let result = (index > 0 && (index as usize) <= working_set.len())
-            .then(|| working_set.by_index((index as usize) - 1))
-            .flatten()
-            .filter(|uuid| !uuid.is_nil())
-            .map(|uuid| uuid.to_string());
-        match result.as_ref() {
-            Some(s) => info!("Found UUID {} for index {}", s, index),
-            None => info!("No task found at index {}", index),
-        }
-        Ok(result)
-    });
-
-    let Some(uuid_string) = uuid_string else {
-        // Exception pending; any further env call would abort the process.
-        return JObject::null().into();
-    };
+    old_dir: JString<'local>,
+    new_dir: JString<'local>,
+) -> jint {
+    catch_panics!(&mut env, "nativeMigrateDataDir", 0, {
+        let old_dir_str = match read_jstring(&mut env, &old_dir, "oldDir") { Some(s) => s, None => return 0 };
+        let new_dir_str = match read_jstring(&mut env, &new_dir, "newDir") { Some(s) => s, None => return 0 };
+        let old_dir_path = PathBuf::from(&old_dir_str);
+
+        // Held until the old database is gone, so no handle opens on it
+        // mid-move.
+        let _claim = match DirClaim::take(&old_dir_path) {
+            Ok(claim) => claim,
+            Err(e) => {
+                throw(&mut env, EXC_STORAGE, &format!("Cannot migrate {}: {}", old_dir_str, e));
+                return 0;
+            }
+        };
 
-    match uuid_string {
-        Some(s) => match env.new_string(s) {
-            Ok(jstr) => jstr,
+        match migrate::migrate(&old_dir_path, Path::new(&new_dir_str)) {
+            Ok(moved) => {
+                info!(
+                    "Migrated {} to {} ({} tasks, {} operations)",
+                    old_dir_str, new_dir_str, moved.tasks, moved.operations
+                );
+                moved.tasks as jint
+            }
             Err(e) => {
-                error!("Failed to create JString for UUID: {:?}", e);
-                throw(&mut env, EXC_STORAGE, &format!("Failed to marshal UUID: {}", e));
-                JObject::null().into()
+                error!("Failed to migrate {} to {}: {}", old_dir_str, new_dir_str, e);
+                throw(&mut env, EXC_STORAGE, &e);
+                0
             }
-        },
-        None => JObject::null().into(),
-    }
+        }
     })
 }
 
@@ -1038,11 +3189,12 @@ fn do_sync(env: &mut JNIEnv, replica_ptr: jlong, method_name: &str, server_confi
         PostSyncRebuild(String),
     }
 
-    let result: Option<Result<(), SyncFailure>> = run_with_replica(
+    let result: Option<Result<(), SyncFailure>> = run_with_session(
         env,
         replica_ptr,
         method_name,
-        |replica| {
+        |session| {
+            let replica = &mut session.replica;
             let mut server = match server_config.into_server() {
                 Ok(s) => s,
                 Err(e) => return Ok(Err(SyncFailure::ServerCreate(format!("{}", e)))),
@@ -1051,6 +3203,11 @@ fn do_sync(env: &mut JNIEnv, replica_ptr: jlong, method_name: &str, server_confi
             let sync_result = panic::catch_unwind(panic::AssertUnwindSafe(|| {
                 replica.sync(&mut server, false)
             }));
+            // Sync may have applied remote changes (even when it then
+            // failed part way), and which tasks they touched is not
+            // reported, so every cache is suspect.
+            session.all_tasks_changed();
+            let replica = &mut session.replica;
 
             match sync_result {
                 Ok(Ok(())) => {
@@ -1253,25 +3410,19 @@ pub extern "system" fn Java_com_tasksquire_data_storage_TaskChampionJniImpl_nati
 #[cfg(test)]
 mod tests {
     use super::*;
+    use std::collections::HashMap;
     use tempfile::TempDir;
 
     fn create_test_replica() -> (Replica, TempDir) {
         let temp_dir = TempDir::new().expect("Failed to create temp directory");
-        let storage_config = StorageConfig::OnDisk {
-            taskdb_dir: temp_dir.path().to_path_buf(),
-            create_if_missing: true,
-            access_mode: taskchampion::storage::AccessMode::ReadWrite,
-        };
-        
-        let storage = storage_config.into_storage().expect("Failed to create storage");
-        let replica = Replica::new(storage);
+        let replica = storage::open_on_disk(temp_dir.path(), true).expect("Failed to create storage");
         (replica, temp_dir)
     }
 
     #[test]
     fn test_replica_lifecycle() {
         let (replica, _temp_dir) = create_test_replica();
-        let handle = register_replica(replica);
+        let handle = register_replica(replica, PathBuf::new());
 
         // 0 is the failure sentinel and must never be allocated.
         assert_ne!(handle, 0);
@@ -1459,8 +3610,7 @@ mod tests {
         let (mut replica, _temp_dir) = create_test_replica();
         
         // Add an undo point
-        let mut ops = Operations::new();
-        ops.push(Operation::UndoPoint);
+        let ops = vec![Operation::UndoPoint];
         replica.commit_operations(ops).expect("Failed to add undo point");
         
         // Create a task
@@ -1486,6 +3636,212 @@ mod tests {
         // This test mainly verifies the undo mechanism works without errors
     }
 
+    #[test]
+    fn test_undo_operations_with_auto_undo_points() {
+        let temp_dir = TempDir::new().expect("Failed to create temp directory");
+        let mut session = open_test_session(temp_dir.path());
+        session.auto_undo_points = true;
+        let description = |session: &mut ReplicaSession, uuid: Uuid| -> Option<String> {
+            session
+                .replica
+                .get_task(uuid)
+                .expect("Failed to get task")
+                .map(|t| t.get_description().to_string())
+        };
+        let undo_once = |session: &mut ReplicaSession| -> bool {
+            let ops = session.replica.get_undo_operations().expect("Failed to get undo operations");
+            session.undo(ops).expect("Undo failed")
+        };
+
+        // Two separate edits, with no explicit undo points.
+        let task_uuid = create_described_task(&mut session, "Test task");
+        let mut ops = Operations::new();
+        let mut task = session.replica.get_task(task_uuid).expect("Failed to get task").expect("Task missing");
+        task.set_description("Edited".to_string(), &mut ops).expect("Failed to set description");
+        drop(task);
+        session.commit_operations(ops).expect("Failed to commit operations");
+
+        // Each undo reverses exactly one edit.
+        assert!(undo_once(&mut session));
+        assert_eq!(description(&mut session, task_uuid).as_deref(), Some("Test task"));
+        assert!(undo_once(&mut session));
+        assert_eq!(description(&mut session, task_uuid), None);
+
+        // An explicit undo point right before an edit is not doubled up.
+        session.commit_operations(vec![Operation::UndoPoint]).expect("Failed to add undo point");
+        create_described_task(&mut session, "Other");
+        let points = undo_point_docs(&mut session).expect("Failed to list undo points");
+        assert_eq!(points.len(), 1);
+
+        // With the mode off, edits share the enclosing undo group again.
+        session.auto_undo_points = false;
+        create_described_task(&mut session, "Third");
+        assert!(undo_once(&mut session));
+        assert!(session.replica.all_tasks().expect("Failed to get all tasks").is_empty());
+    }
+
+    #[test]
+    fn test_done_and_undone_report_dependents() {
+        let temp_dir = TempDir::new().expect("Failed to create temp directory");
+        let mut session = open_test_session(temp_dir.path());
+        let blocker = create_described_task(&mut session, "Buy paint");
+        let other = create_described_task(&mut session, "Buy brushes");
+        let dependent = create_described_task(&mut session, "Paint fence");
+        let mut ops = Operations::new();
+        for uuid in [blocker, other, dependent] {
+            let mut task = session.replica.get_task(uuid).expect("Failed to get task").expect("Task missing");
+            task.set_status(Status::Pending, &mut ops).expect("Failed to set status");
+            if uuid == dependent {
+                task.add_dependency(blocker, &mut ops).expect("Failed to add dependency");
+                task.add_dependency(other, &mut ops).expect("Failed to add dependency");
+            }
+        }
+        session.commit_operations(ops).expect("Failed to commit operations");
+        let value = |session: &mut ReplicaSession, uuid: Uuid, key: &str| -> Option<String> {
+            let task = session.replica.get_task(uuid).expect("Failed to get task").expect("Task missing");
+            task.get_value(key).map(str::to_string)
+        };
+
+        // Completing one of two blockers unblocks nothing; the second does.
+        assert!(set_task_done(&mut session, blocker, true).expect("Done failed").is_empty());
+        assert!(value(&mut session, blocker, "end").is_some());
+        assert_eq!(set_task_done(&mut session, other, true).expect("Done failed"), vec![dependent]);
+
+        // Done again is a no-op; undone blocks the dependent again and
+        // clears end.
+        assert!(set_task_done(&mut session, other, true).expect("Done failed").is_empty());
+        assert_eq!(set_task_done(&mut session, other, false).expect("Undone failed"), vec![dependent]);
+        assert_eq!(value(&mut session, other, "end"), None);
+        assert_eq!(value(&mut session, other, "status").as_deref(), Some("pending"));
+
+        // Deleting keeps an existing end; only pending tasks can be done,
+        // and only completed ones undone.
+        let end = value(&mut session, blocker, "end");
+        let mut ops = Operations::new();
+        let mut task = session.replica.get_task(blocker).expect("Failed to get task").expect("Task missing");
+        set_task_status(&mut task, Status::Deleted, &mut ops).expect("Failed to set status");
+        drop(task);
+        session.commit_operations(ops).expect("Failed to commit operations");
+        assert_eq!(value(&mut session, blocker, "end"), end);
+        assert!(set_task_done(&mut session, blocker, true).is_err());
+        assert!(set_task_done(&mut session, blocker, false).is_err());
+        assert_eq!(value(&mut session, blocker, "status").as_deref(), Some("deleted"));
+    }
+
+    #[test]
+    fn test_duplicate_and_template_tasks() {
+        let temp_dir = TempDir::new().expect("Failed to create temp directory");
+        let mut session = open_test_session(temp_dir.path());
+        let source = create_described_task(&mut session, "Water plants");
+        let mut ops = Operations::new();
+        let mut task = session.replica.get_task(source).expect("Failed to get task").expect("Task missing");
+        task.add_tag(&Tag::try_from("home").unwrap(), &mut ops).expect("Failed to add tag");
+        task.set_value("project", Some("Garden".to_string()), &mut ops).expect("Failed to set project");
+        task.add_annotation(Annotation { entry: Utc::now(), description: "Not the cactus".to_string() }, &mut ops)
+            .expect("Failed to add annotation");
+        task.set_value("entry", Some("1000".to_string()), &mut ops).expect("Failed to set entry");
+        set_task_status(&mut task, Status::Completed, &mut ops).expect("Failed to set status");
+        drop(task);
+        session.commit_operations(ops).expect("Failed to commit operations");
+        let doc = |session: &mut ReplicaSession, uuid: Uuid| -> serde_json::Value {
+            let task = session.replica.get_task(uuid).expect("Failed to get task").expect("Task missing");
+            serde_json::from_str(&task_to_json(&uuid.to_string(), &task, None).unwrap()).unwrap()
+        };
+
+        // The copy keeps the content but starts a fresh life.
+        let overrides = TaskSpec::parse(r#"{"udas": {"priority": "H"}}"#).unwrap();
+        let copy = duplicate_task(&mut session, source, Some(&overrides)).expect("Duplicate failed");
+        let copied = doc(&mut session, copy);
+        assert_eq!(copied["description"], "Water plants");
+        assert_eq!(copied["status"], "pending");
+        assert_ne!(copied["entry"], "1000");
+        assert_eq!(copied["udas"]["project"], "Garden");
+        assert_eq!(copied["udas"]["priority"], "H");
+        assert!(copied["udas"].get("end").is_none());
+        assert!(copied["tags"].as_array().unwrap().contains(&serde_json::json!("home")));
+        assert_eq!(copied["annotations"][0]["description"], "Not the cactus");
+        assert!(duplicate_task(&mut session, Uuid::new_v4(), None).is_err());
+
+        let spec = TaskSpec::parse(r#"{"description": "Call {{who}}", "tags": ["phone"]}"#).unwrap();
+        templates::save(session.journal().unwrap(), "call", &spec).expect("Failed to save template");
+        let listed: serde_json::Value = serde_json::from_str(&template_docs(&mut session).unwrap()[0]).unwrap();
+        assert_eq!(listed["name"], "call");
+        assert_eq!(listed["template"]["description"], "Call {{who}}");
+        let params = HashMap::from([("who".to_string(), "Sam".to_string())]);
+        let spec = templates::get(session.journal().unwrap(), "call").unwrap().unwrap();
+        let created = create_task_from_spec(&mut session, &spec.substitute(&params, Utc::now()).unwrap())
+            .expect("Create from template failed");
+        let created = doc(&mut session, created);
+        assert_eq!(created["description"], "Call Sam");
+        assert_eq!(created["status"], "pending");
+        assert_eq!(created["tags"].as_array().unwrap().iter().filter(|t| *t == "phone").count(), 1);
+        assert!(templates::delete(session.journal().unwrap(), "call").unwrap());
+        assert!(template_docs(&mut session).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_expire_deleted_tasks() {
+        let temp_dir = TempDir::new().expect("Failed to create temp directory");
+        let mut session = open_test_session(temp_dir.path());
+        let now = Utc::now();
+        let mut make = |status: Status, modified: chrono::DateTime<Utc>| -> Uuid {
+            let uuid = create_described_task(&mut session, "Task");
+            let mut ops = Operations::new();
+            let mut task = session.replica.get_task(uuid).expect("Failed to get task").expect("Task missing");
+            task.set_status(status, &mut ops).expect("Failed to set status");
+            task.set_value("modified", Some(modified.timestamp().to_string()), &mut ops)
+                .expect("Failed to set modified");
+            drop(task);
+            session.commit_operations(ops).expect("Failed to commit operations");
+            uuid
+        };
+        let old = make(Status::Deleted, now - chrono::Duration::days(40));
+        let recent = make(Status::Deleted, now - chrono::Duration::days(5));
+        let completed = make(Status::Completed, now - chrono::Duration::days(40));
+
+        let mut ops = Operations::new();
+        let expired = expire_deleted_tasks(&mut session.replica, now - chrono::Duration::days(30), &mut ops)
+            .expect("Expiry failed");
+        session.commit_operations(ops).expect("Failed to commit operations");
+        assert_eq!(expired, 1);
+        let exists = |session: &mut ReplicaSession, uuid: Uuid| {
+            session.replica.get_task(uuid).expect("Failed to get task").is_some()
+        };
+        assert!(!exists(&mut session, old));
+        assert!(exists(&mut session, recent));
+        assert!(exists(&mut session, completed));
+    }
+
+    #[test]
+    fn test_trim_synced_operations_keeps_tasks() {
+        let temp_dir = TempDir::new().expect("Failed to create temp directory");
+        let mut session = open_test_session(temp_dir.path());
+        let synced = create_described_task(&mut session, "Synced");
+        // What a successful sync leaves behind.
+        session
+            .journal()
+            .unwrap()
+            .execute("UPDATE operations SET synced = true", [])
+            .expect("Failed to mark operations synced");
+        let unsynced = create_described_task(&mut session, "Local");
+
+        // Not while anything is unsynced.
+        let conn = session.journal().unwrap();
+        let total = storage::operation_count(conn).unwrap();
+        assert_eq!(journal::trim_synced_operations(conn).unwrap(), None);
+        assert_eq!(storage::operation_count(conn).unwrap(), total);
+
+        conn.execute("UPDATE operations SET synced = true", [])
+            .expect("Failed to mark operations synced");
+        assert_eq!(journal::trim_synced_operations(conn).unwrap(), Some(total));
+        journal::vacuum(conn).expect("Vacuum failed");
+        assert_eq!(storage::operation_count(conn).unwrap(), 0);
+
+        for uuid in [synced, unsynced] {
+            assert!(session.replica.get_task(uuid).expect("Failed to get task").is_some());
+        }
+    }
+
     #[test]
     fn test_data_export() {
         let (mut replica, _temp_dir) = create_test_replica();
@@ -1519,7 +3875,7 @@ mod tests {
         use std::thread;
 
         let (replica, _temp_dir) = create_test_replica();
-        let handle = register_replica(replica);
+        let handle = register_replica(replica, PathBuf::new());
 
         let num_threads = 4;
         let tasks_per_thread = 10;
@@ -1567,7 +3923,7 @@ mod tests {
         use std::thread;
 
         let (replica, _temp_dir) = create_test_replica();
-        let handle = register_replica(replica);
+        let handle = register_replica(replica, PathBuf::new());
 
         // Test that a poisoned mutex is recovered from: panic while
         // holding the per-replica lock.
@@ -1604,7 +3960,7 @@ mod tests {
     #[test]
     fn test_replica_cleanup() {
         let (replica, _temp_dir) = create_test_replica();
-        let handle = register_replica(replica);
+        let handle = register_replica(replica, PathBuf::new());
 
         // Verify the handle is registered
         assert!(REPLICAS.contains_key(&handle));
@@ -1628,7 +3984,7 @@ mod tests {
         use std::thread;
 
         let (replica, _temp_dir) = create_test_replica();
-        let handle = register_replica(replica);
+        let handle = register_replica(replica, PathBuf::new());
 
         let stop = Arc::new(AtomicBool::new(false));
         let worker_stop = Arc::clone(&stop);
@@ -1676,6 +4032,22 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_claimed_dir_cannot_be_opened() {
+        let temp_dir = TempDir::new().expect("Failed to create temp directory");
+        let dir = temp_dir.path();
+
+        let claim = DirClaim::take(dir).expect("Failed to claim directory");
+        assert!(DirClaim::take(dir).is_err());
+        assert!(open_replica(dir).is_err());
+        drop(claim);
+
+        let handle = open_replica(dir).expect("Failed to open replica");
+        assert!(DirClaim::take(dir).is_err());
+        assert!(REPLICAS.remove(&handle).is_some());
+        assert!(DirClaim::take(dir).is_ok());
+    }
+
     #[test]
     fn test_stale_handle_after_reinitialize() {
         // Destroying handle A and then initializing a new replica B must
@@ -1683,11 +4055,11 @@ mod tests {
         // and are never reused, so the ABA hazard of address-based
         // handles cannot occur.
         let (replica_a, _temp_dir_a) = create_test_replica();
-        let handle_a = register_replica(replica_a);
+        let handle_a = register_replica(replica_a, PathBuf::new());
         assert!(REPLICAS.remove(&handle_a).is_some());
 
         let (replica_b, _temp_dir_b) = create_test_replica();
-        let handle_b = register_replica(replica_b);
+        let handle_b = register_replica(replica_b, PathBuf::new());
 
         assert_ne!(handle_a, handle_b, "Handles must never be reused");
         assert!(
@@ -1740,7 +4112,7 @@ mod tests {
         assert_eq!(all_tasks.len(), 2);
 
         let json_a: serde_json::Value = serde_json::from_str(
-            &task_to_json(&uuid_a.to_string(), all_tasks.get(&uuid_a).expect("Task A missing"))
+            &task_to_json(&uuid_a.to_string(), all_tasks.get(&uuid_a).expect("Task A missing"), None)
                 .expect("Failed to build JSON for task A"),
         ).expect("Task A JSON did not parse");
 
@@ -1767,7 +4139,7 @@ mod tests {
         assert_eq!(json_a["udas"], serde_json::json!({"project": "alpha"}));
 
         let json_b: serde_json::Value = serde_json::from_str(
-            &task_to_json(&uuid_b.to_string(), all_tasks.get(&uuid_b).expect("Task B missing"))
+            &task_to_json(&uuid_b.to_string(), all_tasks.get(&uuid_b).expect("Task B missing"), None)
                 .expect("Failed to build JSON for task B"),
         ).expect("Task B JSON did not parse");
 
@@ -1809,7 +4181,7 @@ mod tests {
         let single_task = replica.get_task(task_uuid)
             .expect("Failed to get task")
             .expect("Task not found");
-        let single_json = task_to_json(&task_uuid.to_string(), &single_task)
+        let single_json = task_to_json(&task_uuid.to_string(), &single_task, None)
             .expect("Failed to build JSON via single path");
 
         // Bulk path (as used by nativeGetAllTasks).
@@ -1817,6 +4189,7 @@ mod tests {
         let bulk_json = task_to_json(
             &task_uuid.to_string(),
             all_tasks.get(&task_uuid).expect("Task missing from all_tasks"),
+            None,
         ).expect("Failed to build JSON via bulk path");
 
         // Compare as parsed values so map key ordering cannot matter.
@@ -1827,6 +4200,353 @@ mod tests {
         assert_eq!(single_value, bulk_value);
     }
 
+    #[test]
+    fn test_query_task_docs_filters_deleted_tasks() {
+        let (mut replica, _temp_dir) = create_test_replica();
+
+        let mut ops = Operations::new();
+        for (description, status) in [("keep me", Status::Pending), ("gone", Status::Deleted)] {
+            let mut task = replica.create_task(Uuid::new_v4(), &mut ops).expect("Failed to create task");
+            task.set_description(description.to_string(), &mut ops).expect("Failed to set description");
+            task.set_status(status, &mut ops).expect("Failed to set status");
+        }
+        replica.commit_operations(ops).expect("Failed to commit operations");
+
+        let everything = query_task_docs(&mut replica, &Filter::All).expect("Query failed");
+        assert_eq!(everything.len(), 2);
+
+        let filter = filter::parse("status:pending or status:completed").expect("Filter did not parse");
+        let docs = query_task_docs(&mut replica, &filter).expect("Query failed");
+        assert_eq!(docs.len(), 1);
+        let doc: serde_json::Value = serde_json::from_str(&docs[0]).expect("JSON did not parse");
+        assert_eq!(doc["description"], "keep me");
+    }
+
+    #[test]
+    fn test_query_task_page_is_sorted_and_bounded() {
+        let (mut replica, _temp_dir) = create_test_replica();
+
+        let mut ops = Operations::new();
+        for i in 0..25 {
+            let mut task = replica.create_task(Uuid::new_v4(), &mut ops).expect("Failed to create task");
+            task.set_description(format!("task {:02}", i), &mut ops).expect("Failed to set description");
+            task.set_value("due", Some((1_700_000_000 + i * 60).to_string()), &mut ops)
+                .expect("Failed to set due");
+        }
+        replica.commit_operations(ops).expect("Failed to commit operations");
+
+        let keys = sort::parse("due-").expect("Sort did not parse");
+        let descriptions = |docs: Vec<String>| -> Vec<String> {
+            docs.iter()
+                .map(|d| {
+                    let v: serde_json::Value = serde_json::from_str(d).expect("JSON did not parse");
+                    v["description"].as_str().unwrap().to_string()
+                })
+                .collect()
+        };
+
+        let first = query_task_page(&mut replica, &Filter::All, &keys, 0, 10).expect("Query failed");
+        assert_eq!(descriptions(first)[..2], ["task 24".to_string(), "task 23".to_string()]);
+
+        let last = query_task_page(&mut replica, &Filter::All, &keys, 20, 10).expect("Query failed");
+        assert_eq!(
+            descriptions(last),
+            vec!["task 04", "task 03", "task 02", "task 01", "task 00"]
+        );
+
+        let past_end = query_task_page(&mut replica, &Filter::All, &keys, 30, 10).expect("Query failed");
+        assert!(past_end.is_empty());
+    }
+
+    #[test]
+    fn test_working_set_and_pending_docs() {
+        let (mut replica, _temp_dir) = create_test_replica();
+
+        let mut ops = Operations::new();
+        let mut uuids = Vec::new();
+        for description in ["first", "second", "third"] {
+            let uuid = Uuid::new_v4();
+            let mut task = replica.create_task(uuid, &mut ops).expect("Failed to create task");
+            task.set_description(description.to_string(), &mut ops).expect("Failed to set description");
+            task.set_status(Status::Pending, &mut ops).expect("Failed to set status");
+            uuids.push(uuid);
+        }
+        replica.commit_operations(ops).expect("Failed to commit operations");
+
+        // Complete the second task without rebuilding: its index stays
+        // occupied in the working set but it is no longer pending.
+        let mut ops = Operations::new();
+        let mut task = replica.get_task(uuids[1]).expect("Failed to get task").expect("Task not found");
+        task.set_status(Status::Completed, &mut ops).expect("Failed to set status");
+        replica.commit_operations(ops).expect("Failed to commit operations");
+
+        let entries: Vec<serde_json::Value> = working_set_docs(&mut replica)
+            .expect("Failed to build working set docs")
+            .iter()
+            .map(|d| serde_json::from_str(d).expect("JSON did not parse"))
+            .collect();
+        assert_eq!(entries.len(), 3);
+        assert_eq!(entries[0], serde_json::json!({"id": 1, "uuid": uuids[0].to_string()}));
+
+        let pending: Vec<serde_json::Value> = pending_task_docs(&mut replica)
+            .expect("Failed to build pending docs")
+            .iter()
+            .map(|d| serde_json::from_str(d).expect("JSON did not parse"))
+            .collect();
+        assert_eq!(pending.len(), 2);
+        assert_eq!(pending[0]["description"], "first");
+        assert_eq!(pending[0]["id"], 1);
+        assert_eq!(pending[1]["description"], "third");
+        assert_eq!(pending[1]["id"], 3);
+
+        // The ids reported agree with the index lookup used by
+        // nativeGetUuidForIndex.
+        let working_set = replica.working_set().expect("Failed to get working set");
+        assert_eq!(working_set.by_index(3), Some(uuids[2]));
+    }
+
+    #[test]
+    fn test_uuid_for_index_is_one_based() {
+        let (mut replica, _temp_dir) = create_test_replica();
+
+        let mut ops = Operations::new();
+        let mut uuids = Vec::new();
+        for _ in 0..3 {
+            let uuid = Uuid::new_v4();
+            let mut task = replica.create_task(uuid, &mut ops).expect("Failed to create task");
+            task.set_status(Status::Pending, &mut ops).expect("Failed to set status");
+            uuids.push(uuid);
+        }
+        replica.commit_operations(ops).expect("Failed to commit operations");
+
+        // Index 1 is the first task, as TaskWarrior numbers it; this was
+        // once off by one.
+        assert_eq!(uuid_for_index(&mut replica, 1).unwrap(), Some(uuids[0]));
+        assert_eq!(uuid_for_index(&mut replica, 3).unwrap(), Some(uuids[2]));
+        assert_eq!(uuid_for_index(&mut replica, 4).unwrap(), None);
+        assert_eq!(uuid_for_index(&mut replica, 0).unwrap(), None);
+        assert_eq!(uuid_for_index(&mut replica, -1).unwrap(), None);
+    }
+
+    #[test]
+    fn test_task_json_carries_working_set_id() {
+        let (mut replica, _temp_dir) = create_test_replica();
+
+        let pending_uuid = Uuid::new_v4();
+        let completed_uuid = Uuid::new_v4();
+        let mut ops = Operations::new();
+        let mut task = replica.create_task(pending_uuid, &mut ops).expect("Failed to create task");
+        task.set_status(Status::Pending, &mut ops).expect("Failed to set status");
+        let mut task = replica.create_task(completed_uuid, &mut ops).expect("Failed to create task");
+        task.set_status(Status::Completed, &mut ops).expect("Failed to set status");
+        replica.commit_operations(ops).expect("Failed to commit operations");
+
+        // Reverse lookup agrees with the forward lookup.
+        let working_set = replica.working_set().expect("Failed to get working set");
+        let index = working_set.by_uuid(pending_uuid).expect("Pending task not in working set");
+        assert_eq!(working_set.by_index(index), Some(pending_uuid));
+        assert_eq!(working_set.by_uuid(completed_uuid), None);
+
+        let docs: std::collections::HashMap<String, serde_json::Value> =
+            query_task_docs(&mut replica, &Filter::All)
+                .expect("Query failed")
+                .iter()
+                .map(|d| {
+                    let v: serde_json::Value = serde_json::from_str(d).expect("JSON did not parse");
+                    (v["uuid"].as_str().unwrap().to_string(), v)
+                })
+                .collect();
+        assert_eq!(docs[&pending_uuid.to_string()]["id"], index);
+        assert!(docs[&completed_uuid.to_string()].get("id").is_none());
+    }
+
+    #[test]
+    fn test_task_docs_by_uuid_preserves_order_and_gaps() {
+        let (mut replica, _temp_dir) = create_test_replica();
+
+        let uuid_a = Uuid::new_v4();
+        let uuid_b = Uuid::new_v4();
+        let missing = Uuid::new_v4();
+        let mut ops = Operations::new();
+        for (uuid, description) in [(uuid_a, "A"), (uuid_b, "B")] {
+            let mut task = replica.create_task(uuid, &mut ops).expect("Failed to create task");
+            task.set_description(description.to_string(), &mut ops).expect("Failed to set description");
+        }
+        replica.commit_operations(ops).expect("Failed to commit operations");
+
+        let docs = task_docs_by_uuid(&mut replica, &[uuid_b, missing, uuid_a])
+            .expect("Failed to build docs");
+        assert_eq!(docs.len(), 3);
+        let description = |doc: &Option<String>| -> String {
+            let v: serde_json::Value = serde_json::from_str(doc.as_ref().expect("doc missing"))
+                .expect("JSON did not parse");
+            v["description"].as_str().unwrap().to_string()
+        };
+        assert_eq!(description(&docs[0]), "B");
+        assert!(docs[1].is_none());
+        assert_eq!(description(&docs[2]), "A");
+
+        assert!(task_docs_by_uuid(&mut replica, &[]).expect("Failed to build docs").is_empty());
+    }
+
+    /// Open a session over an on-disk replica in `dir`, as nativeInitialize
+    /// does.
+    fn open_test_session(dir: &Path) -> ReplicaSession {
+        let replica = storage::open_on_disk(dir, true).expect("Failed to create storage");
+        ReplicaSession::new(replica, dir.to_path_buf())
+    }
+
+    /// Create a task with a description through the session, as
+    /// nativeCreateTask plus nativeTaskSetDescription would.
+    fn create_described_task(session: &mut ReplicaSession, description: &str) -> Uuid {
+        let uuid = Uuid::new_v4();
+        let mut ops = Operations::new();
+        let mut task = session.replica.create_task(uuid, &mut ops).expect("Failed to create task");
+        task.set_description(description.to_string(), &mut ops).expect("Failed to set description");
+        drop(task);
+        session.commit_operations(ops).expect("Failed to commit operations");
+        uuid
+    }
+
+    #[test]
+    fn test_search_index_tracks_commits_across_sessions() {
+        let temp_dir = TempDir::new().expect("Failed to create temp directory");
+        let mut ui = open_test_session(temp_dir.path());
+        let mut other = open_test_session(temp_dir.path());
+        let hits = |session: &mut ReplicaSession, query: &str| -> Vec<String> {
+            search_task_docs(session, query, &Filter::All, 10)
+                .expect("Search failed")
+                .iter()
+                .map(|d| {
+                    let v: serde_json::Value = serde_json::from_str(d).expect("JSON did not parse");
+                    v["description"].as_str().unwrap().to_string()
+                })
+                .collect()
+        };
+
+        let first = create_described_task(&mut ui, "Renew passport");
+        assert_eq!(hits(&mut ui, "pass"), vec!["Renew passport"]);
+
+        // A commit through this session patches the built index in place.
+        let mut ops = Operations::new();
+        let mut task = ui.replica.get_task(first).unwrap().unwrap();
+        task.set_description("Renew driving licence".to_string(), &mut ops).unwrap();
+        drop(task);
+        ui.commit_operations(ops).unwrap();
+        assert!(ui.search_index.is_some());
+        assert!(hits(&mut ui, "pass").is_empty());
+        assert_eq!(hits(&mut ui, "LICENCE"), vec!["Renew driving licence"]);
+
+        // A commit through another handle on the same directory makes the
+        // index stale; the next search rebuilds it.
+        create_described_task(&mut other, "Passport photos");
+        assert_eq!(hits(&mut ui, "passport"), vec!["Passport photos"]);
+
+        // The limit caps the result count; the filter narrows it.
+        create_described_task(&mut ui, "Renew insurance");
+        assert_eq!(search_task_docs(&mut ui, "renew", &Filter::All, 1).unwrap().len(), 1);
+        let filter = filter::parse("description.has:insurance").unwrap();
+        assert_eq!(search_task_docs(&mut ui, "renew", &filter, 10).unwrap().len(), 1);
+    }
+
+    #[test]
+    fn test_labelled_undo_points() {
+        let temp_dir = TempDir::new().expect("Failed to create temp directory");
+        let mut session = open_test_session(temp_dir.path());
+        let descriptions = |session: &mut ReplicaSession| -> Vec<String> {
+            let mut d: Vec<String> = session
+                .replica
+                .all_tasks()
+                .expect("Failed to get all tasks")
+                .values()
+                .map(|t| t.get_description().to_string())
+                .collect();
+            d.sort();
+            d
+        };
+
+        add_labelled_undo_point(&mut session, "first").expect("Failed to add undo point");
+        create_described_task(&mut session, "A");
+        session.commit_operations(vec![Operation::UndoPoint]).expect("Failed to add undo point");
+        create_described_task(&mut session, "B");
+        add_labelled_undo_point(&mut session, "third").expect("Failed to add undo point");
+        create_described_task(&mut session, "C");
+
+        let points: Vec<serde_json::Value> = undo_point_docs(&mut session)
+            .expect("Failed to list undo points")
+            .iter()
+            .map(|d| serde_json::from_str(d).expect("JSON did not parse"))
+            .collect();
+        assert_eq!(points.len(), 3);
+        assert_eq!(points[0]["label"], "third");
+        assert!(points[1]["label"].is_null());
+        assert!(points[1]["timestamp"].is_string());
+        assert_eq!(points[2]["label"], "first");
+        assert!(points[2]["changes"].as_u64().unwrap() > points[0]["changes"].as_u64().unwrap());
+
+        // Unknown labels change nothing.
+        assert!(!undo_to_label(&mut session, "missing").expect("Undo failed"));
+        assert_eq!(descriptions(&mut session), vec!["A", "B", "C"]);
+
+        // Undoing to a point reverses everything after it in one step,
+        // including the unlabelled point in between, and drops the labels
+        // of the points undone.
+        assert!(undo_to_label(&mut session, "first").expect("Undo failed"));
+        assert!(descriptions(&mut session).is_empty());
+        assert!(undo_point_docs(&mut session).expect("Failed to list undo points").is_empty());
+        assert!(!undo_to_label(&mut session, "third").expect("Undo failed"));
+    }
+
+    #[test]
+    fn test_redo_replays_undone_changes() {
+        let temp_dir = TempDir::new().expect("Failed to create temp directory");
+        let mut session = open_test_session(temp_dir.path());
+        let undo_once = |session: &mut ReplicaSession| -> bool {
+            let ops = session.replica.get_undo_operations().expect("Failed to get undo operations");
+            session.undo(ops).expect("Undo failed")
+        };
+        let count = |session: &mut ReplicaSession| session.replica.all_tasks().expect("Failed to get all tasks").len();
+
+        assert!(!session.redo().expect("Redo failed"));
+
+        add_labelled_undo_point(&mut session, "add A").expect("Failed to add undo point");
+        let a = create_described_task(&mut session, "A");
+        session.commit_operations(vec![Operation::UndoPoint]).expect("Failed to add undo point");
+        create_described_task(&mut session, "B");
+
+        // Undo twice, then redo twice: both tasks come back in order, and
+        // the label comes back with its point.
+        assert!(undo_once(&mut session));
+        assert!(undo_to_label(&mut session, "add A").expect("Undo failed"));
+        assert_eq!(count(&mut session), 0);
+        assert!(session.redo().expect("Redo failed"));
+        assert_eq!(count(&mut session), 1);
+        assert!(session.replica.get_task(a).expect("Failed to get task").is_some());
+        assert!(session.redo().expect("Redo failed"));
+        assert_eq!(count(&mut session), 2);
+        assert!(!session.redo().expect("Redo failed"));
+        let points = undo_point_docs(&mut session).expect("Failed to list undo points");
+        assert!(points.last().expect("No undo points").contains("\"add A\""));
+
+        // Adding undo points changes no task, so redo survives them.
+        assert!(undo_once(&mut session));
+        session.commit_operations(vec![Operation::UndoPoint]).expect("Failed to add undo point");
+        add_labelled_undo_point(&mut session, "before redo").expect("Failed to add undo point");
+        assert!(session.redo().expect("Redo failed"));
+        assert_eq!(count(&mut session), 2);
+
+        // A new change after an undo discards the redo stack.
+        assert!(undo_once(&mut session));
+        create_described_task(&mut session, "C");
+        assert!(!session.redo().expect("Redo failed"));
+
+        // So does a change through another handle on the same directory.
+        assert!(undo_once(&mut session));
+        let mut other = open_test_session(temp_dir.path());
+        create_described_task(&mut other, "D");
+        assert!(!session.redo().expect("Redo failed"));
+    }
+
     #[test]
     fn test_all_tasks_empty_replica_yields_empty_docs() {
         let (mut replica, _temp_dir) = create_test_replica();
@@ -1838,7 +4558,7 @@ mod tests {
         // produce an empty vec of JSON documents (empty Java array).
         let docs: Vec<String> = all_tasks
             .iter()
-            .map(|(uuid, task)| task_to_json(&uuid.to_string(), task))
+            .map(|(uuid, task)| task_to_json(&uuid.to_string(), task, None))
             .collect::<Result<Vec<_>, _>>()
             .expect("Failed to build docs for empty replica");
         assert!(docs.is_empty());
@@ -0,0 +1,244 @@
+//! Binding-side metadata kept alongside TaskChampion's operation journal.
+//!
+//! TaskChampion's journal is the `operations` table of its database, one
+//! row per operation with an `AUTOINCREMENT` id, so ids are never reused;
+//! an undo point there is an anonymous `UndoPoint` row. The `Replica` API
+//! only exposes the latest undo group, so the journal is read through
+//! [`crate::storage`]. Undo-point labels, keyed by the undo point's
+//! operation id, and the template registry live in a database of the
+//! binding's own, [`META_DB_FILE`] in the same data directory, attached to
+//! the journal connection as `jni`. TaskChampion's database never holds
+//! a table of the binding's.
+//!
+//! Only unsynced operations can be undone, so everything here considers
+//! those alone. A label whose operation has been undone, or synced, no
+//! longer names an undo point; [`prune_labels`] removes such rows.
+
+use crate::storage;
+use chrono::{DateTime, Utc};
+use rusqlite::{params, Connection, OpenFlags, OptionalExtension};
+use std::collections::HashSet;
+use std::path::Path;
+use taskchampion::Operation;
+
+/// Name of the binding's own database file within a data directory.
+pub const META_DB_FILE: &str = "taskchampion-jni.sqlite3";
+
+/// Open a connection to the database in `data_dir`, which must already
+/// have been created by TaskChampion, with the binding's own database
+/// attached as `jni` and its tables created.
+pub fn open(data_dir: &Path) -> Result<Connection, String> {
+    let conn = storage::open(data_dir)?;
+    let meta = data_dir.join(META_DB_FILE);
+    let meta = meta
+        .to_str()
+        .ok_or_else(|| format!("{} is not valid UTF-8", meta.display()))?;
+    conn.execute("ATTACH DATABASE ?1 AS jni", [meta])
+        .map_err(|e| format!("Failed to open binding database: {}", e))?;
+    conn.execute_batch(
+        "CREATE TABLE IF NOT EXISTS jni.undo_point_labels (
+            operation_id INTEGER PRIMARY KEY,
+            label TEXT NOT NULL,
+            created_at INTEGER NOT NULL
+        );
+        CREATE TABLE IF NOT EXISTS jni.task_templates (
+            name TEXT PRIMARY KEY,
+            spec TEXT NOT NULL
+        );",
+    )
+    .map_err(|e| format!("Failed to create binding tables: {}", e))?;
+    Ok(conn)
+}
+
+/// Copy the binding's database in `old_dir`, if there is one, into
+/// `new_dir` as a consistent snapshot. Returns whether there was one.
+pub fn copy_metadata(old_dir: &Path, new_dir: &Path) -> Result<bool, String> {
+    let old = old_dir.join(META_DB_FILE);
+    if !old.is_file() {
+        return Ok(false);
+    }
+    let new = new_dir.join(META_DB_FILE);
+    let new = new
+        .to_str()
+        .ok_or_else(|| format!("{} is not valid UTF-8", new.display()))?;
+    let conn = Connection::open_with_flags(&old, OpenFlags::SQLITE_OPEN_READ_ONLY | OpenFlags::SQLITE_OPEN_NO_MUTEX)
+        .map_err(|e| format!("Failed to open {}: {}", old.display(), e))?;
+    conn.execute("VACUUM INTO ?1", [new])
+        .map_err(|e| format!("Failed to copy binding database: {}", e))?;
+    Ok(true)
+}
+
+/// Label the first undo point recorded after operation `after_id`.
+/// Returns false if there is none.
+pub fn label_undo_point_after(
+    conn: &Connection,
+    after_id: i64,
+    label: &str,
+    created_at: DateTime<Utc>,
+) -> Result<bool, String> {
+    let Some(id) = storage::first_undo_point_after(conn, after_id)? else { return Ok(false) };
+    conn.execute(
+        "INSERT OR REPLACE INTO jni.undo_point_labels (operation_id, label, created_at)
+         VALUES (?1, ?2, ?3)",
+        params![id, label, created_at.timestamp()],
+    )
+    .map_err(|e| format!("Failed to label undo point: {}", e))?;
+    Ok(true)
+}
+
+fn label_of(conn: &Connection, operation_id: i64) -> Result<Option<(String, i64)>, String> {
+    conn.query_row(
+        "SELECT label, created_at FROM jni.undo_point_labels WHERE operation_id = ?1",
+        [operation_id],
+        |row| Ok((row.get(0)?, row.get(1)?)),
+    )
+    .optional()
+    .map_err(|e| format!("Failed to read undo point labels: {}", e))
+}
+
+/// One undo point that can still be undone to.
+#[derive(Debug, Clone, PartialEq)]
+pub struct UndoPointInfo {
+    pub operation_id: i64,
+    pub label: Option<String>,
+    /// When the point was labelled; for an unlabelled point, the time of
+    /// the first change after it, if any change is timestamped.
+    pub timestamp: Option<DateTime<Utc>>,
+    /// Number of changes undoing to this point would reverse.
+    pub changes: usize,
+}
+
+/// The undo points among the unsynced operations, newest first.
+pub fn undo_points(conn: &Connection) -> Result<Vec<UndoPointInfo>, String> {
+    let ops = storage::unsynced_operations(conn)?;
+    let mut points = Vec::new();
+    for (i, (id, op)) in ops.iter().enumerate() {
+        if *op != Operation::UndoPoint {
+            continue;
+        }
+        let later = &ops[i + 1..];
+        let changes = later.iter().filter(|(_, op)| *op != Operation::UndoPoint).count();
+        let (label, timestamp) = match label_of(conn, *id)? {
+            Some((label, created_at)) => (Some(label), DateTime::from_timestamp(created_at, 0)),
+            None => {
+                let first_change = later.iter().find_map(|(_, op)| match op {
+                    Operation::Update { timestamp, .. } => Some(*timestamp),
+                    _ => None,
+                });
+                (None, first_change)
+            }
+        };
+        points.push(UndoPointInfo { operation_id: *id, label, timestamp, changes });
+    }
+    points.reverse();
+    Ok(points)
+}
+
+/// The operations from the newest undo point labelled `label` through the
+/// end of the journal, oldest first, in the form
+/// `Replica::commit_reversed_operations` expects. `None` if no unsynced
+/// undo point has that label.
+pub fn operations_since_label(conn: &Connection, label: &str) -> Result<Option<Vec<Operation>>, String> {
+    let ops = storage::unsynced_operations(conn)?;
+    for (i, (id, op)) in ops.iter().enumerate().rev() {
+        if *op == Operation::UndoPoint
+            && label_of(conn, *id)?.is_some_and(|(l, _)| l == label)
+        {
+            return Ok(Some(ops[i..].iter().map(|(_, op)| op.clone()).collect()));
+        }
+    }
+    Ok(None)
+}
+
+/// A label recorded against one undo point among a run of operations.
+#[derive(Debug, Clone, PartialEq)]
+pub struct PositionedLabel {
+    /// Index of the undo point within the run.
+    pub position: usize,
+    pub label: String,
+    pub created_at: i64,
+}
+
+/// The labels of undo points among the newest `count` unsynced
+/// operations, positioned relative to the first of them.
+pub fn tail_labels(conn: &Connection, count: usize) -> Result<Vec<PositionedLabel>, String> {
+    let ops = storage::unsynced_operations(conn)?;
+    let tail = &ops[ops.len().saturating_sub(count)..];
+    let mut labels = Vec::new();
+    for (position, (id, op)) in tail.iter().enumerate() {
+        if *op != Operation::UndoPoint {
+            continue;
+        }
+        if let Some((label, created_at)) = label_of(conn, *id)? {
+            labels.push(PositionedLabel { position, label, created_at });
+        }
+    }
+    Ok(labels)
+}
+
+/// Re-attach `labels` to a run of operations recorded after operation
+/// `after_id`, e.g. operations replayed by redo. Positions that do not
+/// hold an undo point are skipped.
+pub fn restore_labels(conn: &Connection, after_id: i64, labels: &[PositionedLabel]) -> Result<(), String> {
+    if labels.is_empty() {
+        return Ok(());
+    }
+    let run: Vec<(i64, Operation)> = storage::unsynced_operations(conn)?
+        .into_iter()
+        .filter(|(id, _)| *id > after_id)
+        .collect();
+    for label in labels {
+        if let Some((id, Operation::UndoPoint)) = run.get(label.position) {
+            conn.execute(
+                "INSERT OR REPLACE INTO jni.undo_point_labels (operation_id, label, created_at)
+                 VALUES (?1, ?2, ?3)",
+                params![id, label.label, label.created_at],
+            )
+            .map_err(|e| format!("Failed to label undo point: {}", e))?;
+        }
+    }
+    Ok(())
+}
+
+/// Remove labels whose undo point has been undone or synced.
+pub fn prune_labels(conn: &Connection) -> Result<(), String> {
+    let live: HashSet<i64> = storage::unsynced_operation_ids(conn)?.into_iter().collect();
+    let mut stmt = conn
+        .prepare("SELECT operation_id FROM jni.undo_point_labels")
+        .map_err(|e| format!("Failed to read undo point labels: {}", e))?;
+    let labelled = stmt
+        .query_map([], |row| row.get::<_, i64>(0))
+        .map_err(|e| format!("Failed to read undo point labels: {}", e))?
+        .collect::<Result<Vec<_>, _>>()
+        .map_err(|e| format!("Failed to read undo point labels: {}", e))?;
+    for id in labelled.into_iter().filter(|id| !live.contains(id)) {
+        conn.execute("DELETE FROM jni.undo_point_labels WHERE operation_id = ?1", [id])
+            .map_err(|e| format!("Failed to prune undo point labels: {}", e))?;
+    }
+    Ok(())
+}
+
+/// Delete every synced operation, returning how many there were. The
+/// server holds them, and undo only reaches unsynced operations, so
+/// only the per-task change history loses them.
+///
+/// Returns `None`, deleting nothing, while any operation is unsynced:
+/// the journal is only trimmed when it is fully acknowledged, as it is
+/// straight after a successful sync.
+pub fn trim_synced_operations(conn: &Connection) -> Result<Option<usize>, String> {
+    if !storage::unsynced_operation_ids(conn)?.is_empty() {
+        return Ok(None);
+    }
+    let trimmed = storage::delete_synced_operations(conn)?;
+    prune_labels(conn)?;
+    Ok(Some(trimmed))
+}
+
+/// Rebuild TaskChampion's database file without its free pages, then fold the WAL
+/// back in and truncate it, so the space is returned to the filesystem.
+pub fn vacuum(conn: &Connection) -> Result<(), String> {
+    conn.execute_batch("VACUUM")
+        .map_err(|e| format!("Failed to vacuum database: {}", e))?;
+    conn.query_row("PRAGMA wal_checkpoint(TRUNCATE)", [], |_| Ok(()))
+        .map_err(|e| format!("Failed to checkpoint database: {}", e))
+}
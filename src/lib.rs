@@ -1,3 +1,19 @@
-
 pub mod logging;
+pub mod dates;
+pub mod filter;
+pub mod sort;
+pub mod search;
+pub mod recurrence;
+pub mod history;
+pub mod storage;
+pub mod journal;
+pub mod backup;
+pub mod integrity;
+pub mod stats;
+pub mod migrate;
+pub mod templates;
+pub mod taskwarrior;
+pub mod ical;
+pub mod tabular;
+pub mod todotxt;
 pub mod jni_bindings;
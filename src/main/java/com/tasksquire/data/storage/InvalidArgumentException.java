@@ -0,0 +1,14 @@
+package com.tasksquire.data.storage;
+
+/**
+ * Thrown when an argument is malformed or out of range, such as a task
+ * spec or template parameters that are not JSON of the expected shape,
+ * a blank template name, a negative age, an unparseable date, an unknown
+ * timezone or bad export columns. Invalid tags in a spec raise
+ * {@link InvalidTagException} instead.
+ */
+public class InvalidArgumentException extends TaskChampionException {
+    public InvalidArgumentException(String message) {
+        super(message);
+    }
+}
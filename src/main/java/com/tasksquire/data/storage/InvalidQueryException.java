@@ -0,0 +1,11 @@
+package com.tasksquire.data.storage;
+
+/**
+ * Thrown when a task filter expression, sort specification or import
+ * document cannot be parsed, or when requested page bounds are invalid.
+ */
+public class InvalidQueryException extends TaskChampionException {
+    public InvalidQueryException(String message) {
+        super(message);
+    }
+}
@@ -8,9 +8,25 @@ package com.tasksquire.data.storage;
  * <h2>Capabilities</h2>
  * <ul>
  *   <li>Task creation, modification, and queries</li>
+ *   <li>TaskWarrior-style filter expressions, sorting and paging
+ *       evaluated natively</li>
+ *   <li>Ranked full-text search</li>
  *   <li>Tag and annotation management</li>
+ *   <li>Task duplication and stored task templates</li>
  *   <li>Arbitrary key/value attributes per task</li>
- *   <li>Undo via undo points in the operation journal</li>
+ *   <li>TaskWarrior-compatible recurring tasks</li>
+ *   <li>Undo via undo points in the operation journal, including
+ *       labelled points, multi-step undo and redo</li>
+ *   <li>Per-task change history from the operation journal</li>
+ *   <li>Import and export in TaskWarrior's JSON format</li>
+ *   <li>Import and export as iCalendar VTODOs</li>
+ *   <li>Export to CSV and Markdown checklists</li>
+ *   <li>Import and export in the todo.txt format</li>
+ *   <li>Purging of individual tasks and expiry of old deleted tasks</li>
+ *   <li>Consistent backups and restoring them into a new data directory</li>
+ *   <li>Verified migration of a replica to another data directory</li>
+ *   <li>Integrity checks and repair of a data directory</li>
+ *   <li>Storage statistics and compaction</li>
  *   <li>Synchronisation with a remote storage server (Google Cloud Storage
  *       or AWS S3-compatible)</li>
  * </ul>
@@ -75,9 +91,14 @@ package com.tasksquire.data.storage;
  *   <li>{@link InvalidReplicaException} — null or unregistered handle</li>
  *   <li>{@link InvalidUuidException} — UUID could not be parsed</li>
  *   <li>{@link InvalidStatusException} — status string not in
- *       {@code pending}, {@code completed}, {@code deleted}</li>
+ *       {@code pending}, {@code completed}, {@code deleted},
+ *       {@code recurring}</li>
  *   <li>{@link InvalidTagException} — tag string failed
  *       TaskChampion's tag-name validation</li>
+ *   <li>{@link InvalidQueryException} — filter expression, sort
+ *       specification or page bounds were invalid</li>
+ *   <li>{@link InvalidArgumentException} — any other malformed
+ *       argument, such as task spec JSON</li>
  *   <li>{@link ReplicaInitializationException} — storage could not be
  *       opened or created</li>
  *   <li>{@link SyncException} — synchronisation failed (invalid config,
@@ -89,12 +110,20 @@ package com.tasksquire.data.storage;
  * <p>A few queries return {@code null} or an empty array to mean "no such
  * value", which is a normal answer rather than a failure:
  * {@link #nativeGetUuidForIndex} returns {@code null} when no task occupies
- * the index, {@link #nativeGetTaskData} returns {@code null} when the task
- * does not exist, {@link #nativeGetAllTaskUuids} and
+ * the index, {@link #nativeGetIndexForUuid} returns {@code 0} when the task
+ * is not in the working set, {@link #nativeSearchTasks} returns an empty
+ * array when nothing matches, {@link #nativeGetTaskData} returns {@code null} when the task
+ * does not exist (and {@link #nativeGetTasks} puts {@code null} in that
+ * task's slot), {@link #nativeGetAllTaskUuids} and
  * {@link #nativeGetAllTasks} return an empty array when there are no
- * tasks, and {@link #nativeUndo} returns {@code false} when there is
- * nothing to undo. When reading many tasks, prefer the single
- * {@link #nativeGetAllTasks} call over iterating
+ * tasks, {@link #nativeQueryTasks} returns an empty array when no task
+ * matches, {@link #nativeUndo} returns {@code false} (and
+ * {@link #nativeGetUndoPreview} {@code null}) when there is nothing to
+ * undo, {@link #nativeRedo} returns {@code false} when there is
+ * nothing to redo, and {@link #nativeCreateFromTemplate} returns
+ * {@code null} when there is no such template. When reading many tasks, prefer the single
+ * {@link #nativeGetAllTasks} (or, for a known handful,
+ * {@link #nativeGetTasks}) call over iterating
  * {@link #nativeGetTaskData} per UUID.
  *
  * <p>Rust panics in the underlying library are caught at every native
@@ -111,6 +140,13 @@ public class TaskChampionJniImpl {
     
     /**
      * Initialize a new TaskChampion replica
+     *
+     * <p>If the data directory's database is damaged, this throws
+     * {@link ReplicaInitializationException};
+     * {@link #nativeCheckIntegrity} can diagnose and repair it. It also
+     * throws while {@link #nativeMigrateDataDir} is moving the directory,
+     * or {@link #nativeCheckIntegrity} is repairing it.
+     *
      * @param dataDir Directory to store task data
      * @return Opaque handle to the replica (0 on failure)
      */
@@ -131,6 +167,62 @@ public class TaskChampionJniImpl {
      */
     public static native boolean nativeUndo(long replicaPtr);
     
+    /**
+     * Redo the changes reversed by the most recent {@link #nativeUndo} or
+     * {@link #nativeUndoTo} on this handle.
+     *
+     * <p>Each successful undo on a handle pushes what it reversed onto
+     * that handle's redo stack, so several undos can be redone in turn,
+     * most recent first. Labels of undone undo points are restored with
+     * them. The stack lives in memory only: it is discarded when the
+     * handle is destroyed, and as soon as any task is changed in the
+     * data directory — through this handle, another handle, or sync —
+     * since replaying after that could not faithfully restore the undone
+     * state. Adding an undo point, labelled or not, changes no task and
+     * keeps the stack.
+     *
+     * @param replicaPtr Opaque handle to the replica
+     * @return {@code true} if changes were redone; {@code false} if there
+     *         is nothing to redo
+     */
+    public static native boolean nativeRedo(long replicaPtr);
+    
+    /**
+     * Describe what {@link #nativeUndo} would reverse, without reversing
+     * anything.
+     *
+     * <p>Returns a JSON document:
+     * <pre>
+     * {
+     *   "summary": "Undo: completed 'Buy milk'",
+     *   "tasks": [
+     *     {
+     *       "uuid": "...",
+     *       "description": "Buy milk",
+     *       "action": "completed 'Buy milk'",
+     *       "changes": [
+     *         {"property": "status", "old_value": "pending", "value": "completed"}
+     *       ]
+     *     }
+     *   ]
+     * }
+     * </pre>
+     * {@code summary} is a ready-to-display sentence; {@code tasks} lists
+     * each affected task in the order it was first changed, with its
+     * current (or last known) description, a short phrase for what
+     * happened to it, and every property change except {@code modified}.
+     * In {@code changes}, {@code old_value} is what undo restores and
+     * {@code value} what it discards; either is {@code null} for an
+     * absent property.
+     *
+     * <p>The preview reflects the replica at the time of the call; another
+     * write before {@link #nativeUndo} changes what undo reverses.
+     *
+     * @param replicaPtr Opaque handle to the replica
+     * @return JSON string, or {@code null} if there is nothing to undo
+     */
+    public static native String nativeGetUndoPreview(long replicaPtr);
+    
     /**
      * Add an undo point. The next undo will reverse all operations
      * recorded after this point.
@@ -138,6 +230,79 @@ public class TaskChampionJniImpl {
      */
     public static native void nativeAddUndoPoint(long replicaPtr);
     
+    /**
+     * Turn automatic undo points on or off for this handle.
+     *
+     * <p>When on, every call that changes tasks — each
+     * {@code nativeCreateTask} and {@code nativeTask*} setter, and each
+     * batch operation such as {@link #nativeGenerateRecurrences} — starts
+     * a new undo group, as if {@link #nativeAddUndoPoint} had been called
+     * just before it. The undo point is written in the same commit as
+     * the change, so the two cannot be separated. No point is added when
+     * the journal already ends with one, so an explicit
+     * {@link #nativeAddUndoPoint} (or {@link #nativeAddLabelledUndoPoint})
+     * before a call still produces a single group. {@link #nativeUndo}
+     * then reverses exactly one call.
+     *
+     * <p>The mode is off by default, preserving the behaviour where the
+     * client groups several calls into one undoable action. It applies to
+     * this handle only and is not persisted.
+     *
+     * @param replicaPtr Opaque handle to the replica
+     * @param enabled {@code true} to add undo points automatically
+     */
+    public static native void nativeSetAutoUndoPoints(long replicaPtr, boolean enabled);
+    
+    /**
+     * Add an undo point carrying a label, so that {@link #nativeUndoTo}
+     * can later undo back to it.
+     *
+     * <p>The point behaves exactly like one added by
+     * {@link #nativeAddUndoPoint}; the label is kept by the binding in its
+     * own database in the data directory and is local to this device
+     * (it is not synced). Labels need not be unique.
+     *
+     * @param replicaPtr Opaque handle to the replica
+     * @param label Label for the point, e.g. "Before bulk edit"
+     */
+    public static native void nativeAddLabelledUndoPoint(long replicaPtr, String label);
+    
+    /**
+     * List the undo points that can still be undone to, newest first.
+     *
+     * <p>Each element is a JSON object:
+     * <pre>
+     * {"label": "Before bulk edit", "timestamp": "1760616000", "changes": 12}
+     * </pre>
+     * {@code label} is {@code null} for points added by
+     * {@link #nativeAddUndoPoint}. {@code timestamp} is when a labelled
+     * point was added, or for an unlabelled one when the first change
+     * after it was made ({@code null} if there is no timestamped change
+     * after it). {@code changes} is the number of operations undoing to
+     * the point would reverse. Only points recorded since the last sync
+     * are listed, since synced changes cannot be undone.
+     *
+     * @param replicaPtr Opaque handle to the replica
+     * @return Array of JSON strings; empty when there is nothing to undo
+     */
+    public static native String[] nativeListUndoPoints(long replicaPtr);
+    
+    /**
+     * Undo every change made since the newest undo point with the given
+     * label, including any later undo points, as a single atomic step.
+     *
+     * <p>Either everything back to the point is reversed or nothing is:
+     * if another change lands in between reading the journal and
+     * reversing it, nothing is reversed and {@code false} is returned.
+     *
+     * @param replicaPtr Opaque handle to the replica
+     * @param label Label given to {@link #nativeAddLabelledUndoPoint}
+     * @return {@code true} if changes were reversed; {@code false} if no
+     *         undoable point has the label, or the journal changed
+     *         concurrently
+     */
+    public static native boolean nativeUndoTo(long replicaPtr, String label);
+    
     /**
      * Rebuild the working set so that 1-based pending-task indices reflect
      * current task state. Equivalent to TaskChampion's
@@ -179,13 +344,103 @@ public class TaskChampionJniImpl {
     public static native void nativeTaskSetDescription(long replicaPtr, String uuid, String description);
     
     /**
-     * Set the status of a task
+     * Set the status of a task.
+     *
+     * <p>TaskChampion manages the {@code end} timestamp: completing or
+     * deleting a task sets it unless already set, and returning to
+     * pending or recurring removes it. Writing {@code status} through
+     * {@link #nativeTaskSetValue} goes through the same path.
+     *
      * @param replicaPtr Opaque handle to the replica
      * @param uuid Task UUID
-     * @param status Task status ("pending", "completed", "deleted")
+     * @param status Task status ("pending", "completed", "deleted", or
+     *               "recurring" to make the task a recurrence template;
+     *               see {@link #nativeGenerateRecurrences})
      */
     public static native void nativeTaskSetStatus(long replicaPtr, String uuid, String status);
     
+    /**
+     * Mark a pending task completed, as TaskWarrior's {@code task done}
+     * does, and report which tasks that unblocked.
+     *
+     * <p>{@code end} is kept as by {@link #nativeTaskSetStatus}. Calling
+     * this on a task that is already completed does nothing and returns
+     * an empty array.
+     *
+     * @param replicaPtr Opaque handle to the replica
+     * @param uuid Task UUID
+     * @return UUIDs of the pending tasks that depended on this task and
+     *         now have no pending dependencies left
+     * @throws TaskChampionStorageException if no task has this UUID, or
+     *         it is deleted or recurring
+     */
+    public static native String[] nativeTaskDone(long replicaPtr, String uuid);
+    
+    /**
+     * Return a completed task to pending, reversing
+     * {@link #nativeTaskDone}, and report which tasks it blocks again.
+     *
+     * <p>{@code end} is removed. Calling this on a task that is already
+     * pending does nothing and returns an empty array. Deleted tasks are
+     * not restored; use {@link #nativeTaskSetStatus} for that.
+     *
+     * @param replicaPtr Opaque handle to the replica
+     * @param uuid Task UUID
+     * @return UUIDs of the pending tasks that depend on this task and
+     *         were unblocked until now
+     * @throws TaskChampionStorageException if no task has this UUID, or
+     *         it is deleted or recurring
+     */
+    public static native String[] nativeTaskUndone(long replicaPtr, String uuid);
+    
+    /**
+     * Permanently remove a task and all of its data.
+     *
+     * <p>Unlike setting status {@code deleted}, which keeps the task so it
+     * can be restored, purging removes it from the replica. The purge is
+     * an ordinary change: it can be undone until synced, and once synced
+     * it removes the task on other replicas too. Changes to the task made
+     * elsewhere and synced afterwards are ignored.
+     *
+     * @param replicaPtr Opaque handle to the replica
+     * @param uuid Task UUID
+     * @throws TaskChampionStorageException if no task has this UUID
+     */
+    public static native void nativeTaskPurge(long replicaPtr, String uuid);
+    
+    /**
+     * Copy a task to a new task, as TaskWarrior's {@code task duplicate}
+     * does, in one change.
+     *
+     * <p>The copy carries the source's description, tags, annotations,
+     * dependencies, project and other attributes. It gets a new
+     * {@code entry} and {@code modified}, is {@code pending} (or
+     * {@code recurring} if the source is a recurrence template), and has
+     * no {@code end} or {@code start}. A copy of a recurrence instance
+     * becomes an ordinary task.
+     *
+     * <p>{@code overridesJson} is then applied to the copy. It takes the
+     * layout of the task JSON {@link #nativeGetTaskData} returns, every
+     * field optional: {@code description} replaces the description,
+     * {@code tags} and {@code annotations} (objects with a
+     * {@code description} and optional {@code entry}) replace the copied
+     * ones, and each {@code udas} entry sets an attribute, or removes it
+     * when {@code null}. Placeholders (see {@link #nativeSaveTemplate})
+     * are not substituted here.
+     *
+     * @param replicaPtr Opaque handle to the replica
+     * @param sourceUuid UUID of the task to copy
+     * @param overridesJson Changes to make to the copy, or {@code null}
+     * @return UUID of the new task
+     * @throws InvalidTagException if {@code overridesJson} names an
+     *         invalid tag
+     * @throws InvalidArgumentException if {@code overridesJson} is
+     *         otherwise invalid
+     * @throws TaskChampionStorageException if no task has
+     *         {@code sourceUuid}
+     */
+    public static native String nativeTaskDuplicate(long replicaPtr, String sourceUuid, String overridesJson);
+    
     // Task property management
     
     /**
@@ -231,6 +486,66 @@ public class TaskChampionJniImpl {
      */
     public static native void nativeTaskRemoveAnnotation(long replicaPtr, String uuid, long entryTimestamp);
     
+    // Templates
+    
+    /**
+     * Store a task template under a name, replacing any template of that
+     * name.
+     *
+     * <p>The template takes the layout of {@code overridesJson} in
+     * {@link #nativeTaskDuplicate}. Its strings may contain placeholders,
+     * substituted by {@link #nativeCreateFromTemplate}:
+     * {@code {{name}}} for a caller-supplied parameter, {@code {{today}}}
+     * (the UTC date, {@code YYYY-MM-DD}), {@code {{now}}} (epoch seconds)
+     * and {@code {{date:EXPR}}} (epoch seconds of any date argument the
+     * binding accepts, e.g. {@code {{date:tomorrow}}} for {@code due}).
+     *
+     * <p>Templates are kept by the binding in its own database in the
+     * data directory; they are local to this device and not synced.
+     *
+     * @param replicaPtr Opaque handle to the replica
+     * @param name Template name; must be non-blank
+     * @param templateJson The template
+     * @throws InvalidTagException if the template names an invalid tag
+     * @throws InvalidArgumentException if the name is blank or the
+     *         template is otherwise invalid
+     */
+    public static native void nativeSaveTemplate(long replicaPtr, String name, String templateJson);
+    
+    /**
+     * Remove a stored template.
+     *
+     * @param replicaPtr Opaque handle to the replica
+     * @param name Template name
+     * @return {@code true} if a template was removed
+     */
+    public static native boolean nativeDeleteTemplate(long replicaPtr, String name);
+    
+    /**
+     * List the stored templates, ordered by name.
+     *
+     * @param replicaPtr Opaque handle to the replica
+     * @return One JSON document per template:
+     *         {@code {"name": "...", "template": {...}}}
+     */
+    public static native String[] nativeListTemplates(long replicaPtr);
+    
+    /**
+     * Create a pending task from a stored template, in one change.
+     *
+     * @param replicaPtr Opaque handle to the replica
+     * @param name Template name
+     * @param paramsJson JSON object of string values for the template's
+     *                   named placeholders, or {@code null}
+     * @return UUID of the new task, or {@code null} if there is no
+     *         template with this name
+     * @throws InvalidTagException if a tag is invalid once its
+     *         placeholders are substituted
+     * @throws InvalidArgumentException if {@code paramsJson} is invalid
+     *         or a placeholder has no value
+     */
+    public static native String nativeCreateFromTemplate(long replicaPtr, String name, String paramsJson);
+    
     // Data retrieval
     
     /**
@@ -258,6 +573,152 @@ public class TaskChampionJniImpl {
      */
     public static native String[] nativeGetAllTasks(long replicaPtr);
 
+    /**
+     * Get the full state of several specific tasks in a single call.
+     *
+     * <p>Element {@code i} of the result is the JSON document (in the
+     * format described on {@link #nativeGetTaskData}) of the task named
+     * by {@code uuids[i]}, or {@code null} if no such task exists. The
+     * per-replica lock is taken once for the whole batch, so the
+     * documents are mutually consistent.
+     *
+     * @param replicaPtr Opaque handle to the replica
+     * @param uuids Task UUIDs; must not be null and must not contain null
+     *              elements
+     * @return Array the same length as {@code uuids}
+     * @throws InvalidUuidException if any element is null or not a valid
+     *         UUID; no task is read in that case
+     */
+    public static native String[] nativeGetTasks(long replicaPtr, String[] uuids);
+
+    /**
+     * Get the full state of every task matching a TaskWarrior-style filter.
+     *
+     * <p>The filter is evaluated natively against the replica, so only
+     * matching documents cross the JNI boundary. Each element is one
+     * task's JSON document in exactly the format described on
+     * {@link #nativeGetTaskData}; element order is unspecified.
+     *
+     * <p>Supported syntax:
+     * <ul>
+     *   <li>{@code +tag} / {@code -tag} — tag present / absent, including
+     *       synthetic tags such as {@code +PENDING} or {@code +BLOCKED}</li>
+     *   <li>{@code name:value} — attribute equality; {@code project:Home}
+     *       also matches sub-projects such as {@code Home.Garden}, and an
+     *       empty value matches tasks without the attribute</li>
+     *   <li>{@code name.modifier:value} — with modifier {@code is},
+     *       {@code isnt}, {@code before}, {@code after}, {@code contains},
+     *       {@code hasnt}, {@code startswith}, {@code endswith},
+     *       {@code none} or {@code any}</li>
+     *   <li>any other word — case-insensitive match on the description
+     *       and annotations</li>
+     *   <li>{@code and}, {@code or} and parentheses; adjacent terms are
+     *       joined with {@code and}, which binds tighter than {@code or}</li>
+     * </ul>
+     *
+     * <p>Date attributes ({@code due}, {@code wait}, {@code entry},
+     * {@code modified}, {@code end}, {@code scheduled}, {@code until},
+     * {@code start}) accept epoch seconds, {@code YYYY-MM-DD}, RFC 3339,
+     * {@code YYYYMMDDTHHMMSSZ}, or {@code now}/{@code today}/
+     * {@code yesterday}/{@code tomorrow} (midnight UTC). Quote values
+     * containing spaces: {@code description.contains:'buy milk'}.
+     *
+     * <p>Unlike TaskWarrior, deleted tasks are not excluded implicitly;
+     * add {@code status:pending} or {@code -DELETED} as required.
+     *
+     * @param replicaPtr Opaque handle to the replica
+     * @param filter Filter expression; {@code null} or empty matches
+     *               every task
+     * @return Array of JSON strings, one per matching task
+     * @throws InvalidQueryException if the filter cannot be parsed
+     */
+    public static native String[] nativeQueryTasks(long replicaPtr, String filter);
+
+    /**
+     * Get one page of the tasks matching a filter, in a defined order.
+     *
+     * <p>Intended for list screens over large replicas: matching and
+     * sorting happen natively and only the requested page of JSON
+     * documents (in the format described on {@link #nativeGetTaskData})
+     * crosses the JNI boundary. Use {@link #nativeCountTasks} with the
+     * same filter to size the list.
+     *
+     * <p>The sort specification is a comma-separated list of keys in
+     * TaskWarrior report syntax, e.g. {@code "due+,priority-,description"}.
+     * A key names any attribute or UDA and may end in {@code +}
+     * (ascending, the default) or {@code -} (descending). Values compare
+     * numerically when both are numbers — which covers the epoch-second
+     * date attributes — and as strings otherwise; {@code priority} orders
+     * {@code L < M < H}. Tasks without a key's attribute sort after those
+     * with it in either direction. Ties are broken by UUID, so pages are
+     * stable as long as the replica is not modified between calls.
+     *
+     * @param replicaPtr Opaque handle to the replica
+     * @param filter Filter expression as for {@link #nativeQueryTasks};
+     *               {@code null} or empty matches every task
+     * @param sort Sort specification; {@code null} or empty orders by
+     *             UUID only
+     * @param offset Number of matching tasks to skip; must be {@code >= 0}
+     * @param limit Maximum number of tasks to return; must be {@code > 0}
+     * @return Array of JSON strings, at most {@code limit} long; empty when
+     *         {@code offset} is past the last match
+     * @throws InvalidQueryException if the filter or sort cannot be
+     *         parsed, or the page bounds are invalid
+     */
+    public static native String[] nativeQueryTasksPage(
+        long replicaPtr,
+        String filter,
+        String sort,
+        int offset,
+        int limit
+    );
+
+    /**
+     * Search tasks by free text, best matches first.
+     *
+     * <p>The query is split into words, each of which must match. A query
+     * word matches a word of the task's description, annotations, user
+     * tags, project or non-numeric UDA values that it equals or is a
+     * prefix of, ignoring case and diacritics ({@code "cafe"} finds
+     * {@code "Café"}). Results are ranked by where the words matched —
+     * description highest, then tags and project, then annotations and
+     * UDAs — with whole-word matches above prefix matches; ties are
+     * ordered by UUID.
+     *
+     * <p>The search index is built natively on the first search and then
+     * kept up to date by every write, including writes through other
+     * handles on the same data directory and sync, so results always
+     * reflect the current tasks.
+     *
+     * @param replicaPtr Opaque handle to the replica
+     * @param query Free-text query; a query with no words matches nothing
+     * @param filter Filter expression as for {@link #nativeQueryTasks}
+     *               restricting the results; {@code null} or empty applies
+     *               no restriction
+     * @param limit Maximum number of tasks to return; must be {@code > 0}
+     * @return Array of JSON strings in the format described on
+     *         {@link #nativeGetTaskData}, in rank order
+     * @throws InvalidQueryException if the filter cannot be parsed or the
+     *         limit is not positive
+     */
+    public static native String[] nativeSearchTasks(
+        long replicaPtr,
+        String query,
+        String filter,
+        int limit
+    );
+
+    /**
+     * Count the tasks matching a filter without marshalling any of them.
+     *
+     * @param replicaPtr Opaque handle to the replica
+     * @param filter Filter expression as for {@link #nativeQueryTasks};
+     *               {@code null} or empty matches every task
+     * @return Number of matching tasks
+     * @throws InvalidQueryException if the filter cannot be parsed
+     */
+    public static native int nativeCountTasks(long replicaPtr, String filter);
+
     /**
      * Get a task's full state as a JSON string.
      *
@@ -265,6 +726,7 @@ public class TaskChampionJniImpl {
      * <pre>
      * {
      *   "uuid": "abc-…",
+     *   "id": 12,
      *   "description": "…",
      *   "status": "pending",
      *   "entry": "1234567890",
@@ -288,6 +750,11 @@ public class TaskChampionJniImpl {
      * the underlying task has them set. Annotation entries are
      * second-precision Unix timestamps.
      *
+     * <p>{@code id} is the task's 1-based working-set index (the number
+     * TaskWarrior shows, as returned by {@link #nativeGetIndexForUuid}).
+     * It is the only non-string value, and is present only while the task
+     * is in the working set.
+     *
      * @param replicaPtr Opaque handle to the replica
      * @param uuid Task UUID
      * @return JSON string of the task's state, or {@code null} if no
@@ -297,11 +764,530 @@ public class TaskChampionJniImpl {
     
     /**
      * Get UUID for a task at the given index in the working set
+     *
+     * <p>Index 1 is the first task, matching the {@code id} reported by
+     * {@link #nativeGetWorkingSet} and {@link #nativeGetIndexForUuid}.
+     * Earlier versions returned the task with id {@code index - 1};
+     * callers that compensated by passing {@code id + 1} must now pass
+     * the id itself.
+     *
      * @param replicaPtr Opaque handle to the replica
      * @param index 1-based index (TaskWarrior style)
      * @return UUID string or null if not found
      */
     public static native String nativeGetUuidForIndex(long replicaPtr, int index);
+
+    /**
+     * Get the working-set index of a task, the reverse of
+     * {@link #nativeGetUuidForIndex}. Useful for telling the user
+     * "Created task 12" after a create: newly pending tasks join the
+     * working set as soon as their status is written.
+     *
+     * @param replicaPtr Opaque handle to the replica
+     * @param uuid Task UUID
+     * @return 1-based index, or 0 if the task is not in the working set
+     *         (including when no such task exists)
+     */
+    public static native int nativeGetIndexForUuid(long replicaPtr, String uuid);
+
+    /**
+     * Get the whole working set in a single call.
+     *
+     * <p>Each element is a JSON document {@code {"id": 3, "uuid": "abc-…"}}
+     * where {@code id} is the 1-based index accepted by
+     * {@link #nativeGetUuidForIndex}. Elements are in ascending
+     * {@code id} order; vacant indices (left behind by tasks completed or
+     * deleted since the last {@link #nativeRebuildWorkingSet}) are
+     * omitted, so ids may have gaps. An entry's task is not necessarily
+     * still pending.
+     *
+     * @param replicaPtr Opaque handle to the replica
+     * @return Array of JSON strings, one per occupied index
+     */
+    public static native String[] nativeGetWorkingSet(long replicaPtr);
+
+    /**
+     * Get the full state of every pending task in the working set, in
+     * working-set order.
+     *
+     * <p>Each element is a task document in the format described on
+     * {@link #nativeGetTaskData}, with an additional numeric {@code id}
+     * key holding the task's 1-based working-set index. Working-set
+     * entries whose task is no longer pending are skipped. This replaces
+     * a {@link #nativeGetUuidForIndex} plus {@link #nativeGetTaskData}
+     * round-trip per row when rendering a TaskWarrior-style list.
+     *
+     * @param replicaPtr Opaque handle to the replica
+     * @return Array of JSON strings, one per pending task
+     */
+    public static native String[] nativeGetPendingTasks(long replicaPtr);
+    
+    // History
+    
+    /**
+     * Get the change history of a task from the operation journal, oldest
+     * first.
+     *
+     * <p>Each element is a JSON object describing one operation that
+     * affected the task:
+     * <pre>
+     * {"type": "create", "uuid": "..."}
+     * {"type": "update", "uuid": "...", "property": "description",
+     *  "old_value": "Buy milk", "value": "Buy oat milk",
+     *  "timestamp": "1760616000"}
+     * {"type": "delete", "uuid": "...", "old_task": {"description": "...", ...}}
+     * </pre>
+     * {@code old_value} and {@code value} are {@code null} when the
+     * property was absent before or removed by the update. Creations
+     * carry no timestamp; the task's {@code entry} usually records it.
+     * Operations received by sync are included, but the journal does not
+     * record which device or user made a change. The history may be
+     * incomplete where TaskChampion, or {@link #nativeCompact}, has since
+     * discarded old operations, and can differ slightly between replicas after conflicting edits.
+     *
+     * @param replicaPtr Opaque handle to the replica
+     * @param uuid Task UUID
+     * @return Array of JSON strings; empty when no operations are recorded
+     *         for the task
+     */
+    public static native String[] nativeGetTaskHistory(long replicaPtr, String uuid);
+    
+    // Import and export
+    
+    /**
+     * Export tasks in TaskWarrior's JSON format, as the desktop
+     * {@code task export} writes it and {@code task import} reads it.
+     *
+     * <p>The result is a JSON array with one object per task, ordered by
+     * entry date. Every attribute, UDAs included, is a top-level field;
+     * dates are in TaskWarrior's compact form ({@code 20261016T120000Z});
+     * {@code tags} and {@code depends} are arrays of strings;
+     * {@code annotations} are objects with an {@code entry} date and a
+     * {@code description}. {@code id} is the working-set index, or 0.
+     *
+     * @param replicaPtr Opaque handle to the replica
+     * @param filter TaskWarrior filter expression (see
+     *               {@link #nativeQueryTasks}), or {@code null} to export
+     *               every task
+     * @return The export document
+     * @throws InvalidQueryException if the filter cannot be parsed
+     */
+    public static native String nativeExportTaskwarriorJson(long replicaPtr, String filter);
+    
+    /**
+     * Import tasks from a TaskWarrior export, as the desktop
+     * {@code task import} does, in one change.
+     *
+     * <p>Accepts the format {@link #nativeExportTaskwarriorJson} writes,
+     * including the older one-object-per-line layout, comma-separated
+     * {@code tags}/{@code depends}, and status {@code waiting} (imported
+     * as pending with its {@code wait} date). {@code id} and
+     * {@code urgency} are ignored. A task whose UUID already exists is
+     * replaced by the imported one.
+     *
+     * <p>Each task is imported or skipped on its own: one missing its
+     * {@code uuid} or {@code description}, with an unknown status, an
+     * unparseable date, tag or dependency, a raw {@code tag_},
+     * {@code dep_} or {@code annotation_} key, or a UUID repeated within
+     * the export is skipped with a reason, as is one identical to the
+     * task already stored.
+     *
+     * @param replicaPtr Opaque handle to the replica
+     * @param jsonOrPath The export document itself, or the path of a
+     *                   file containing it
+     * @return A JSON report:
+     *         {@code {"created": [uuid...], "updated": [uuid...],
+     *         "skipped": [{"index": n, "uuid": "...", "reason": "..."}]}},
+     *         where {@code index} is the task's position in the export
+     *         and {@code uuid} is null if it had none
+     * @throws InvalidQueryException if the document is not valid JSON
+     * @throws TaskChampionStorageException if the file cannot be read
+     */
+    public static native String nativeImportTaskwarriorJson(long replicaPtr, String jsonOrPath);
+    
+    /**
+     * Export tasks as an iCalendar (RFC 5545) document with one VTODO
+     * per task, ordered by entry date, for calendar and to-do apps.
+     *
+     * <p>{@code UID} is the task UUID, {@code SUMMARY} the description,
+     * {@code DUE} and {@code DTSTART} the due and scheduled dates, and
+     * {@code CATEGORIES} the tags. {@code STATUS} is
+     * {@code NEEDS-ACTION} ({@code IN-PROCESS} once started),
+     * {@code COMPLETED} or {@code CANCELLED}; priority H/M/L becomes
+     * {@code PRIORITY} 1/5/9, annotations {@code COMMENT}s and
+     * dependencies {@code RELATED-TO;RELTYPE=DEPENDS-ON}. A recurrence
+     * template carries an {@code RRULE} when its period has one. Every
+     * other attribute, UDAs included, is an
+     * {@code X-TASKCHAMPION-UDA;X-NAME="key"} property holding the
+     * stored value, so {@link #nativeImportIcs} restores the tasks
+     * exactly.
+     *
+     * @param replicaPtr Opaque handle to the replica
+     * @param filter TaskWarrior filter expression (see
+     *               {@link #nativeQueryTasks}), or {@code null} to export
+     *               every task
+     * @return The iCalendar document, with CRLF line endings
+     * @throws InvalidQueryException if the filter cannot be parsed
+     */
+    public static native String nativeExportIcs(long replicaPtr, String filter);
+    
+    /**
+     * Import the VTODOs of an iCalendar document in one change, with the
+     * same replace-or-skip rules and report as
+     * {@link #nativeImportTaskwarriorJson}.
+     *
+     * <p>Reads {@link #nativeExportIcs} output back unchanged, and
+     * VTODOs from other applications: a {@code UID} that is not a UUID
+     * maps to a stable UUID derived from it, {@code DESCRIPTION} becomes
+     * an annotation, {@code PRIORITY} 1-4/5/6-9 becomes H/M/L, and an
+     * {@code RRULE} on a pending task with a due date makes it a
+     * recurrence template. Times with a {@code TZID} or no zone are
+     * read as UTC. A VTODO without a {@code SUMMARY}, or with an
+     * invalid date, category, dependency or annotation (including one
+     * named by an {@code X-TASKCHAMPION-UDA}), is skipped with a reason;
+     * other components are ignored.
+     *
+     * <p>This takes the document's text only, never a path; read the
+     * file on the Java side. A leading byte order mark is ignored.
+     *
+     * @param replicaPtr Opaque handle to the replica
+     * @param ics The iCalendar document
+     * @return A JSON report as for {@link #nativeImportTaskwarriorJson},
+     *         where {@code uuid} is the VTODO's {@code UID}
+     * @throws InvalidQueryException if the input is not an iCalendar
+     *         document
+     */
+    public static native String nativeImportIcs(long replicaPtr, String ics);
+    
+    /**
+     * Export tasks as CSV (RFC 4180, CRLF line endings) for
+     * spreadsheets: a header row of column names, then one row per task
+     * in entry-date order.
+     *
+     * <p>A column is {@code uuid}, {@code id} (working-set index),
+     * {@code tags} (space-separated), {@code depends} (space-separated
+     * UUIDs), {@code annotations} (one per line, each prefixed with its
+     * date), or the name of any stored attribute or UDA. Dates among the
+     * built-in attributes are written as {@code YYYY-MM-DD HH:MM} in
+     * {@code timezone}; other values are written as stored, except that
+     * a cell starting with {@code =}, {@code +}, {@code -} or {@code @}
+     * is prefixed with {@code '} so spreadsheets show it rather than run
+     * it as a formula. A task without a column's attribute has an empty
+     * cell.
+     *
+     * @param replicaPtr Opaque handle to the replica
+     * @param filter TaskWarrior filter expression (see
+     *               {@link #nativeQueryTasks}), or {@code null} to export
+     *               every task
+     * @param columns Column names in order, or {@code null} for
+     *                {@code id, description, status, project, priority,
+     *                due, tags}
+     * @param timezone IANA timezone name (e.g. {@code Europe/London}), or
+     *                 {@code null} for UTC
+     * @return The CSV document
+     * @throws InvalidQueryException if the filter cannot be parsed
+     * @throws InvalidArgumentException if the timezone is unknown, or a
+     *         column name is empty, null or repeated
+     */
+    public static native String nativeExportCsv(long replicaPtr, String filter, String[] columns, String timezone);
+    
+    /**
+     * Export tasks as a Markdown checklist, in entry-date order.
+     *
+     * <p>Each task is an item {@code - [ ] description}, ticked once
+     * completed and struck through once deleted, followed by its project,
+     * due date (as {@code YYYY-MM-DD HH:MM} in {@code timezone}) and
+     * tags; its annotations are nested items. Markdown syntax in
+     * descriptions is escaped.
+     *
+     * @param replicaPtr Opaque handle to the replica
+     * @param filter TaskWarrior filter expression (see
+     *               {@link #nativeQueryTasks}), or {@code null} to export
+     *               every task
+     * @param timezone IANA timezone name, or {@code null} for UTC
+     * @return The Markdown document
+     * @throws InvalidQueryException if the filter cannot be parsed
+     * @throws InvalidArgumentException if the timezone is unknown
+     */
+    public static native String nativeExportMarkdown(long replicaPtr, String filter, String timezone);
+    
+    /**
+     * Export pending and completed tasks in the todo.txt format, one line
+     * per task in entry-date order.
+     *
+     * <p>A line is {@code x} and the completion date for a completed
+     * task, the priority ({@code (A)}/{@code (B)}/{@code (C)} for
+     * H/M/L) for an open one, the creation date, the description, then
+     * {@code +project}, an {@code @context} per tag, {@code due:} and
+     * {@code t:} (wait) dates and other attributes as
+     * {@code key:value}. A completed task's priority is written as
+     * {@code pri:A}. Dates are UTC calendar days. Deleted tasks,
+     * recurrence templates, annotations and dependencies have no todo.txt
+     * form and are left out.
+     *
+     * @param replicaPtr Opaque handle to the replica
+     * @param filter TaskWarrior filter expression (see
+     *               {@link #nativeQueryTasks}), or {@code null} to export
+     *               every task
+     * @return The todo.txt document
+     * @throws InvalidQueryException if the filter cannot be parsed
+     */
+    public static native String nativeExportTodoTxt(long replicaPtr, String filter);
+    
+    /**
+     * Import a todo.txt document in one change, one task per non-blank
+     * line, reading what {@link #nativeExportTodoTxt} writes. Priorities
+     * below {@code (C)} and further {@code +project}s are kept in the
+     * {@code todotxt_priority} and {@code todotxt_projects} UDAs so they
+     * export again unchanged.
+     *
+     * <p>todo.txt has no task identity: each line's UUID is derived from
+     * its text, and a line whose task already exists is skipped as
+     * {@code already imported}, so importing the same file twice adds
+     * its tasks once. A line with no description, an unparseable
+     * {@code due:} or {@code t:} date, an invalid context, or a
+     * {@code key:value} naming a built-in attribute is skipped with a
+     * reason. A word is only read as {@code key:value} when the key
+     * starts with a letter, is not {@code tag_}-, {@code annotation_}-
+     * or {@code dep_}-prefixed and is no URI scheme, and the value has no
+     * further colon; anything else, such as {@code 10:30} or
+     * {@code mailto:a@b}, stays in the description.
+     *
+     * <p>Like {@link #nativeImportIcs}, this takes the document's text
+     * only, never a path: any line is a valid todo.txt task, so a path
+     * could not be told apart from one. Read the file on the Java side.
+     *
+     * @param replicaPtr Opaque handle to the replica
+     * @param text The todo.txt document
+     * @return A JSON report as for {@link #nativeImportTaskwarriorJson};
+     *         {@code updated} is always empty, {@code index} counts
+     *         non-blank lines from 0 and {@code uuid} is null
+     */
+    public static native String nativeImportTodoTxt(long replicaPtr, String text);
+    
+    // Recurrence
+    
+    /**
+     * Generate the instances of recurring tasks that fall due by a
+     * horizon.
+     *
+     * <p>A recurring task is a template in TaskWarrior's layout: status
+     * {@code recurring} and the attributes {@code recur} (the period) and
+     * {@code due} (the first instance's due date), optionally
+     * {@code until} (no instances after this date) and {@code rtype}
+     * ({@code periodic}, the default, or {@code chained}). Set them with
+     * {@link #nativeTaskSetStatus} and {@link #nativeTaskSetValue}.
+     *
+     * <p>Each instance is a new pending task carrying a copy of the
+     * template's description, attributes, tags and annotations, with
+     * {@code parent} set to the template's UUID, {@code imask} to its
+     * 0-based position in the series and {@code due} to its own due date
+     * ({@code wait} and {@code scheduled} keep their offset from due).
+     * Periodic instance {@code n} is due {@code n} periods after the
+     * template's due date; a chained series gets its next instance only
+     * once the previous one is completed or deleted, due one period
+     * after it ended. The template's {@code mask} tracks which instances
+     * exist, so calling this repeatedly — or on several devices that
+     * later sync, or alongside the desktop {@code task} CLI — never
+     * duplicates an instance. When {@code until} has passed the template
+     * is marked deleted.
+     *
+     * <p>Periods may be named ({@code daily}, {@code weekly},
+     * {@code weekdays}, {@code biweekly}, {@code monthly},
+     * {@code quarterly}, {@code semiannual}, {@code yearly}, ...), a
+     * count and unit ({@code 3d}, {@code 2 weeks}, {@code 6mo},
+     * {@code 1y}) or an ISO-8601 duration ({@code P3D}, {@code P1Y2M},
+     * {@code PT12H}). Dates are computed in UTC; monthly steps clamp to
+     * the end of shorter months. A template whose recurrence cannot be
+     * understood is skipped, not reported.
+     *
+     * <p>Nothing generates instances implicitly; call this on start-up,
+     * after sync, or periodically. At most 1000 instances per template
+     * are generated per call.
+     *
+     * @param replicaPtr Opaque handle to the replica
+     * @param horizon Latest due date to generate instances for, in any
+     *                form accepted by filter dates (epoch seconds,
+     *                {@code now}, {@code tomorrow}, {@code YYYY-MM-DD},
+     *                RFC 3339 or {@code YYYYMMDDTHHMMSSZ})
+     * @return Number of instances created
+     * @throws InvalidArgumentException if the horizon cannot be parsed
+     */
+    public static native int nativeGenerateRecurrences(long replicaPtr, String horizon);
+    
+    // Maintenance
+    
+    /**
+     * Purge deleted tasks that have not been modified for a given time,
+     * as {@link #nativeTaskPurge} would one at a time.
+     *
+     * <p>Only tasks with status {@code deleted} are eligible; completed
+     * tasks are kept. A task's age is taken from its {@code modified}
+     * timestamp, or its {@code end} if it has none; a task with neither
+     * is kept. TaskWarrior expires after 180 days
+     * ({@code 15552000} seconds). All purges form one change.
+     *
+     * @param replicaPtr Opaque handle to the replica
+     * @param olderThanSeconds Minimum time since the task was last
+     *                         modified; {@code 0} purges every deleted task
+     * @return Number of tasks purged
+     * @throws InvalidArgumentException if {@code olderThanSeconds} is
+     *         negative
+     */
+    public static native int nativeExpireTasks(long replicaPtr, long olderThanSeconds);
+    
+    /**
+     * Write a consistent snapshot of the replica's database to a new
+     * file, safe to take while this and other handles are open.
+     *
+     * <p>The snapshot is one SQLite file holding the operation journal,
+     * task data, working set and sync state as of a single moment,
+     * compacted; copying the data directory by hand can tear a commit in
+     * progress. It is written beside {@code destPath} and renamed into
+     * place once validated, so a file at {@code destPath} is always a
+     * complete backup. Undo-point labels and templates are device-local
+     * metadata kept in the binding's own database, and are not included.
+     *
+     * @param replicaPtr Opaque handle to the replica
+     * @param destPath Path of the backup file; must not exist
+     * @return Size of the backup in bytes
+     * @throws TaskChampionStorageException if {@code destPath} exists or
+     *         the snapshot cannot be written
+     */
+    public static native long nativeBackup(long replicaPtr, String destPath);
+    
+    /**
+     * Restore a backup written by {@link #nativeBackup} as the database
+     * of a new data directory, for {@link #nativeInitialize} to open.
+     *
+     * <p>The backup is validated first: SQLite's integrity check must
+     * pass, the schema must be one this binding supports and every task
+     * must decode.
+     * {@code dataDir} must not exist or be empty, so a restore never
+     * overwrites live data; to replace a replica, restore beside it,
+     * then swap directories with no handle open. On failure
+     * {@code dataDir} is left as it was given.
+     *
+     * @param dataDir Directory to restore into, created if missing
+     * @param backupPath Path of the backup file
+     * @return Number of tasks restored
+     * @throws TaskChampionStorageException if the backup is missing or
+     *         invalid, {@code dataDir} is not empty, or the copy fails
+     */
+    public static native int nativeRestore(String dataDir, String backupPath);
+    
+    /**
+     * Check the database in a data directory for damage, and optionally
+     * repair it. Works on a directory {@link #nativeInitialize} rejects
+     * with {@link ReplicaInitializationException}, as long as its schema
+     * is one this binding supports, and needs no handle.
+     *
+     * <p>Runs SQLite's integrity check, then reports each task whose
+     * UUID or data does not decode ({@code unreadable_task}), has an
+     * unknown status ({@code invalid_status}), or has a {@code tag_},
+     * {@code annotation_} or {@code dep_} key naming no valid tag,
+     * timestamp or UUID ({@code invalid_tag}, {@code invalid_annotation},
+     * {@code invalid_dependency}), and each working-set entry whose task
+     * does not exist ({@code orphan_working_set}).
+     *
+     * <p>Without {@code repair} the database is opened read-only. With
+     * it, in one transaction, unreadable tasks and orphan entries are
+     * deleted and invalid keys removed, an invalid status among them (a
+     * task without a status reads as pending); SQLite-level damage is
+     * answered with {@code REINDEX}, which fixes corrupt indexes only.
+     * Repair needs the directory to itself: no handle may be open on it,
+     * and {@link #nativeInitialize} on it throws until repair returns.
+     *
+     * <p>Repairs are local-only. They bypass the operation journal, so
+     * they are neither undoable nor synced, and a later sync may bring
+     * back values other replicas still hold.
+     *
+     * @param dataDir The data directory
+     * @param repair Whether to repair what can be repaired
+     * @return A JSON report:
+     *         {@code {"ok": bool, "sqlite": ["..."], "sqliteRepaired": bool,
+     *         "issues": [{"kind": "...", "uuid": "...", "key": "...",
+     *         "detail": "...", "repaired": bool}]}}, where {@code ok}
+     *         means nothing unrepaired remains and {@code key} is the
+     *         offending key, or an orphan's working-set id
+     * @throws TaskChampionStorageException if there is no database in
+     *         {@code dataDir}, it cannot be read at all, or
+     *         {@code repair} is set while a handle is open on it
+     */
+    public static native String nativeCheckIntegrity(String dataDir, boolean repair);
+    
+    /**
+     * Storage statistics for the replica, for a settings screen.
+     *
+     * @param replicaPtr Opaque handle to the replica
+     * @return A JSON object:
+     *         {@code {"tasks": {"pending": n, "completed": n, "deleted": n,
+     *         "recurring": n}, "totalTasks": n, "operations": n,
+     *         "unsyncedOperations": n, "undoDepth": n,
+     *         "workingSetSize": n, "udaCount": n, "udas": ["..."],
+     *         "diskBytes": n, "oldestUnsynced": "<epoch seconds>"}}.
+     *         {@code tasks} also counts any non-standard status found.
+     *         {@code operations} is the whole journal, synced or not;
+     *         {@code undoDepth} is the number of undo points
+     *         {@link #nativeUndo} can still reach. {@code udas} names
+     *         every attribute set on some task that TaskWarrior does not
+     *         define. {@code diskBytes} is the size of every file in the
+     *         data directory, WAL included. {@code oldestUnsynced} is the
+     *         time of the oldest timestamped change not yet synced, or
+     *         null.
+     */
+    public static native String nativeGetReplicaStats(long replicaPtr);
+    
+    /**
+     * Shrink the replica's storage: delete every operation the sync
+     * server has already acknowledged, expire deleted tasks untouched for
+     * 180 days (as {@link #nativeExpireTasks} with {@code 15552000}),
+     * then rebuild the SQLite file to return the freed space.
+     *
+     * <p>Operations are only trimmed when every one has been
+     * acknowledged, so run this right after a successful sync; while any
+     * change is unsynced nothing is trimmed and {@code trimSkipped} says
+     * why. Undo is unaffected, since it only reaches unsynced operations,
+     * but {@link #nativeGetTaskHistory} loses the trimmed changes. The rebuild briefly needs free space for a copy
+     * of the database and waits for other handles' writes.
+     *
+     * @param replicaPtr Opaque handle to the replica
+     * @return A JSON report:
+     *         {@code {"tasksExpired": n, "operationsTrimmed": n,
+     *         "trimSkipped": "..." | null, "bytesBefore": n,
+     *         "bytesAfter": n, "bytesReclaimed": n}}, sizes covering every
+     *         file in the data directory
+     */
+    public static native String nativeCompact(long replicaPtr);
+    
+    /**
+     * Move a replica's database to another data directory, such as from
+     * internal to adopted storage.
+     *
+     * <p>The database is copied as one consistent snapshot, then checked
+     * against the original: same task and operation counts, same hash
+     * over every task, working-set and sync-state row, and readable by
+     * TaskChampion. Only then is the binding's own database of labels and
+     * templates copied too, and the old database files removed, along
+     * with {@code oldDir} itself if nothing else is left in it. On
+     * failure the original is untouched and {@code newDir} holds no
+     * database.
+     *
+     * <p>No handle may be open on {@code oldDir}; call
+     * {@link #nativeDestroy} on each first, and {@link #nativeInitialize}
+     * with {@code newDir} afterwards. Until this returns,
+     * {@link #nativeInitialize} on {@code oldDir} throws
+     * {@link ReplicaInitializationException}.
+     *
+     * @param oldDir Current data directory
+     * @param newDir Directory to move into, created if missing; must be
+     *        empty and must not contain or lie inside {@code oldDir}
+     * @return Number of tasks moved
+     * @throws TaskChampionStorageException if a handle is open on
+     *         {@code oldDir}, either directory is unsuitable, or the
+     *         copy does not verify
+     */
+    public static native int nativeMigrateDataDir(String oldDir, String newDir);
     
     // Synchronization
     
@@ -0,0 +1,166 @@
+//! Moving a replica's database to a new data directory.
+//!
+//! The copy is a [`backup`](crate::backup::backup) snapshot written
+//! straight into the new directory, so it is consistent even if the WAL
+//! holds uncheckpointed commits. It is then compared with the original
+//! (task count, and a hash over every task, working-set and sync-state
+//! row) and opened once with TaskChampion; only when both agree is the
+//! binding's own database (see [`crate::journal`]) copied after it, and
+//! the old database files removed. Other files in the old directory are
+//! left where they are.
+
+use crate::backup;
+use crate::journal::{self, META_DB_FILE};
+use crate::storage::{self, open_on_disk, DB_FILE};
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+use std::path::{Path, PathBuf};
+
+/// Task count and content hash of a database, for comparing a copy with
+/// its original.
+#[derive(Debug, PartialEq)]
+pub struct Fingerprint {
+    pub tasks: usize,
+    pub operations: usize,
+    pub hash: u64,
+}
+
+/// Fingerprint the database at `path`, opened read-only.
+pub fn fingerprint(path: &Path) -> Result<Fingerprint, String> {
+    let conn = storage::open_read_only(path)?;
+    let mut hasher = DefaultHasher::new();
+    let tasks = storage::task_rows(&conn)?;
+    ("tasks", &tasks).hash(&mut hasher);
+    ("working_set", storage::working_set_rows(&conn)?).hash(&mut hasher);
+    ("sync_meta", storage::sync_meta_rows(&conn)?).hash(&mut hasher);
+    let operations = storage::operation_count(&conn)?;
+    Ok(Fingerprint { tasks: tasks.len(), operations, hash: hasher.finish() })
+}
+
+fn same_or_nested(a: &Path, b: &Path) -> bool {
+    a.starts_with(b) || b.starts_with(a)
+}
+
+/// The database files SQLite may keep for `db`.
+fn database_files(db: &Path) -> [PathBuf; 3] {
+    let with_suffix = |suffix: &str| {
+        let mut name = db.as_os_str().to_owned();
+        name.push(suffix);
+        PathBuf::from(name)
+    };
+    [db.to_path_buf(), with_suffix("-wal"), with_suffix("-shm")]
+}
+
+/// Move the database in `old_dir` to `new_dir`, which must not exist or
+/// be empty, and must not contain or lie inside `old_dir`. No handle may
+/// be open on `old_dir`. Returns the fingerprint of the moved database.
+pub fn migrate(old_dir: &Path, new_dir: &Path) -> Result<Fingerprint, String> {
+    let old_db = old_dir.join(DB_FILE);
+    if !old_db.is_file() {
+        return Err(format!("No database in {}", old_dir.display()));
+    }
+    let created = !new_dir.exists();
+    if !created {
+        let mut entries = std::fs::read_dir(new_dir)
+            .map_err(|e| format!("Failed to read {}: {}", new_dir.display(), e))?;
+        if entries.next().is_some() {
+            return Err(format!("{} is not empty; migration needs a fresh directory", new_dir.display()));
+        }
+    }
+    std::fs::create_dir_all(new_dir).map_err(|e| format!("Failed to create {}: {}", new_dir.display(), e))?;
+    let old_canonical = old_dir.canonicalize().map_err(|e| format!("Failed to resolve {}: {}", old_dir.display(), e))?;
+    let new_canonical = new_dir.canonicalize().map_err(|e| format!("Failed to resolve {}: {}", new_dir.display(), e))?;
+    if same_or_nested(&old_canonical, &new_canonical) {
+        if created {
+            let _ = std::fs::remove_dir(new_dir);
+        }
+        return Err(format!(
+            "{} and {} overlap; migration needs separate directories",
+            old_dir.display(),
+            new_dir.display()
+        ));
+    }
+
+    let new_db = new_dir.join(DB_FILE);
+    let copied = backup::backup(old_dir, &new_db).and_then(|_| {
+        let original = fingerprint(&old_db)?;
+        let copy = fingerprint(&new_db)?;
+        if original != copy {
+            return Err(format!(
+                "Copy does not match the original ({} tasks, {} operations vs {} tasks, {} operations)",
+                original.tasks, original.operations, copy.tasks, copy.operations
+            ));
+        }
+        let opened = open_on_disk(new_dir, false)?
+            .all_task_uuids()
+            .map_err(|e| format!("Failed to read migrated database: {}", e))?;
+        if opened.len() != copy.tasks {
+            return Err(format!("Migrated database shows {} of {} tasks", opened.len(), copy.tasks));
+        }
+        journal::copy_metadata(old_dir, new_dir)?;
+        Ok(copy)
+    });
+    let new_meta = new_dir.join(META_DB_FILE);
+    let copy = match copied {
+        Ok(copy) => copy,
+        Err(e) => {
+            for file in database_files(&new_db).into_iter().chain(database_files(&new_meta)) {
+                let _ = std::fs::remove_file(file);
+            }
+            return Err(e);
+        }
+    };
+
+    let old_meta = old_dir.join(META_DB_FILE);
+    for file in database_files(&old_db).into_iter().chain(database_files(&old_meta)) {
+        if let Err(e) = std::fs::remove_file(&file) {
+            if e.kind() != std::io::ErrorKind::NotFound {
+                return Err(format!("Migrated, but failed to remove {}: {}", file.display(), e));
+            }
+        }
+    }
+    // Only succeeds if nothing else was kept there.
+    let _ = std::fs::remove_dir(old_dir);
+    Ok(copy)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::templates::{self, TaskSpec};
+    use taskchampion::{Operations, Status};
+    use tempfile::TempDir;
+    use uuid::Uuid;
+
+    #[test]
+    fn test_migrate_moves_database() {
+        let dir = TempDir::new().unwrap();
+        let old_dir = dir.path().join("internal");
+        let new_dir = dir.path().join("encrypted");
+        let uuid = Uuid::new_v4();
+        {
+            let mut replica = open_on_disk(&old_dir, true).unwrap();
+            let mut ops = Operations::new();
+            let mut task = replica.create_task(uuid, &mut ops).unwrap();
+            task.set_description("Move me".to_string(), &mut ops).unwrap();
+            task.set_status(Status::Pending, &mut ops).unwrap();
+            replica.commit_operations(ops).unwrap();
+            let spec = TaskSpec::parse(r#"{"description": "Call"}"#).unwrap();
+            templates::save(&journal::open(&old_dir).unwrap(), "call", &spec).unwrap();
+        }
+        let before = fingerprint(&old_dir.join(DB_FILE)).unwrap();
+
+        assert!(migrate(&old_dir, &old_dir.join("nested")).is_err());
+        assert!(old_dir.join(DB_FILE).exists());
+
+        let moved = migrate(&old_dir, &new_dir).unwrap();
+        assert_eq!(moved, before);
+        assert!(!old_dir.exists());
+        let mut replica = open_on_disk(&new_dir, false).unwrap();
+        assert_eq!(replica.get_task(uuid).unwrap().unwrap().get_description(), "Move me");
+        assert!(templates::get(&journal::open(&new_dir).unwrap(), "call").unwrap().is_some());
+
+        // Nothing left to move.
+        assert!(migrate(&old_dir, &dir.path().join("again")).is_err());
+    }
+}
@@ -0,0 +1,578 @@
+//! TaskWarrior-compatible recurrence.
+//!
+//! A recurring task is a *template*: status `recurring`, a `recur` period
+//! and a `due` date, optionally an `until` date and an `rtype` of
+//! `periodic` (the default) or `chained`. Instances are ordinary pending
+//! tasks carrying `parent` (the template's UUID) and `imask` (their
+//! 0-based position in the series), plus copies of the template's other
+//! attributes, tags and annotations.
+//!
+//! The template's `mask` records one character per generated instance:
+//! `-` pending, `W` waiting, `+` completed and `X` deleted. Its length is
+//! the number of instances generated so far, so generation never repeats
+//! an instance even after the instance itself has been purged. This is
+//! the layout the `task` CLI reads and writes, so a replica synced with a
+//! desktop TaskWarrior neither loses nor duplicates instances.
+//!
+//! Periodic instance `n` is due at the template's `due` plus `n` periods.
+//! Chained series generate one instance at a time: the next instance is
+//! due one period after the previous one ended, and is only generated
+//! once no instance is pending. Calendar arithmetic is done in UTC;
+//! month steps clamp to the end of shorter months, always measured from
+//! the template's `due` so a series starting on the 31st does not drift.
+
+use crate::dates::stored_timestamp;
+use crate::jni_bindings::raw_taskmap;
+use chrono::{DateTime, Datelike, Duration, Months, Utc, Weekday};
+use log::warn;
+use std::collections::HashMap;
+use taskchampion::{Operations, Replica, Status, Task};
+use uuid::Uuid;
+
+/// Most instances generated for one template in one call, so a short
+/// period with a distant horizon (or an ancient `due`) cannot flood the
+/// replica. A later call continues where this one stopped.
+const MAX_INSTANCES_PER_CALL: usize = 1000;
+
+/// Template properties that are not copied to instances as-is.
+const NOT_INHERITED: &[&str] = &[
+    "status", "mask", "imask", "parent", "entry", "modified", "start", "end", "due", "wait",
+    "scheduled",
+];
+
+/// A recurrence period: whole months plus a fixed number of seconds, or
+/// every weekday (Monday to Friday).
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Period {
+    Span { months: u32, seconds: i64 },
+    Weekdays,
+}
+
+/// Parse a TaskWarrior recurrence period: a named period (`daily`,
+/// `weekly`, `weekdays`, `biweekly`, `monthly`, `quarterly`,
+/// `semiannual`, `yearly`, ...), a count and unit (`3d`, `2 weeks`,
+/// `6mo`, `1y`), or an ISO-8601 duration (`P3D`, `P1Y2M`, `PT12H`).
+pub fn parse_period(value: &str) -> Result<Period, String> {
+    let trimmed = value.trim();
+    let lower = trimmed.to_ascii_lowercase();
+    let period = match lower.as_str() {
+        "weekdays" => Some(Period::Weekdays),
+        "hourly" => Some(span(0, 3600)),
+        "daily" | "day" => Some(span(0, 86400)),
+        "weekly" | "week" | "sennight" => Some(span(0, 7 * 86400)),
+        "biweekly" | "fortnight" => Some(span(0, 14 * 86400)),
+        "monthly" | "month" => Some(span(1, 0)),
+        "bimonthly" => Some(span(2, 0)),
+        "quarterly" | "quarter" => Some(span(3, 0)),
+        "semiannual" => Some(span(6, 0)),
+        "annual" | "yearly" | "year" => Some(span(12, 0)),
+        "biannual" | "biyearly" => Some(span(24, 0)),
+        _ => None,
+    };
+    let period = match period {
+        Some(p) => p,
+        None if trimmed.starts_with(['P', 'p']) => parse_iso_duration(&trimmed[1..])
+            .ok_or_else(|| format!("Invalid ISO-8601 period '{}'", trimmed))?,
+        None => {
+            let digits = lower.find(|c: char| !c.is_ascii_digit()).unwrap_or(lower.len());
+            let count: u32 = if digits == 0 {
+                1
+            } else {
+                lower[..digits].parse().map_err(|_| format!("Invalid period '{}'", trimmed))?
+            };
+            let unit = lower[digits..].trim();
+            unit_period(unit, count).ok_or_else(|| format!("Invalid period '{}'", trimmed))?
+        }
+    };
+    if period == span(0, 0) {
+        return Err(format!("Period '{}' is empty", trimmed));
+    }
+    if let Period::Span { seconds, .. } = period {
+        Duration::try_seconds(seconds).ok_or_else(|| format!("Period '{}' is out of range", trimmed))?;
+    }
+    Ok(period)
+}
+
+fn span(months: u32, seconds: i64) -> Period {
+    Period::Span { months, seconds }
+}
+
+/// `count` units, or `None` for an unknown unit or a span too large to
+/// represent.
+fn unit_period(unit: &str, count: u32) -> Option<Period> {
+    let n = count as i64;
+    Some(match unit {
+        "s" | "sec" | "secs" | "second" | "seconds" => span(0, n),
+        "min" | "mins" | "minute" | "minutes" => span(0, n.checked_mul(60)?),
+        "h" | "hr" | "hrs" | "hour" | "hours" => span(0, n.checked_mul(3600)?),
+        "d" | "day" | "days" => span(0, n.checked_mul(86400)?),
+        "w" | "wk" | "wks" | "week" | "weeks" => span(0, n.checked_mul(7 * 86400)?),
+        "mo" | "mth" | "mths" | "month" | "months" => span(count, 0),
+        "q" | "qtr" | "qtrs" | "quarter" | "quarters" => span(count.checked_mul(3)?, 0),
+        "y" | "yr" | "yrs" | "year" | "years" => span(count.checked_mul(12)?, 0),
+        _ => return None,
+    })
+}
+
+/// Parse the part of an ISO-8601 duration after the leading `P`.
+fn parse_iso_duration(rest: &str) -> Option<Period> {
+    let (date_part, time_part) = match rest.to_ascii_uppercase().split_once('T') {
+        Some((d, t)) => (d.to_string(), Some(t.to_string())),
+        None => (rest.to_ascii_uppercase(), None),
+    };
+    let mut months: u32 = 0;
+    let mut seconds: i64 = 0;
+    let mut any = false;
+    for (part, is_time) in [(Some(date_part), false), (time_part, true)] {
+        let Some(part) = part else { continue };
+        let mut number = String::new();
+        for c in part.chars() {
+            if c.is_ascii_digit() {
+                number.push(c);
+                continue;
+            }
+            let n: u32 = number.parse().ok()?;
+            number.clear();
+            any = true;
+            match (c, is_time) {
+                ('Y', false) => months = months.checked_add(n.checked_mul(12)?)?,
+                ('M', false) => months = months.checked_add(n)?,
+                ('W', false) => seconds = seconds.checked_add((n as i64).checked_mul(7 * 86400)?)?,
+                ('D', false) => seconds = seconds.checked_add((n as i64).checked_mul(86400)?)?,
+                ('H', true) => seconds = seconds.checked_add((n as i64).checked_mul(3600)?)?,
+                ('M', true) => seconds = seconds.checked_add((n as i64).checked_mul(60)?)?,
+                ('S', true) => seconds = seconds.checked_add(n as i64)?,
+                _ => return None,
+            }
+        }
+        if !number.is_empty() {
+            return None;
+        }
+    }
+    any.then_some(span(months, seconds))
+}
+
+impl Period {
+    /// The date `n` periods after `start`, or `None` if it is out of
+    /// range.
+    pub fn nth(&self, start: DateTime<Utc>, n: u32) -> Option<DateTime<Utc>> {
+        match *self {
+            Period::Span { months, seconds } => start
+                .checked_add_months(Months::new(months.checked_mul(n)?))?
+                .checked_add_signed(Duration::try_seconds(seconds.checked_mul(n as i64)?)?),
+            Period::Weekdays => {
+                let mut date = start;
+                for _ in 0..n {
+                    date += Duration::days(1);
+                    while matches!(date.weekday(), Weekday::Sat | Weekday::Sun) {
+                        date += Duration::days(1);
+                    }
+                }
+                Some(date)
+            }
+        }
+    }
+}
+
+/// The mask character for an instance in its current state.
+fn mask_char(task: &Task, now: DateTime<Utc>) -> char {
+    match task.get_status() {
+        Status::Completed => '+',
+        Status::Deleted => 'X',
+        _ if task.get_wait().is_some_and(|w| w > now) => 'W',
+        _ => '-',
+    }
+}
+
+fn instance_index(task: &Task) -> Option<usize> {
+    // The CLI has written imask both as an integer and as a float.
+    let value: f64 = task.get_value("imask")?.parse().ok()?;
+    (value >= 0.0 && value.fract() == 0.0).then_some(value as usize)
+}
+
+/// What generation should do for one template.
+#[derive(Debug)]
+struct Plan {
+    /// (imask, due) of each instance to create.
+    instances: Vec<(usize, DateTime<Utc>)>,
+    /// The template's mask after generation.
+    mask: String,
+    /// The series is over: `until` has passed and every instance up to
+    /// it exists.
+    finished: bool,
+}
+
+fn plan(
+    template: &Task,
+    instances: &[&Task],
+    horizon: DateTime<Utc>,
+    now: DateTime<Utc>,
+) -> Result<Plan, String> {
+    let recur = template.get_value("recur").ok_or("no 'recur' period")?;
+    let period = parse_period(recur)?;
+    let due = template
+        .get_value("due")
+        .and_then(stored_timestamp)
+        .ok_or("no valid 'due' date")?;
+    let until = match template.get_value("until") {
+        Some(v) => Some(stored_timestamp(v).ok_or("invalid 'until' date")?),
+        None => None,
+    };
+    let chained = template.get_value("rtype") == Some("chained");
+
+    // Refresh the mask from the instances that still exist.
+    let mut mask: Vec<char> = template.get_value("mask").unwrap_or("").chars().collect();
+    // No generation could have produced an instance beyond this; a larger
+    // imask (synced or imported) would grow the mask without bound.
+    let max_index = mask.len() + MAX_INSTANCES_PER_CALL;
+    let mut by_index: HashMap<usize, &Task> = HashMap::new();
+    for instance in instances {
+        if let Some(i) = instance_index(instance) {
+            if i > max_index {
+                warn!(
+                    "Ignoring instance {} of recurring task {}: imask {} is beyond its mask",
+                    instance.get_uuid(),
+                    template.get_uuid(),
+                    i
+                );
+                continue;
+            }
+            if mask.len() <= i {
+                mask.resize(i + 1, '-');
+            }
+            mask[i] = mask_char(instance, now);
+            by_index.insert(i, instance);
+        }
+    }
+
+    let limit = match until {
+        Some(until) => horizon.min(until),
+        None => horizon,
+    };
+    let mut planned = Vec::new();
+    if chained {
+        let waiting_on_instance = mask.iter().any(|c| matches!(c, '-' | 'W'));
+        if !waiting_on_instance {
+            let next_due = match mask.len().checked_sub(1) {
+                None => Some(due),
+                // Chain from when the previous instance ended; if it is
+                // gone (purged), from when it was due.
+                Some(prev) => {
+                    let prev_end = by_index
+                        .get(&prev)
+                        .and_then(|t| t.get_value("end").and_then(stored_timestamp));
+                    let base = match prev_end {
+                        Some(end) => end,
+                        None => period.nth(due, prev as u32).ok_or("date overflow")?,
+                    };
+                    period.nth(base, 1)
+                }
+            };
+            if let Some(next_due) = next_due.filter(|d| *d <= limit) {
+                planned.push((mask.len(), next_due));
+            }
+        }
+    } else {
+        for i in mask.len()..mask.len() + MAX_INSTANCES_PER_CALL {
+            match period.nth(due, i as u32) {
+                Some(d) if d <= limit => planned.push((i, d)),
+                _ => break,
+            }
+        }
+        if planned.len() == MAX_INSTANCES_PER_CALL {
+            warn!(
+                "Recurring task {} has more than {} instances due; generating the first {}",
+                template.get_uuid(),
+                MAX_INSTANCES_PER_CALL,
+                MAX_INSTANCES_PER_CALL
+            );
+        }
+    }
+    // A new instance is waiting if its wait, kept at the template's
+    // offset from due, is still ahead.
+    let wait_offset = template.get_wait().map(|wait| due - wait);
+    mask.extend(planned.iter().map(|(_, d)| match wait_offset {
+        Some(offset) if *d - offset > now => 'W',
+        _ => '-',
+    }));
+
+    let finished = until.is_some_and(|until| until < now && until <= horizon)
+        && planned.len() < MAX_INSTANCES_PER_CALL;
+    Ok(Plan { instances: planned, mask: mask.into_iter().collect(), finished })
+}
+
+/// The property values of instance `imask` of `template`, due at `due`.
+fn instance_values(
+    template: &Task,
+    imask: usize,
+    due: DateTime<Utc>,
+    now: DateTime<Utc>,
+) -> Vec<(String, String)> {
+    let taskmap = raw_taskmap(template);
+    let mut values: Vec<(String, String)> = taskmap
+        .iter()
+        .filter(|(k, _)| !NOT_INHERITED.contains(&k.as_str()))
+        .map(|(k, v)| (k.clone(), v.clone()))
+        .collect();
+    // wait and scheduled keep their offset from due.
+    let template_due = template.get_value("due").and_then(stored_timestamp);
+    for key in ["wait", "scheduled"] {
+        let offset_date = template.get_value(key).and_then(stored_timestamp);
+        if let (Some(template_due), Some(date)) = (template_due, offset_date) {
+            let shifted = due - (template_due - date);
+            values.push((key.to_string(), shifted.timestamp().to_string()));
+        }
+    }
+    values.push(("parent".to_string(), template.get_uuid().to_string()));
+    values.push(("imask".to_string(), imask.to_string()));
+    values.push(("due".to_string(), due.timestamp().to_string()));
+    values.push(("entry".to_string(), now.timestamp().to_string()));
+    values.push(("modified".to_string(), now.timestamp().to_string()));
+    values
+}
+
+/// Generate every instance of every recurring template that falls due
+/// by `horizon`, recording the operations in `ops`. Returns the UUIDs of
+/// the new instances. Templates whose recurrence cannot be understood
+/// are skipped with a warning rather than failing the whole run.
+pub fn generate(
+    replica: &mut Replica,
+    horizon: DateTime<Utc>,
+    now: DateTime<Utc>,
+    ops: &mut Operations,
+) -> Result<Vec<Uuid>, String> {
+    let tasks = replica
+        .all_tasks()
+        .map_err(|e| format!("Failed to get all tasks: {}", e))?;
+    let mut instances_of: HashMap<Uuid, Vec<&Task>> = HashMap::new();
+    for task in tasks.values() {
+        if let Some(parent) = task.get_value("parent").and_then(|p| Uuid::parse_str(p).ok()) {
+            instances_of.entry(parent).or_default().push(task);
+        }
+    }
+    let mut templates: Vec<&Task> = tasks
+        .values()
+        .filter(|t| t.get_status() == Status::Recurring)
+        .collect();
+    templates.sort_by_key(|t| t.get_uuid());
+
+    let mut created = Vec::new();
+    for template in templates {
+        let template_uuid = template.get_uuid();
+        let instances = instances_of.get(&template_uuid).map(Vec::as_slice).unwrap_or(&[]);
+        let plan = match plan(template, instances, horizon, now) {
+            Ok(plan) => plan,
+            Err(e) => {
+                warn!("Skipping recurring task {}: {}", template_uuid, e);
+                continue;
+            }
+        };
+
+        for (imask, due) in &plan.instances {
+            let uuid = Uuid::new_v4();
+            let mut instance = replica
+                .create_task(uuid, ops)
+                .map_err(|e| format!("Failed to create recurrence instance: {}", e))?;
+            instance
+                .set_status(Status::Pending, ops)
+                .map_err(|e| format!("Failed to set instance status: {}", e))?;
+            for (key, value) in instance_values(template, *imask, *due, now) {
+                instance
+                    .set_value(key, Some(value), ops)
+                    .map_err(|e| format!("Failed to set instance property: {}", e))?;
+            }
+            created.push(uuid);
+        }
+
+        if template.get_value("mask").unwrap_or("") != plan.mask || plan.finished {
+            let mut template = replica
+                .get_task(template_uuid)
+                .map_err(|e| format!("Failed to get task: {}", e))?
+                .ok_or_else(|| format!("Task not found: {}", template_uuid))?;
+            template
+                .set_value("mask", Some(plan.mask), ops)
+                .map_err(|e| format!("Failed to update recurrence mask: {}", e))?;
+            if plan.finished {
+                template
+                    .set_status(Status::Deleted, ops)
+                    .map_err(|e| format!("Failed to end recurrence: {}", e))?;
+            }
+        }
+    }
+    Ok(created)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+    use taskchampion::StorageConfig;
+
+    #[test]
+    fn test_parse_period() {
+        assert_eq!(parse_period("daily").unwrap(), span(0, 86400));
+        assert_eq!(parse_period("Weekly").unwrap(), span(0, 7 * 86400));
+        assert_eq!(parse_period("weekdays").unwrap(), Period::Weekdays);
+        assert_eq!(parse_period("P3D").unwrap(), span(0, 3 * 86400));
+        assert_eq!(parse_period("P1Y2M").unwrap(), span(14, 0));
+        assert_eq!(parse_period("PT12H").unwrap(), span(0, 12 * 3600));
+        assert_eq!(parse_period("3d").unwrap(), span(0, 3 * 86400));
+        assert_eq!(parse_period("2 weeks").unwrap(), span(0, 14 * 86400));
+        assert_eq!(parse_period("6mo").unwrap(), span(6, 0));
+        assert_eq!(parse_period("quarterly").unwrap(), span(3, 0));
+        for bad in [
+            "", "0d", "P", "P3X", "fortnightly", "3 parsecs", "400000000y", "2000000000q", "P400000000Y",
+            "P4294967295M1Y", "P300000000Y4000000000M", "P4294967295W4294967295W4294967295W4294967295W",
+        ] {
+            assert!(parse_period(bad).is_err(), "{:?} should not parse", bad);
+        }
+        // Enough weeks to overflow the seconds themselves.
+        assert!(parse_period(&format!("P{}", "4294967295W".repeat(4000))).is_err());
+
+        // Valid, but a few periods on is past any representable date.
+        let huge = parse_period("4294967295w").unwrap();
+        let now = Utc.with_ymd_and_hms(2026, 10, 16, 9, 0, 0).unwrap();
+        assert_eq!(huge.nth(now, 4), None);
+
+        let jan31 = Utc.with_ymd_and_hms(2026, 1, 31, 9, 0, 0).unwrap();
+        let monthly = parse_period("monthly").unwrap();
+        assert_eq!(monthly.nth(jan31, 1), Some(Utc.with_ymd_and_hms(2026, 2, 28, 9, 0, 0).unwrap()));
+        assert_eq!(monthly.nth(jan31, 2), Some(Utc.with_ymd_and_hms(2026, 3, 31, 9, 0, 0).unwrap()));
+        // 2026-10-16 is a Friday.
+        let friday = Utc.with_ymd_and_hms(2026, 10, 16, 9, 0, 0).unwrap();
+        assert_eq!(Period::Weekdays.nth(friday, 1), Some(Utc.with_ymd_and_hms(2026, 10, 19, 9, 0, 0).unwrap()));
+    }
+
+    fn instances(replica: &mut Replica, parent: Uuid) -> Vec<Task> {
+        let mut found: Vec<Task> = replica
+            .all_tasks()
+            .unwrap()
+            .into_values()
+            .filter(|t| t.get_value("parent") == Some(parent.to_string().as_str()))
+            .collect();
+        found.sort_by_key(instance_index);
+        found
+    }
+
+    #[test]
+    fn test_generate_periodic_instances() {
+        let mut replica = Replica::new(StorageConfig::InMemory.into_storage().unwrap());
+        let start = Utc.with_ymd_and_hms(2026, 10, 1, 9, 0, 0).unwrap();
+        let template_uuid = Uuid::new_v4();
+        let mut ops = Operations::new();
+        let mut template = replica.create_task(template_uuid, &mut ops).unwrap();
+        template.set_description("Water plants".to_string(), &mut ops).unwrap();
+        template.set_status(Status::Recurring, &mut ops).unwrap();
+        template.set_value("recur", Some("weekly".to_string()), &mut ops).unwrap();
+        template.set_value("due", Some(start.timestamp().to_string()), &mut ops).unwrap();
+        let wait = start - Duration::days(1);
+        template.set_value("wait", Some(wait.timestamp().to_string()), &mut ops).unwrap();
+        template.set_value("project", Some("home".to_string()), &mut ops).unwrap();
+        replica.commit_operations(ops).unwrap();
+
+        let now = start;
+        let horizon = start + Duration::days(14);
+        let mut ops = Operations::new();
+        let created = generate(&mut replica, horizon, now, &mut ops).unwrap();
+        replica.commit_operations(ops).unwrap();
+        assert_eq!(created.len(), 3);
+
+        let series = instances(&mut replica, template_uuid);
+        assert_eq!(series.len(), 3);
+        for (i, instance) in series.iter().enumerate() {
+            let due = start + Duration::days(7 * i as i64);
+            assert_eq!(instance.get_status(), Status::Pending);
+            assert_eq!(instance.get_description(), "Water plants");
+            assert_eq!(instance.get_value("project"), Some("home"));
+            assert_eq!(instance.get_value("recur"), Some("weekly"));
+            assert_eq!(instance.get_value("imask"), Some(i.to_string().as_str()));
+            assert_eq!(instance.get_due(), Some(due));
+            assert_eq!(instance.get_wait(), Some(due - Duration::days(1)));
+        }
+        // Instances whose wait is still ahead are masked as waiting.
+        let template = replica.get_task(template_uuid).unwrap().unwrap();
+        assert_eq!(template.get_value("mask"), Some("-WW"));
+
+        // Completing an instance is reflected in the mask; nothing is
+        // generated twice, and a later horizon extends the series.
+        let mut ops = Operations::new();
+        let mut first = replica.get_task(series[0].get_uuid()).unwrap().unwrap();
+        first.done(&mut ops).unwrap();
+        replica.commit_operations(ops).unwrap();
+        let mut ops = Operations::new();
+        let created = generate(&mut replica, horizon + Duration::days(7), now, &mut ops).unwrap();
+        replica.commit_operations(ops).unwrap();
+        assert_eq!(created.len(), 1);
+        let template = replica.get_task(template_uuid).unwrap().unwrap();
+        assert_eq!(template.get_value("mask"), Some("+WWW"));
+
+        // An instance claiming an absurd position is ignored rather than
+        // stretching the mask to match.
+        let mut ops = Operations::new();
+        let mut rogue = replica.create_task(Uuid::new_v4(), &mut ops).unwrap();
+        rogue.set_status(Status::Pending, &mut ops).unwrap();
+        rogue.set_value("parent", Some(template_uuid.to_string()), &mut ops).unwrap();
+        rogue.set_value("imask", Some("1e15".to_string()), &mut ops).unwrap();
+        replica.commit_operations(ops).unwrap();
+        let mut ops = Operations::new();
+        assert!(generate(&mut replica, horizon + Duration::days(7), now, &mut ops).unwrap().is_empty());
+        replica.commit_operations(ops).unwrap();
+        let template = replica.get_task(template_uuid).unwrap().unwrap();
+        assert_eq!(template.get_value("mask"), Some("+WWW"));
+    }
+
+    #[test]
+    fn test_generate_chained_and_until() {
+        let mut replica = Replica::new(StorageConfig::InMemory.into_storage().unwrap());
+        let start = Utc.with_ymd_and_hms(2026, 10, 1, 9, 0, 0).unwrap();
+        let template_uuid = Uuid::new_v4();
+        let mut ops = Operations::new();
+        let mut template = replica.create_task(template_uuid, &mut ops).unwrap();
+        template.set_status(Status::Recurring, &mut ops).unwrap();
+        template.set_value("recur", Some("P3D".to_string()), &mut ops).unwrap();
+        template.set_value("rtype", Some("chained".to_string()), &mut ops).unwrap();
+        template.set_value("due", Some(start.timestamp().to_string()), &mut ops).unwrap();
+        let until = start + Duration::days(5);
+        template.set_value("until", Some(until.timestamp().to_string()), &mut ops).unwrap();
+        replica.commit_operations(ops).unwrap();
+
+        let far = start + Duration::days(365);
+        let mut ops = Operations::new();
+        assert_eq!(generate(&mut replica, far, start, &mut ops).unwrap().len(), 1);
+        replica.commit_operations(ops).unwrap();
+        // Nothing more while the first instance is pending.
+        let mut ops = Operations::new();
+        assert!(generate(&mut replica, far, start, &mut ops).unwrap().is_empty());
+        replica.commit_operations(ops).unwrap();
+
+        // Finished a day late: the next one is due three days after that.
+        let finished_at = start + Duration::days(1);
+        let mut ops = Operations::new();
+        let first = instances(&mut replica, template_uuid)[0].get_uuid();
+        let mut first = replica.get_task(first).unwrap().unwrap();
+        first.done(&mut ops).unwrap();
+        first.set_value("end", Some(finished_at.timestamp().to_string()), &mut ops).unwrap();
+        replica.commit_operations(ops).unwrap();
+        let mut ops = Operations::new();
+        assert_eq!(generate(&mut replica, far, start, &mut ops).unwrap().len(), 1);
+        replica.commit_operations(ops).unwrap();
+        let series = instances(&mut replica, template_uuid);
+        assert_eq!(series[1].get_due(), Some(finished_at + Duration::days(3)));
+
+        // Once until has passed the series ends and the template is
+        // retired; the next step (day 7) would fall after until.
+        let mut ops = Operations::new();
+        let mut second = replica.get_task(series[1].get_uuid()).unwrap().unwrap();
+        second.done(&mut ops).unwrap();
+        let finished_at = start + Duration::days(3);
+        second.set_value("end", Some(finished_at.timestamp().to_string()), &mut ops).unwrap();
+        replica.commit_operations(ops).unwrap();
+        let mut ops = Operations::new();
+        let later = start + Duration::days(30);
+        assert!(generate(&mut replica, far, later, &mut ops).unwrap().is_empty());
+        replica.commit_operations(ops).unwrap();
+        let template = replica.get_task(template_uuid).unwrap().unwrap();
+        assert_eq!(template.get_status(), Status::Deleted);
+        assert_eq!(template.get_value("mask"), Some("++"));
+    }
+}
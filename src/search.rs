@@ -0,0 +1,225 @@
+//! In-memory full-text index over task text.
+//!
+//! Each task contributes tokens from its description, annotations, user
+//! tags, project and non-numeric UDA values. Tokens are folded before
+//! indexing and querying — Unicode NFD decomposition with combining marks
+//! stripped, then lower-cased — so `Café`, `cafe` and `CAFÉ` all match.
+//!
+//! A query matches a task when every query token is a prefix of (or equal
+//! to) some token of the task. Results are ranked by the sum, over query
+//! tokens, of the best-scoring field the token hit: description matches
+//! outrank tag and project matches, which outrank annotation and UDA
+//! matches, and an exact token match scores twice a prefix match.
+//!
+//! The index is maintained incrementally: [`SearchIndex::update`]
+//! re-indexes a single task after it changes.
+
+use std::collections::{BTreeMap, HashMap};
+use std::ops::Bound;
+use taskchampion::Task;
+use unicode_normalization::char::is_combining_mark;
+use unicode_normalization::UnicodeNormalization;
+use uuid::Uuid;
+
+const DESCRIPTION_WEIGHT: f32 = 4.0;
+const TAG_WEIGHT: f32 = 3.0;
+const PROJECT_WEIGHT: f32 = 3.0;
+const ANNOTATION_WEIGHT: f32 = 1.5;
+const UDA_WEIGHT: f32 = 1.0;
+
+/// Weight multiplier for a query token that is a strict prefix of the
+/// indexed token.
+const PREFIX_FACTOR: f32 = 0.5;
+
+/// Fold and split text into search tokens: maximal runs of alphanumeric
+/// characters, with diacritics removed and letters lower-cased.
+pub fn tokenize(text: &str) -> Vec<String> {
+    let folded: String = text
+        .nfd()
+        .filter(|c| !is_combining_mark(*c))
+        .flat_map(char::to_lowercase)
+        .collect();
+    folded
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|t| !t.is_empty())
+        .map(str::to_string)
+        .collect()
+}
+
+/// The weighted tokens of one task: token -> best field weight.
+fn task_tokens(task: &Task) -> HashMap<String, f32> {
+    let mut tokens: HashMap<String, f32> = HashMap::new();
+    let mut add = |text: &str, weight: f32| {
+        for token in tokenize(text) {
+            let entry = tokens.entry(token).or_insert(0.0);
+            *entry = entry.max(weight);
+        }
+    };
+
+    add(task.get_description(), DESCRIPTION_WEIGHT);
+    for tag in task.get_tags().filter(|t| t.is_user()) {
+        add(tag.as_ref(), TAG_WEIGHT);
+    }
+    if let Some(project) = task.get_value("project") {
+        add(project, PROJECT_WEIGHT);
+    }
+    for annotation in task.get_annotations() {
+        add(&annotation.description, ANNOTATION_WEIGHT);
+    }
+    for (key, value) in task.get_user_defined_attributes() {
+        // Numeric UDAs (estimates, counters, dates) are not text.
+        if key != "project" && value.parse::<f64>().is_err() {
+            add(value, UDA_WEIGHT);
+        }
+    }
+    tokens
+}
+
+#[derive(Debug, Default)]
+pub struct SearchIndex {
+    /// token -> (task -> weight). Ordered so prefix queries are a range
+    /// scan.
+    postings: BTreeMap<String, HashMap<Uuid, f32>>,
+    /// task -> its indexed tokens, so an update can remove stale postings.
+    tokens_by_task: HashMap<Uuid, Vec<String>>,
+}
+
+impl SearchIndex {
+    /// Build an index over the given tasks.
+    pub fn build<'a>(tasks: impl IntoIterator<Item = &'a Task>) -> SearchIndex {
+        let mut index = SearchIndex::default();
+        for task in tasks {
+            index.update(task.get_uuid(), Some(task));
+        }
+        index
+    }
+
+    /// Number of indexed tasks.
+    pub fn len(&self) -> usize {
+        self.tokens_by_task.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.tokens_by_task.is_empty()
+    }
+
+    /// Re-index one task. `None` removes it (the task no longer exists).
+    pub fn update(&mut self, uuid: Uuid, task: Option<&Task>) {
+        if let Some(old_tokens) = self.tokens_by_task.remove(&uuid) {
+            for token in old_tokens {
+                if let Some(posting) = self.postings.get_mut(&token) {
+                    posting.remove(&uuid);
+                    if posting.is_empty() {
+                        self.postings.remove(&token);
+                    }
+                }
+            }
+        }
+        let Some(task) = task else { return };
+        let tokens = task_tokens(task);
+        if tokens.is_empty() {
+            return;
+        }
+        let mut names = Vec::with_capacity(tokens.len());
+        for (token, weight) in tokens {
+            self.postings.entry(token.clone()).or_default().insert(uuid, weight);
+            names.push(token);
+        }
+        self.tokens_by_task.insert(uuid, names);
+    }
+
+    /// Score every task matching all tokens of `query`, best first. Ties
+    /// are broken by UUID so results are deterministic. An empty query
+    /// matches nothing.
+    pub fn search(&self, query: &str) -> Vec<(Uuid, f32)> {
+        let query_tokens = tokenize(query);
+        if query_tokens.is_empty() {
+            return Vec::new();
+        }
+
+        let mut totals: Option<HashMap<Uuid, f32>> = None;
+        for q in &query_tokens {
+            // Best score this query token achieves on each task.
+            let mut best: HashMap<Uuid, f32> = HashMap::new();
+            let range = self
+                .postings
+                .range::<str, _>((Bound::Included(q.as_str()), Bound::Unbounded))
+                .take_while(|(token, _)| token.starts_with(q.as_str()));
+            for (token, posting) in range {
+                let factor = if token == q { 1.0 } else { PREFIX_FACTOR };
+                for (uuid, weight) in posting {
+                    let entry = best.entry(*uuid).or_insert(0.0);
+                    *entry = entry.max(weight * factor);
+                }
+            }
+            totals = Some(match totals {
+                None => best,
+                Some(prev) => prev
+                    .into_iter()
+                    .filter_map(|(uuid, score)| best.get(&uuid).map(|s| (uuid, score + s)))
+                    .collect(),
+            });
+        }
+
+        let mut results: Vec<(Uuid, f32)> = totals.unwrap_or_default().into_iter().collect();
+        results.sort_by(|a, b| b.1.total_cmp(&a.1).then(a.0.cmp(&b.0)));
+        results
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::Utc;
+    use taskchampion::{Annotation, Operations, Replica, StorageConfig, Tag};
+
+    #[test]
+    fn test_tokenize_folds_case_and_diacritics() {
+        assert_eq!(tokenize("Crème Brûlée, at 7pm!"), vec!["creme", "brulee", "at", "7pm"]);
+        assert_eq!(tokenize("  "), Vec::<String>::new());
+    }
+
+    #[test]
+    fn test_search_ranks_and_updates() {
+        let mut replica = Replica::new(StorageConfig::InMemory.into_storage().unwrap());
+        let desc_uuid = Uuid::new_v4();
+        let note_uuid = Uuid::new_v4();
+        let mut ops = Operations::new();
+        let mut task = replica.create_task(desc_uuid, &mut ops).unwrap();
+        task.set_description("Book café table".to_string(), &mut ops).unwrap();
+        task.add_tag(&Tag::try_from("social").unwrap(), &mut ops).unwrap();
+        let mut task = replica.create_task(note_uuid, &mut ops).unwrap();
+        task.set_description("Call Sam".to_string(), &mut ops).unwrap();
+        task.add_annotation(
+            Annotation { entry: Utc::now(), description: "ask about the cafe".to_string() },
+            &mut ops,
+        ).unwrap();
+        replica.commit_operations(ops).unwrap();
+
+        let all = replica.all_tasks().unwrap();
+        let mut index = SearchIndex::build(all.values());
+        assert_eq!(index.len(), 2);
+
+        // Description hit outranks annotation hit; prefix and folding apply.
+        let hits: Vec<Uuid> = index.search("CAF").into_iter().map(|(u, _)| u).collect();
+        assert_eq!(hits, vec![desc_uuid, note_uuid]);
+        // Every query token must match.
+        let hits: Vec<Uuid> = index.search("cafe soc").into_iter().map(|(u, _)| u).collect();
+        assert_eq!(hits, vec![desc_uuid]);
+        assert!(index.search("").is_empty());
+
+        // Incremental update replaces the task's postings.
+        let mut ops = Operations::new();
+        let mut task = replica.get_task(desc_uuid).unwrap().unwrap();
+        task.set_description("Book restaurant".to_string(), &mut ops).unwrap();
+        replica.commit_operations(ops).unwrap();
+        let task = replica.get_task(desc_uuid).unwrap().unwrap();
+        index.update(desc_uuid, Some(&task));
+        let hits: Vec<Uuid> = index.search("cafe").into_iter().map(|(u, _)| u).collect();
+        assert_eq!(hits, vec![note_uuid]);
+
+        index.update(note_uuid, None);
+        assert!(index.search("cafe").is_empty());
+        assert_eq!(index.len(), 1);
+    }
+}
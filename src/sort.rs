@@ -0,0 +1,160 @@
+//! Multi-key task ordering for paged retrieval.
+//!
+//! A sort specification is a comma-separated list of keys in TaskWarrior's
+//! report syntax: `due+,priority-,description`. Each key names any
+//! attribute or UDA, optionally suffixed with `+` (ascending, the default)
+//! or `-` (descending). Values compare as in
+//! [`crate::filter::compare_values`]: finite numbers (which covers the
+//! epoch-second date attributes) numerically and first, anything else as
+//! strings; `priority` orders `L` < `M` < `H`. Tasks lacking a key's
+//! attribute sort after those that have it, in either direction. Ties on
+//! every key are broken by UUID so that paging is stable.
+
+use crate::filter::{attribute_value, compare_values};
+use std::cmp::Ordering;
+use taskchampion::Task;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SortKey {
+    pub field: String,
+    pub descending: bool,
+}
+
+/// Parse a sort specification. An empty or all-whitespace input yields no
+/// keys, which orders tasks by UUID alone.
+pub fn parse(spec: &str) -> Result<Vec<SortKey>, String> {
+    let mut keys = Vec::new();
+    if spec.trim().is_empty() {
+        return Ok(keys);
+    }
+    for part in spec.split(',') {
+        let part = part.trim();
+        let (field, descending) = if let Some(f) = part.strip_suffix('-') {
+            (f, true)
+        } else if let Some(f) = part.strip_suffix('+') {
+            (f, false)
+        } else {
+            (part, false)
+        };
+        if field.is_empty() || field.contains(char::is_whitespace) {
+            return Err(format!("Invalid sort key '{}'", part));
+        }
+        keys.push(SortKey { field: field.to_string(), descending });
+    }
+    Ok(keys)
+}
+
+fn priority_rank(value: &str) -> u8 {
+    match value {
+        "H" => 3,
+        "M" => 2,
+        "L" => 1,
+        _ => 0,
+    }
+}
+
+/// Order two tasks by the given keys, falling back to UUID order.
+pub fn compare(a: &Task, b: &Task, keys: &[SortKey]) -> Ordering {
+    for key in keys {
+        let va = attribute_value(a, &key.field).filter(|v| !v.is_empty());
+        let vb = attribute_value(b, &key.field).filter(|v| !v.is_empty());
+        let ordering = match (va, vb) {
+            (None, None) => Ordering::Equal,
+            // Missing values sort last regardless of direction.
+            (None, Some(_)) => return Ordering::Greater,
+            (Some(_), None) => return Ordering::Less,
+            (Some(x), Some(y)) => {
+                let ordering = if key.field == "priority" {
+                    priority_rank(&x).cmp(&priority_rank(&y))
+                } else {
+                    compare_values(&x, &y)
+                };
+                if key.descending {
+                    ordering.reverse()
+                } else {
+                    ordering
+                }
+            }
+        };
+        if ordering != Ordering::Equal {
+            return ordering;
+        }
+    }
+    a.get_uuid().cmp(&b.get_uuid())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use taskchampion::{Operations, Replica, StorageConfig};
+    use uuid::Uuid;
+
+    #[test]
+    fn test_parse_sort_spec() {
+        assert_eq!(parse("").unwrap(), vec![]);
+        assert_eq!(
+            parse("due+, priority-,description").unwrap(),
+            vec![
+                SortKey { field: "due".to_string(), descending: false },
+                SortKey { field: "priority".to_string(), descending: true },
+                SortKey { field: "description".to_string(), descending: false },
+            ]
+        );
+        assert!(parse("due,,priority").is_err());
+        assert!(parse("-").is_err());
+    }
+
+    #[test]
+    fn test_compare_multi_key() {
+        let mut replica = Replica::new(StorageConfig::InMemory.into_storage().unwrap());
+        let mut ops = Operations::new();
+        let rows = [("a", Some("H"), Some("300")), ("b", Some("L"), Some("100")), ("c", None, Some("100")), ("d", Some("H"), None)];
+        let mut uuids = Vec::new();
+        for (desc, priority, due) in rows {
+            let uuid = Uuid::new_v4();
+            let mut task = replica.create_task(uuid, &mut ops).unwrap();
+            task.set_description(desc.to_string(), &mut ops).unwrap();
+            task.set_value("priority", priority.map(str::to_string), &mut ops).unwrap();
+            task.set_value("due", due.map(str::to_string), &mut ops).unwrap();
+            uuids.push(uuid);
+        }
+        replica.commit_operations(ops).unwrap();
+        let all = replica.all_tasks().unwrap();
+        let mut tasks: Vec<&Task> = uuids.iter().map(|u| &all[u]).collect();
+
+        let keys = parse("due+,priority-").unwrap();
+        tasks.sort_by(|a, b| compare(a, b, &keys));
+        let order: Vec<&str> = tasks.iter().map(|t| t.get_description()).collect();
+        // due 100 (b: L, c: none -> last within tie), then 300, then no due.
+        assert_eq!(order, vec!["b", "c", "a", "d"]);
+
+        let keys = parse("priority-,description-").unwrap();
+        tasks.sort_by(|a, b| compare(a, b, &keys));
+        let order: Vec<&str> = tasks.iter().map(|t| t.get_description()).collect();
+        assert_eq!(order, vec!["d", "a", "b", "c"]);
+    }
+
+    #[test]
+    fn test_compare_mixed_uda_values_is_total() {
+        let mut replica = Replica::new(StorageConfig::InMemory.into_storage().unwrap());
+        let mut ops = Operations::new();
+        let mut uuids = Vec::new();
+        for size in ["1a", "NaN", "10", "9"] {
+            let uuid = Uuid::new_v4();
+            let mut task = replica.create_task(uuid, &mut ops).unwrap();
+            task.set_value("size", Some(size.to_string()), &mut ops).unwrap();
+            uuids.push(uuid);
+        }
+        replica.commit_operations(ops).unwrap();
+        let all = replica.all_tasks().unwrap();
+        let mut tasks: Vec<&Task> = uuids.iter().map(|u| &all[u]).collect();
+
+        let keys = parse("size").unwrap();
+        tasks.sort_by(|a, b| compare(a, b, &keys));
+        let order: Vec<&str> = tasks.iter().map(|t| t.get_value("size").unwrap()).collect();
+        // Numbers first, numerically; NaN is not one.
+        assert_eq!(order, vec!["9", "10", "1a", "NaN"]);
+        assert_eq!(compare_values("NaN", "1"), Ordering::Greater);
+        assert_eq!(compare_values("9", "1a"), Ordering::Less);
+    }
+}
@@ -0,0 +1,153 @@
+//! Storage statistics for a replica, for a settings screen.
+
+use crate::{journal, storage};
+use chrono::{DateTime, Utc};
+use rusqlite::Connection;
+use serde_json::{json, Map, Value};
+use std::collections::{BTreeMap, BTreeSet};
+use std::path::Path;
+use taskchampion::{Operation, Replica};
+
+/// Attributes TaskWarrior defines itself; every other plain key is a UDA.
+const CORE_ATTRIBUTES: &[&str] = &[
+    "description", "status", "entry", "modified", "end", "start", "due", "wait", "scheduled", "until",
+    "recur", "mask", "imask", "parent", "rtype", "project", "priority",
+];
+
+/// What nativeGetReplicaStats reports.
+#[derive(Debug, Default, PartialEq)]
+pub struct ReplicaStats {
+    /// Task count per stored status; a task without one counts as pending.
+    pub tasks_by_status: BTreeMap<String, usize>,
+    pub operations: usize,
+    pub unsynced_operations: usize,
+    /// Undo points that can still be undone to.
+    pub undo_depth: usize,
+    /// Occupied working-set slots.
+    pub working_set_size: usize,
+    /// Distinct UDA names set on any task.
+    pub udas: BTreeSet<String>,
+    /// Total size of the files in the data directory.
+    pub disk_bytes: u64,
+    /// Timestamp of the oldest unsynced change that carries one.
+    pub oldest_unsynced: Option<DateTime<Utc>>,
+}
+
+impl ReplicaStats {
+    /// The stats as a JSON document:
+    /// `{"tasks": {"pending": n, ...}, "totalTasks": n, "operations": n,
+    ///   "unsyncedOperations": n, "undoDepth": n, "workingSetSize": n,
+    ///   "udaCount": n, "udas": ["..."], "diskBytes": n,
+    ///   "oldestUnsynced": "<epoch seconds>" | null}`.
+    pub fn to_json(&self) -> Result<String, String> {
+        let tasks: Map<String, Value> = self.tasks_by_status.iter().map(|(k, v)| (k.clone(), json!(v))).collect();
+        let stats = json!({
+            "tasks": tasks,
+            "totalTasks": self.tasks_by_status.values().sum::<usize>(),
+            "operations": self.operations,
+            "unsyncedOperations": self.unsynced_operations,
+            "undoDepth": self.undo_depth,
+            "workingSetSize": self.working_set_size,
+            "udaCount": self.udas.len(),
+            "udas": self.udas,
+            "diskBytes": self.disk_bytes,
+            "oldestUnsynced": self.oldest_unsynced.map(|t| t.timestamp().to_string()),
+        });
+        serde_json::to_string(&stats).map_err(|e| format!("Failed to serialize replica stats to JSON: {}", e))
+    }
+}
+
+/// Total size of the files under `dir`.
+pub fn directory_size(dir: &Path) -> Result<u64, String> {
+    let mut total = 0;
+    let entries = std::fs::read_dir(dir).map_err(|e| format!("Failed to read {}: {}", dir.display(), e))?;
+    for entry in entries {
+        let entry = entry.map_err(|e| format!("Failed to read {}: {}", dir.display(), e))?;
+        let metadata = entry.metadata().map_err(|e| format!("Failed to read {}: {}", entry.path().display(), e))?;
+        total += if metadata.is_dir() { directory_size(&entry.path())? } else { metadata.len() };
+    }
+    Ok(total)
+}
+
+/// Gather the stats for `replica`, whose journal is `conn` and whose
+/// files are in `data_dir`.
+pub fn collect(replica: &mut Replica, conn: &Connection, data_dir: &Path) -> Result<ReplicaStats, String> {
+    let mut stats = ReplicaStats::default();
+    for status in ["pending", "completed", "deleted", "recurring"] {
+        stats.tasks_by_status.insert(status.to_string(), 0);
+    }
+    let tasks = replica
+        .all_task_data()
+        .map_err(|e| format!("Failed to get all tasks: {}", e))?;
+    for data in tasks.values() {
+        let status = data.get("status").unwrap_or("pending");
+        *stats.tasks_by_status.entry(status.to_string()).or_insert(0) += 1;
+        for key in data.properties() {
+            if !CORE_ATTRIBUTES.contains(&key.as_str())
+                && !key.starts_with("tag_")
+                && !key.starts_with("annotation_")
+                && !key.starts_with("dep_")
+            {
+                stats.udas.insert(key.clone());
+            }
+        }
+    }
+
+    stats.operations = storage::operation_count(conn)?;
+    let unsynced = storage::unsynced_operations(conn)?;
+    stats.unsynced_operations = unsynced.len();
+    stats.oldest_unsynced = unsynced
+        .iter()
+        .filter_map(|(_, op)| match op {
+            Operation::Update { timestamp, .. } => Some(*timestamp),
+            _ => None,
+        })
+        .min();
+    stats.undo_depth = journal::undo_points(conn)?.len();
+
+    let working_set = replica
+        .working_set()
+        .map_err(|e| format!("Failed to get working set: {}", e))?;
+    stats.working_set_size = working_set.iter().count();
+    stats.disk_bytes = directory_size(data_dir)?;
+    Ok(stats)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use taskchampion::{Operations, Status};
+    use tempfile::TempDir;
+    use uuid::Uuid;
+
+    #[test]
+    fn test_collect_stats() {
+        let dir = TempDir::new().unwrap();
+        let mut replica = storage::open_on_disk(dir.path(), true).unwrap();
+        let mut ops = Operations::new();
+        ops.push(Operation::UndoPoint);
+        for (description, status) in [("One", Status::Pending), ("Two", Status::Pending), ("Three", Status::Completed)] {
+            let mut task = replica.create_task(Uuid::new_v4(), &mut ops).unwrap();
+            task.set_description(description.to_string(), &mut ops).unwrap();
+            task.set_status(status, &mut ops).unwrap();
+            task.set_value("estimate", Some("1h".to_string()), &mut ops).unwrap();
+            task.set_value("project", Some("Home".to_string()), &mut ops).unwrap();
+        }
+        replica.commit_operations(ops).unwrap();
+        replica.rebuild_working_set(true).unwrap();
+
+        let conn = journal::open(dir.path()).unwrap();
+        let stats = collect(&mut replica, &conn, dir.path()).unwrap();
+        assert_eq!(stats.tasks_by_status["pending"], 2);
+        assert_eq!(stats.tasks_by_status["completed"], 1);
+        assert_eq!(stats.tasks_by_status["deleted"], 0);
+        assert_eq!(stats.working_set_size, 2);
+        assert_eq!(stats.undo_depth, 1);
+        assert_eq!(stats.operations, stats.unsynced_operations);
+        assert!(stats.operations > 10);
+        assert!(stats.oldest_unsynced.is_some());
+        assert_eq!(stats.udas.iter().collect::<Vec<_>>(), ["estimate"]);
+        assert!(stats.disk_bytes > 0);
+        assert!(stats.to_json().unwrap().contains("\"totalTasks\":3"));
+    }
+}
@@ -0,0 +1,286 @@
+//! TaskChampion's on-disk storage in a data directory.
+//!
+//! Most of the binding goes through the `Replica` API. What that API
+//! does not expose (the full operation journal, raw task rows for
+//! integrity checks, whole-database snapshots) is read from
+//! `taskchampion.sqlite3` directly, and all such access lives here. That
+//! schema is TaskChampion's own and may change under a dependency
+//! upgrade, so every connection is opened through [`open`] or
+//! [`open_read_only`], which check the schema version and the columns
+//! this module reads, and refuse a database that differs rather than
+//! misread it.
+//!
+//! The binding's own tables are not kept in this database; see
+//! [`crate::journal`].
+
+use rusqlite::{params, Connection, OpenFlags, OptionalExtension};
+use std::path::Path;
+use std::time::Duration;
+use taskchampion::storage::AccessMode;
+use taskchampion::{Operation, Replica, StorageConfig};
+
+/// Name of TaskChampion's database file within a data directory.
+pub const DB_FILE: &str = "taskchampion.sqlite3";
+
+/// The schema major version this module was written against. Minor
+/// versions only add to a schema, so any of them will do.
+const SCHEMA_MAJOR_VERSION: u32 = 0;
+
+/// The tables, and columns, read or written here.
+const SCHEMA: &[(&str, &[&str])] = &[
+    ("operations", &["id", "data", "synced"]),
+    ("sync_meta", &["key", "value"]),
+    ("tasks", &["uuid", "data"]),
+    ("working_set", &["id", "uuid"]),
+];
+
+/// A row of two text columns as stored; either may be NULL in a
+/// damaged database.
+pub type TextRow = (Option<String>, Option<String>);
+
+/// How long a statement waits on a lock held by another connection (a
+/// Replica, possibly on another handle) before failing.
+const BUSY_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Open a Replica over the database in `data_dir`, creating the
+/// directory and an empty database if `create_if_missing` is set.
+pub fn open_on_disk(data_dir: &Path, create_if_missing: bool) -> Result<Replica, String> {
+    let storage = StorageConfig::OnDisk {
+        taskdb_dir: data_dir.to_path_buf(),
+        create_if_missing,
+        access_mode: AccessMode::ReadWrite,
+    }
+    .into_storage()
+    .map_err(|e| format!("Failed to open storage in {}: {}", data_dir.display(), e))?;
+    Ok(Replica::new(storage))
+}
+
+/// Open the database in `data_dir` for reading and writing. It must
+/// already have been created by TaskChampion.
+pub fn open(data_dir: &Path) -> Result<Connection, String> {
+    let path = data_dir.join(DB_FILE);
+    if !path.is_file() {
+        return Err(format!("No database in {}", data_dir.display()));
+    }
+    // CREATE lets the binding attach its own database, which may not
+    // exist yet; this one was checked above.
+    let flags = OpenFlags::SQLITE_OPEN_READ_WRITE | OpenFlags::SQLITE_OPEN_CREATE | OpenFlags::SQLITE_OPEN_NO_MUTEX;
+    let conn = Connection::open_with_flags(&path, flags)
+        .map_err(|e| format!("Failed to open {}: {}", path.display(), e))?;
+    configure(conn, &path)
+}
+
+/// Open the TaskChampion database file at `path` read-only.
+pub fn open_read_only(path: &Path) -> Result<Connection, String> {
+    let conn = Connection::open_with_flags(path, OpenFlags::SQLITE_OPEN_READ_ONLY | OpenFlags::SQLITE_OPEN_NO_MUTEX)
+        .map_err(|e| format!("Failed to open {}: {}", path.display(), e))?;
+    configure(conn, path)
+}
+
+fn configure(conn: Connection, path: &Path) -> Result<Connection, String> {
+    conn.busy_timeout(BUSY_TIMEOUT)
+        .map_err(|e| format!("Failed to configure {}: {}", path.display(), e))?;
+    check_schema(&conn).map_err(|e| format!("Unsupported database {}: {}", path.display(), e))?;
+    Ok(conn)
+}
+
+/// Check that `conn` holds a TaskChampion database this module can read.
+fn check_schema(conn: &Connection) -> Result<(), String> {
+    let has_version_table = has_column(conn, "version", "major")?;
+    if has_version_table {
+        let major: Option<u32> = conn
+            .query_row("SELECT major FROM version", [], |row| row.get(0))
+            .optional()
+            .map_err(|e| format!("failed to read schema version: {}", e))?;
+        if let Some(major) = major.filter(|m| *m != SCHEMA_MAJOR_VERSION) {
+            return Err(format!("schema version {} is not supported", major));
+        }
+    }
+    for (table, columns) in SCHEMA {
+        for column in *columns {
+            if !has_column(conn, table, column)? {
+                return Err(format!("no {}.{} column", table, column));
+            }
+        }
+    }
+    Ok(())
+}
+
+fn has_column(conn: &Connection, table: &str, column: &str) -> Result<bool, String> {
+    conn.query_row(
+        "SELECT count(*) > 0 FROM pragma_table_xinfo(?1) WHERE name = ?2",
+        [table, column],
+        |row| row.get(0),
+    )
+    .map_err(|e| format!("failed to read schema: {}", e))
+}
+
+/// How TaskChampion stores an undo point in the journal.
+fn undo_point_data() -> Result<String, String> {
+    serde_json::to_string(&Operation::UndoPoint).map_err(|e| format!("Failed to encode undo point: {}", e))
+}
+
+/// Every unsynced operation with its id, oldest first.
+pub fn unsynced_operations(conn: &Connection) -> Result<Vec<(i64, Operation)>, String> {
+    let mut stmt = conn
+        .prepare("SELECT id, data FROM operations WHERE NOT synced ORDER BY id")
+        .map_err(|e| format!("Failed to read operations: {}", e))?;
+    let rows = stmt
+        .query_map([], |row| Ok((row.get::<_, i64>(0)?, row.get::<_, String>(1)?)))
+        .map_err(|e| format!("Failed to read operations: {}", e))?;
+    let mut ops = Vec::new();
+    for row in rows {
+        let (id, data) = row.map_err(|e| format!("Failed to read operations: {}", e))?;
+        let op: Operation = serde_json::from_str(&data)
+            .map_err(|e| format!("Failed to decode operation {}: {}", id, e))?;
+        ops.push((id, op));
+    }
+    Ok(ops)
+}
+
+/// The ids of every unsynced operation, oldest first.
+pub fn unsynced_operation_ids(conn: &Connection) -> Result<Vec<i64>, String> {
+    let mut stmt = conn
+        .prepare("SELECT id FROM operations WHERE NOT synced ORDER BY id")
+        .map_err(|e| format!("Failed to read operations: {}", e))?;
+    let rows = stmt
+        .query_map([], |row| row.get(0))
+        .map_err(|e| format!("Failed to read operations: {}", e))?;
+    rows.collect::<Result<_, _>>()
+        .map_err(|e| format!("Failed to read operations: {}", e))
+}
+
+/// Number of operations in the journal, synced or not.
+pub fn operation_count(conn: &Connection) -> Result<usize, String> {
+    conn.query_row("SELECT count(*) FROM operations", [], |row| row.get::<_, i64>(0))
+        .map(|n| n as usize)
+        .map_err(|e| format!("Failed to read operations: {}", e))
+}
+
+/// Id of the newest operation in the journal, or 0 if it is empty.
+pub fn last_operation_id(conn: &Connection) -> Result<i64, String> {
+    conn.query_row("SELECT coalesce(max(id), 0) FROM operations", [], |row| row.get(0))
+        .map_err(|e| format!("Failed to read operations: {}", e))
+}
+
+/// Whether the newest unsynced operation is an undo point, i.e. another
+/// undo point now would mark an empty group.
+pub fn ends_with_undo_point(conn: &Connection) -> Result<bool, String> {
+    let last: Option<String> = conn
+        .query_row(
+            "SELECT data FROM operations WHERE NOT synced ORDER BY id DESC LIMIT 1",
+            [],
+            |row| row.get(0),
+        )
+        .optional()
+        .map_err(|e| format!("Failed to read operations: {}", e))?;
+    Ok(last == Some(undo_point_data()?))
+}
+
+/// Id of the first undo point recorded after operation `after_id`.
+pub fn first_undo_point_after(conn: &Connection, after_id: i64) -> Result<Option<i64>, String> {
+    conn.query_row(
+        "SELECT min(id) FROM operations WHERE id > ?1 AND data = ?2",
+        params![after_id, undo_point_data()?],
+        |row| row.get(0),
+    )
+    .map_err(|e| format!("Failed to read operations: {}", e))
+}
+
+/// Delete every synced operation, returning how many there were.
+pub fn delete_synced_operations(conn: &Connection) -> Result<usize, String> {
+    conn.execute("DELETE FROM operations WHERE synced", [])
+        .map_err(|e| format!("Failed to trim synced operations: {}", e))
+}
+
+/// Every task row, as stored, ordered by UUID.
+pub fn task_rows(conn: &Connection) -> Result<Vec<TextRow>, String> {
+    let mut stmt = conn
+        .prepare("SELECT uuid, data FROM tasks ORDER BY uuid")
+        .map_err(|e| format!("Failed to read tasks: {}", e))?;
+    let rows = stmt
+        .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))
+        .map_err(|e| format!("Failed to read tasks: {}", e))?;
+    rows.collect::<Result<_, _>>()
+        .map_err(|e| format!("Failed to read tasks: {}", e))
+}
+
+/// The stored data of task `uuid`.
+pub fn task_data(conn: &Connection, uuid: &str) -> Result<String, String> {
+    conn.query_row("SELECT data FROM tasks WHERE uuid = ?1", [uuid], |row| row.get(0))
+        .map_err(|e| format!("Failed to read task {}: {}", uuid, e))
+}
+
+/// Replace the stored data of task `uuid`.
+pub fn set_task_data(conn: &Connection, uuid: &str, data: &str) -> Result<(), String> {
+    conn.execute("UPDATE tasks SET data = ?1 WHERE uuid = ?2", params![data, uuid])
+        .map_err(|e| format!("Failed to update task {}: {}", uuid, e))?;
+    Ok(())
+}
+
+/// Delete the task row keyed `uuid`, which may be NULL, and its
+/// working-set entries.
+pub fn delete_task(conn: &Connection, uuid: Option<&str>) -> Result<(), String> {
+    conn.execute("DELETE FROM tasks WHERE uuid IS ?1", params![uuid])
+        .map_err(|e| format!("Failed to delete task {}: {}", uuid.unwrap_or_default(), e))?;
+    conn.execute("DELETE FROM working_set WHERE uuid IS ?1", params![uuid])
+        .map_err(|e| format!("Failed to update working set: {}", e))?;
+    Ok(())
+}
+
+/// Every working-set entry, ordered by id.
+pub fn working_set_rows(conn: &Connection) -> Result<Vec<(i64, Option<String>)>, String> {
+    let mut stmt = conn
+        .prepare("SELECT id, uuid FROM working_set ORDER BY id")
+        .map_err(|e| format!("Failed to read working set: {}", e))?;
+    let rows = stmt
+        .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))
+        .map_err(|e| format!("Failed to read working set: {}", e))?;
+    rows.collect::<Result<_, _>>()
+        .map_err(|e| format!("Failed to read working set: {}", e))
+}
+
+/// Remove working-set entry `id`.
+pub fn delete_working_set_entry(conn: &Connection, id: i64) -> Result<(), String> {
+    conn.execute("DELETE FROM working_set WHERE id = ?1", [id])
+        .map_err(|e| format!("Failed to update working set: {}", e))?;
+    Ok(())
+}
+
+/// Every sync-state entry, ordered by key.
+pub fn sync_meta_rows(conn: &Connection) -> Result<Vec<TextRow>, String> {
+    let mut stmt = conn
+        .prepare("SELECT key, value FROM sync_meta ORDER BY key")
+        .map_err(|e| format!("Failed to read sync state: {}", e))?;
+    let rows = stmt
+        .query_map([], |row| Ok((row.get(0)?, row.get(1)?)))
+        .map_err(|e| format!("Failed to read sync state: {}", e))?;
+    rows.collect::<Result<_, _>>()
+        .map_err(|e| format!("Failed to read sync state: {}", e))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_open_checks_schema() {
+        let dir = TempDir::new().unwrap();
+        assert!(open(dir.path()).is_err());
+
+        let mut replica = open_on_disk(dir.path(), true).unwrap();
+        replica.commit_operations(vec![Operation::UndoPoint]).unwrap();
+        let conn = open(dir.path()).unwrap();
+        assert!(ends_with_undo_point(&conn).unwrap());
+        assert_eq!(first_undo_point_after(&conn, 0).unwrap(), unsynced_operation_ids(&conn).unwrap().first().copied());
+
+        // A schema this module was not written for is refused.
+        conn.execute("UPDATE version SET major = major + 1", []).unwrap();
+        assert!(open(dir.path()).unwrap_err().contains("schema version"));
+        assert!(open_read_only(&dir.path().join(DB_FILE)).is_err());
+        conn.execute("UPDATE version SET major = major - 1", []).unwrap();
+        conn.execute_batch("DROP INDEX operations_by_synced; ALTER TABLE operations DROP COLUMN synced").unwrap();
+        assert!(open(dir.path()).unwrap_err().contains("operations.synced"));
+    }
+}
@@ -0,0 +1,254 @@
+//! CSV and Markdown checklist renderings of a task list, for sharing
+//! with people who do not use the app.
+//!
+//! Both read each task's raw key/value map, as task_to_json does, and
+//! list tasks in entry-date order, then by UUID. Dates are written in a
+//! caller-chosen IANA timezone as `YYYY-MM-DD HH:MM`, a form
+//! spreadsheets recognise. CSV cells that would run as formulas are
+//! written as text.
+
+use crate::dates::stored_timestamp;
+use crate::filter::{Filter, DATE_ATTRIBUTES};
+use crate::jni_bindings::{exported_tasks, raw_taskmap};
+use chrono::{DateTime, Utc};
+use chrono_tz::Tz;
+use std::collections::HashMap;
+use taskchampion::{Replica, Task};
+use uuid::Uuid;
+
+/// Columns used when the caller does not choose any.
+pub const DEFAULT_COLUMNS: &[&str] = &["id", "description", "status", "project", "priority", "due", "tags"];
+
+/// Parse a timezone name (`Europe/London`, `UTC`); `None` means UTC.
+pub fn parse_timezone(name: Option<&str>) -> Result<Tz, String> {
+    match name.map(str::trim) {
+        None | Some("") => Ok(Tz::UTC),
+        Some(name) => name.parse::<Tz>().map_err(|_| format!("Unknown timezone '{}'", name)),
+    }
+}
+
+/// Check a column list: names must be non-empty and distinct.
+pub fn parse_columns(columns: Vec<String>) -> Result<Vec<String>, String> {
+    let mut parsed: Vec<String> = Vec::with_capacity(columns.len());
+    for column in columns {
+        let column = column.trim().to_string();
+        if column.is_empty() {
+            return Err("Column names must not be empty".to_string());
+        }
+        if parsed.contains(&column) {
+            return Err(format!("Column '{}' is listed twice", column));
+        }
+        parsed.push(column);
+    }
+    Ok(parsed)
+}
+
+fn format_date(date: DateTime<Utc>, tz: Tz) -> String {
+    date.with_timezone(&tz).format("%Y-%m-%d %H:%M").to_string()
+}
+
+/// The tasks matching `filter`, in export order, with their working-set
+/// ids.
+fn matching_tasks(replica: &mut Replica, filter: &Filter) -> Result<Vec<(Task, Option<usize>)>, String> {
+    let working_set = replica
+        .working_set()
+        .map_err(|e| format!("Failed to get working set: {}", e))?;
+    Ok(exported_tasks(replica, filter)?
+        .into_iter()
+        .map(|task| {
+            let id = working_set.by_uuid(task.get_uuid());
+            (task, id)
+        })
+        .collect())
+}
+
+/// The value of one column for a task: `uuid`, `id`, `tags` (space
+/// separated), `depends` (space-separated UUIDs), `annotations` (one per
+/// line, each prefixed with its date), or any stored attribute or UDA by
+/// name, with the built-in date attributes formatted in `tz`.
+fn cell(task: &Task, id: Option<usize>, column: &str, taskmap: &HashMap<String, String>, tz: Tz) -> String {
+    match column {
+        "uuid" => task.get_uuid().to_string(),
+        "id" => id.map(|id| id.to_string()).unwrap_or_default(),
+        "tags" => task.get_tags().filter(|t| t.is_user()).map(|t| t.to_string()).collect::<Vec<_>>().join(" "),
+        "depends" => {
+            let mut depends: Vec<Uuid> = task.get_dependencies().collect();
+            depends.sort();
+            depends.iter().map(Uuid::to_string).collect::<Vec<_>>().join(" ")
+        }
+        "annotations" => task
+            .get_annotations()
+            .map(|a| format!("{} {}", format_date(a.entry, tz), a.description))
+            .collect::<Vec<_>>()
+            .join("\n"),
+        "status" => taskmap.get("status").map_or("pending", String::as_str).to_string(),
+        key => match taskmap.get(key) {
+            Some(value) if DATE_ATTRIBUTES.contains(&key) => {
+                stored_timestamp(value).map_or_else(|| value.clone(), |date| format_date(date, tz))
+            }
+            Some(value) => value.clone(),
+            None => String::new(),
+        },
+    }
+}
+
+/// Characters that make a spreadsheet read a cell as a formula.
+const FORMULA_PREFIXES: &[char] = &['=', '+', '-', '@', '\t', '\r'];
+
+/// Quote a CSV field if it needs it (RFC 4180). A field a spreadsheet
+/// would evaluate as a formula is prefixed with `'`, so it shows as text.
+fn csv_field(value: &str) -> String {
+    let value = if value.starts_with(FORMULA_PREFIXES) { format!("'{}", value) } else { value.to_string() };
+    if value.contains([',', '"', '\r', '\n']) || value.starts_with(' ') || value.ends_with(' ') {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value
+    }
+}
+
+fn csv_row<'a>(out: &mut String, fields: impl Iterator<Item = &'a str>) {
+    let fields: Vec<String> = fields.map(csv_field).collect();
+    out.push_str(&fields.join(","));
+    out.push_str("\r\n");
+}
+
+/// Export the tasks matching `filter` as CSV: a header row of the column
+/// names, then one row per task. A task without a column's attribute has
+/// an empty cell.
+pub fn export_csv(replica: &mut Replica, filter: &Filter, columns: &[String], tz: Tz) -> Result<String, String> {
+    let mut out = String::new();
+    csv_row(&mut out, columns.iter().map(String::as_str));
+    for (task, id) in matching_tasks(replica, filter)? {
+        let taskmap = raw_taskmap(&task);
+        let cells: Vec<String> = columns.iter().map(|c| cell(&task, id, c, taskmap, tz)).collect();
+        csv_row(&mut out, cells.iter().map(String::as_str));
+    }
+    Ok(out)
+}
+
+/// Backslash-escape the characters Markdown would otherwise interpret
+/// inline.
+fn escape_markdown(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '\\' | '`' | '*' | '_' | '[' | ']' | '<' | '>' | '#' | '~' | '|' => {
+                out.push('\\');
+                out.push(c);
+            }
+            '\n' | '\r' => out.push(' '),
+            c => out.push(c),
+        }
+    }
+    out
+}
+
+/// Export the tasks matching `filter` as a Markdown checklist: one item
+/// per task, ticked once completed and struck through once deleted,
+/// followed by its project, due date and tags, with annotations as
+/// nested items.
+pub fn export_markdown(replica: &mut Replica, filter: &Filter, tz: Tz) -> Result<String, String> {
+    let mut out = String::new();
+    for (task, _) in matching_tasks(replica, filter)? {
+        let taskmap = raw_taskmap(&task);
+        let status = taskmap.get("status").map_or("pending", String::as_str);
+        let description = escape_markdown(task.get_description());
+        let (mark, description) = match status {
+            "completed" => ("x", description),
+            "deleted" => ("x", format!("~~{}~~", description)),
+            _ => (" ", description),
+        };
+        let mut line = format!("- [{}] {}", mark, description);
+        if let Some(project) = taskmap.get("project") {
+            line.push_str(&format!(" (project: {})", escape_markdown(project)));
+        }
+        if let Some(due) = taskmap.get("due").and_then(|d| stored_timestamp(d)) {
+            line.push_str(&format!(" (due: {})", format_date(due, tz)));
+        }
+        for tag in task.get_tags().filter(|t| t.is_user()) {
+            line.push_str(&format!(" `+{}`", tag));
+        }
+        out.push_str(&line);
+        out.push('\n');
+        for annotation in task.get_annotations() {
+            out.push_str(&format!(
+                "  - {} {}\n",
+                format_date(annotation.entry, tz),
+                escape_markdown(&annotation.description)
+            ));
+        }
+    }
+    Ok(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use taskchampion::{Annotation, Operations, Status, StorageConfig, Tag};
+
+    #[test]
+    fn test_csv_and_markdown_exports() {
+        let mut replica = Replica::new(StorageConfig::InMemory.into_storage().unwrap());
+        let mut ops = Operations::new();
+        let mut task = replica.create_task(Uuid::new_v4(), &mut ops).unwrap();
+        task.set_description("Quote \"budget\", then *review*".to_string(), &mut ops).unwrap();
+        task.set_status(Status::Pending, &mut ops).unwrap();
+        task.set_entry(DateTime::from_timestamp(1_760_000_000, 0), &mut ops).unwrap();
+        // 2026-10-16T23:30:00Z is already the 17th in Berlin.
+        task.set_value("due", Some("1792193400".to_string()), &mut ops).unwrap();
+        task.set_value("project", Some("Finance".to_string()), &mut ops).unwrap();
+        task.set_value("estimate", Some("3h".to_string()), &mut ops).unwrap();
+        task.add_tag(&Tag::try_from("work").unwrap(), &mut ops).unwrap();
+        task.add_tag(&Tag::try_from("q4").unwrap(), &mut ops).unwrap();
+        task.add_annotation(
+            Annotation { entry: DateTime::from_timestamp(1_760_619_600, 0).unwrap(), description: "Ask Sam".to_string() },
+            &mut ops,
+        )
+        .unwrap();
+        let mut task = replica.create_task(Uuid::new_v4(), &mut ops).unwrap();
+        task.set_description("File report".to_string(), &mut ops).unwrap();
+        task.set_entry(DateTime::from_timestamp(1_760_000_100, 0), &mut ops).unwrap();
+        task.done(&mut ops).unwrap();
+        replica.commit_operations(ops).unwrap();
+
+        let columns = parse_columns(vec!["description".into(), "due".into(), "tags".into(), "estimate".into()]).unwrap();
+        let tz = parse_timezone(Some("Europe/Berlin")).unwrap();
+        let csv = export_csv(&mut replica, &Filter::All, &columns, tz).unwrap();
+        let mut rows = csv.split("\r\n");
+        assert_eq!(rows.next(), Some("description,due,tags,estimate"));
+        let row = rows.next().unwrap();
+        assert!(row.starts_with("\"Quote \"\"budget\"\", then *review*\",2026-10-17 01:30,"));
+        assert!(row.contains("q4") && row.contains("work") && row.ends_with(",3h"));
+        assert_eq!(rows.next(), Some("File report,,,"));
+
+        let markdown = export_markdown(&mut replica, &Filter::All, tz).unwrap();
+        let lines: Vec<&str> = markdown.lines().collect();
+        assert!(lines[0].starts_with("- [ ] Quote \"budget\", then \\*review\\* (project: Finance) (due: 2026-10-17 01:30)"));
+        assert_eq!(lines[1], "  - 2025-10-16 15:00 Ask Sam");
+        assert_eq!(lines[2], "- [x] File report");
+
+        assert!(parse_timezone(Some("Mars/Olympus")).is_err());
+        assert!(parse_columns(vec!["due".into(), "due".into()]).is_err());
+    }
+
+    #[test]
+    fn test_csv_neutralises_formulas() {
+        let mut replica = Replica::new(StorageConfig::InMemory.into_storage().unwrap());
+        let mut ops = Operations::new();
+        for (entry, description) in [(1, "=HYPERLINK(\"http://x\",\"go\")"), (2, "@SUM(A1)"), (3, "-2+3"), (4, "a=b")] {
+            let mut task = replica.create_task(Uuid::new_v4(), &mut ops).unwrap();
+            task.set_description(description.to_string(), &mut ops).unwrap();
+            task.set_entry(DateTime::from_timestamp(entry, 0), &mut ops).unwrap();
+            task.set_value("cost", Some("+1".to_string()), &mut ops).unwrap();
+        }
+        replica.commit_operations(ops).unwrap();
+
+        let columns = parse_columns(vec!["description".into(), "cost".into()]).unwrap();
+        let csv = export_csv(&mut replica, &Filter::All, &columns, Tz::UTC).unwrap();
+        let rows: Vec<&str> = csv.split("\r\n").collect();
+        assert_eq!(rows[1], "\"'=HYPERLINK(\"\"http://x\"\",\"\"go\"\")\",'+1");
+        assert_eq!(rows[2], "'@SUM(A1),'+1");
+        assert_eq!(rows[3], "'-2+3,'+1");
+        assert_eq!(rows[4], "a=b,'+1");
+    }
+}
@@ -0,0 +1,399 @@
+//! TaskWarrior's JSON interchange format, as read and written by the
+//! desktop `task export` and `task import` commands.
+//!
+//! Each task is one JSON object whose attributes sit at the top level:
+//! dates in TaskWarrior's compact ISO-8601 form (`20261016T120000Z`),
+//! `tags` as an array, `annotations` as objects with an `entry` date and
+//! a `description`, `depends` as an array of UUIDs, and every other
+//! attribute (`project`, `priority`, UDAs, ...) as a string, except
+//! `imask`, which TaskWarrior writes as a number. `id` is the task's
+//! working-set index, or 0 when it has none; `task import` ignores it.
+//! An export is an array of such objects.
+//!
+//! Import accepts that array, or the older one-object-per-line layout,
+//! and writes each task's attributes as they would have been stored had
+//! the task been created here. `id` and `urgency` are computed by
+//! TaskWarrior and are not imported; status `waiting` (TaskWarrior 2.x)
+//! becomes `pending`, the `wait` date carrying the meaning.
+
+use crate::filter::{Filter, DATE_ATTRIBUTES};
+use crate::jni_bindings::{exported_tasks, raw_taskmap};
+use chrono::{DateTime, Duration, Utc};
+use serde_json::{json, Map, Value};
+use std::collections::{HashMap, HashSet};
+use taskchampion::{Operations, Replica, Tag, Task, TaskData};
+use uuid::Uuid;
+
+/// Format a date the way TaskWarrior's export does.
+pub fn format_date(date: DateTime<Utc>) -> String {
+    date.format("%Y%m%dT%H%M%SZ").to_string()
+}
+
+/// The TaskWarrior export object for one task.
+pub fn export_task(task: &Task, id: Option<usize>) -> Value {
+    let mut object = Map::new();
+    object.insert("id".to_string(), json!(id.unwrap_or(0)));
+    object.insert("uuid".to_string(), json!(task.get_uuid().to_string()));
+
+    let taskmap = raw_taskmap(task);
+    let mut depends = Vec::new();
+    for (key, value) in taskmap.iter() {
+        if key.starts_with("tag_") || key.starts_with("annotation_") {
+            continue; // exported from the parsed tags and annotations below
+        }
+        if let Some(dep) = key.strip_prefix("dep_") {
+            depends.push(dep.to_string());
+            continue;
+        }
+        let exported = if DATE_ATTRIBUTES.contains(&key.as_str()) {
+            match crate::dates::stored_timestamp(value) {
+                Some(date) => json!(format_date(date)),
+                None => json!(value),
+            }
+        } else if key == "imask" {
+            value.parse::<i64>().map_or_else(|_| json!(value), |n| json!(n))
+        } else {
+            json!(value)
+        };
+        object.insert(key.clone(), exported);
+    }
+    // TaskWarrior treats a missing status as pending but always exports one.
+    object.entry("status").or_insert_with(|| json!("pending"));
+
+    let tags: Vec<String> = task.get_tags().filter(|t| t.is_user()).map(|t| t.to_string()).collect();
+    if !tags.is_empty() {
+        object.insert("tags".to_string(), json!(tags));
+    }
+    let annotations: Vec<Value> = task
+        .get_annotations()
+        .map(|a| json!({ "entry": format_date(a.entry), "description": a.description }))
+        .collect();
+    if !annotations.is_empty() {
+        object.insert("annotations".to_string(), json!(annotations));
+    }
+    if !depends.is_empty() {
+        depends.sort();
+        object.insert("depends".to_string(), json!(depends));
+    }
+    Value::Object(object)
+}
+
+/// Export the tasks matching `filter` as a TaskWarrior export document,
+/// ordered by entry date (tasks without one first), then UUID.
+pub fn export(replica: &mut Replica, filter: &Filter) -> Result<String, String> {
+    let working_set = replica
+        .working_set()
+        .map_err(|e| format!("Failed to get working set: {}", e))?;
+    let exported: Vec<Value> = exported_tasks(replica, filter)?
+        .iter()
+        .map(|task| export_task(task, working_set.by_uuid(task.get_uuid())))
+        .collect();
+    serde_json::to_string(&exported).map_err(|e| format!("Failed to serialize export to JSON: {}", e))
+}
+
+/// Parse a TaskWarrior export: a JSON array of tasks, or a sequence of
+/// task objects (one per line in older exports).
+pub fn parse_export(text: &str) -> Result<Vec<Value>, String> {
+    let text = text.trim_start();
+    if text.starts_with('[') {
+        return serde_json::from_str(text).map_err(|e| format!("Invalid TaskWarrior export: {}", e));
+    }
+    serde_json::Deserializer::from_str(text)
+        .into_iter::<Value>()
+        .map(|v| v.map_err(|e| format!("Invalid TaskWarrior export: {}", e)))
+        .collect()
+}
+
+/// A date attribute's value: a TaskWarrior date string, or epoch seconds.
+fn import_date(key: &str, value: &Value) -> Result<DateTime<Utc>, String> {
+    let parsed = match value {
+        Value::String(s) => crate::dates::parse_date(s),
+        Value::Number(n) => n
+            .as_i64()
+            .and_then(|secs| DateTime::from_timestamp(secs, 0))
+            .ok_or_else(|| format!("Date out of range: {}", n)),
+        _ => Err("not a date".to_string()),
+    };
+    parsed.map_err(|e| format!("Invalid '{}': {}", key, e))
+}
+
+/// A list attribute's items: a JSON array of strings, or (TaskWarrior
+/// 2.5 and earlier) one comma-separated string.
+fn import_list(key: &str, value: &Value) -> Result<Vec<String>, String> {
+    match value {
+        Value::String(s) => Ok(s.split(',').map(str::trim).filter(|s| !s.is_empty()).map(str::to_string).collect()),
+        Value::Array(items) => items
+            .iter()
+            .map(|item| {
+                item.as_str()
+                    .map(str::to_string)
+                    .ok_or_else(|| format!("Invalid '{}': expected strings", key))
+            })
+            .collect(),
+        _ => Err(format!("Invalid '{}': expected an array", key)),
+    }
+}
+
+/// The TaskChampion key/value map for one exported task. An `Err` is the
+/// reason the task cannot be imported.
+fn import_taskmap(task: &Value) -> Result<(Uuid, HashMap<String, String>), String> {
+    let object = task.as_object().ok_or("not a JSON object")?;
+    let uuid = object
+        .get("uuid")
+        .and_then(Value::as_str)
+        .ok_or("missing uuid")?;
+    let uuid = Uuid::parse_str(uuid).map_err(|_| format!("invalid uuid '{}'", uuid))?;
+    match object.get("description").and_then(Value::as_str) {
+        Some(d) if !d.trim().is_empty() => {}
+        _ => return Err("missing description".to_string()),
+    }
+
+    let mut taskmap = HashMap::new();
+    for (key, value) in object {
+        if value.is_null() {
+            continue;
+        }
+        match key.as_str() {
+            "uuid" | "id" | "urgency" => {}
+            "status" => {
+                let status = match value.as_str() {
+                    Some("waiting") => "pending",
+                    Some(s @ ("pending" | "completed" | "deleted" | "recurring")) => s,
+                    _ => return Err(format!("unknown status {}", value)),
+                };
+                taskmap.insert(key.clone(), status.to_string());
+            }
+            "tags" => {
+                for tag in import_list(key, value)? {
+                    Tag::try_from(tag.as_str()).map_err(|e| format!("invalid tag '{}': {}", tag, e))?;
+                    taskmap.insert(format!("tag_{}", tag), String::new());
+                }
+            }
+            "depends" => {
+                for dep in import_list(key, value)? {
+                    let dep = Uuid::parse_str(&dep).map_err(|_| format!("invalid dependency '{}'", dep))?;
+                    taskmap.insert(format!("dep_{}", dep), String::new());
+                }
+            }
+            "annotations" => {
+                for annotation in value.as_array().ok_or("invalid 'annotations': expected an array")? {
+                    let entry = annotation.get("entry").ok_or("annotation without an entry date")?;
+                    let mut entry = import_date("annotations", entry)?;
+                    let description = annotation
+                        .get("description")
+                        .and_then(Value::as_str)
+                        .ok_or("annotation without a description")?;
+                    // Annotations are keyed by their entry second; keep
+                    // any that share one.
+                    while taskmap.contains_key(&format!("annotation_{}", entry.timestamp())) {
+                        entry += Duration::seconds(1);
+                    }
+                    taskmap.insert(format!("annotation_{}", entry.timestamp()), description.to_string());
+                }
+            }
+            k if DATE_ATTRIBUTES.contains(&k) => {
+                taskmap.insert(key.clone(), import_date(key, value)?.timestamp().to_string());
+            }
+            // These keys are only ever written from the checked
+            // attributes above.
+            k if k.starts_with("tag_") => return Err(format!("unexpected '{}': use 'tags'", key)),
+            k if k.starts_with("dep_") => return Err(format!("unexpected '{}': use 'depends'", key)),
+            k if k.starts_with("annotation_") => return Err(format!("unexpected '{}': use 'annotations'", key)),
+            _ => {
+                let value = match value {
+                    Value::String(s) => s.clone(),
+                    Value::Number(n) => n.to_string(),
+                    Value::Bool(b) => b.to_string(),
+                    _ => return Err(format!("unsupported value for '{}'", key)),
+                };
+                taskmap.insert(key.clone(), value);
+            }
+        }
+    }
+    taskmap.entry("status".to_string()).or_insert_with(|| "pending".to_string());
+    Ok((uuid, taskmap))
+}
+
+/// What an import did, task by task.
+#[derive(Debug, Default)]
+pub struct ImportReport {
+    pub created: Vec<Uuid>,
+    pub updated: Vec<Uuid>,
+    /// Position in the export, UUID if it had a readable one, and reason.
+    pub skipped: Vec<(usize, Option<String>, String)>,
+}
+
+impl ImportReport {
+    /// The report as a JSON document:
+    /// `{"created": [uuid...], "updated": [uuid...],
+    ///   "skipped": [{"index": n, "uuid": ..., "reason": "..."}]}`.
+    pub fn to_json(&self) -> Result<String, String> {
+        let skipped: Vec<Value> = self
+            .skipped
+            .iter()
+            .map(|(index, uuid, reason)| json!({ "index": index, "uuid": uuid, "reason": reason }))
+            .collect();
+        let report = json!({
+            "created": self.created.iter().map(Uuid::to_string).collect::<Vec<_>>(),
+            "updated": self.updated.iter().map(Uuid::to_string).collect::<Vec<_>>(),
+            "skipped": skipped,
+        });
+        serde_json::to_string(&report).map_err(|e| format!("Failed to serialize import report to JSON: {}", e))
+    }
+}
+
+/// One task read from an import: its UUID and key/value map, or the
+/// reason it cannot be imported.
+pub type ImportedTask = Result<(Uuid, HashMap<String, String>), String>;
+
+/// Add the operations importing `tasks` to `ops`.
+pub fn import(replica: &mut Replica, tasks: &[Value], ops: &mut Operations) -> Result<ImportReport, String> {
+    let imported = tasks
+        .iter()
+        .map(|task| (task.get("uuid").and_then(Value::as_str).map(str::to_string), import_taskmap(task)))
+        .collect();
+    store_imported(replica, imported, ops)
+}
+
+/// Add the operations storing imported tasks to `ops`, each given with
+/// the UUID as written in the source (if it had one) for the report. A
+/// task whose UUID already exists replaces it, as `task import` does;
+/// one identical to the stored task, or repeating a UUID earlier in the
+/// import, is skipped.
+pub fn store_imported(
+    replica: &mut Replica,
+    tasks: Vec<(Option<String>, ImportedTask)>,
+    ops: &mut Operations,
+) -> Result<ImportReport, String> {
+    let mut report = ImportReport::default();
+    let mut seen = HashSet::new();
+    for (index, (raw_uuid, imported)) in tasks.into_iter().enumerate() {
+        let (uuid, taskmap) = match imported {
+            Ok(imported) => imported,
+            Err(reason) => {
+                report.skipped.push((index, raw_uuid, reason));
+                continue;
+            }
+        };
+        if !seen.insert(uuid) {
+            report.skipped.push((index, raw_uuid, "repeats an earlier task's uuid".to_string()));
+            continue;
+        }
+
+        let existing = replica
+            .get_task_data(uuid)
+            .map_err(|e| format!("Failed to get task: {}", e))?;
+        let (mut data, created) = match existing {
+            Some(data) => (data, false),
+            None => (TaskData::create(uuid, ops), true),
+        };
+        let stale: Vec<String> = data.properties().filter(|k| !taskmap.contains_key(*k)).cloned().collect();
+        let changed: Vec<(&String, &String)> =
+            taskmap.iter().filter(|(k, v)| data.get(k) != Some(v.as_str())).collect();
+        if !created && stale.is_empty() && changed.is_empty() {
+            report.skipped.push((index, raw_uuid, "unchanged".to_string()));
+            continue;
+        }
+        for key in stale {
+            data.update(key, None, ops);
+        }
+        for (key, value) in changed {
+            data.update(key, Some(value.clone()), ops);
+        }
+        if created {
+            report.created.push(uuid);
+        } else {
+            report.updated.push(uuid);
+        }
+    }
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use taskchampion::{Annotation, Operations, Status, StorageConfig, Tag};
+    use uuid::Uuid;
+
+    #[test]
+    fn test_export_uses_taskwarrior_layout() {
+        let mut replica = Replica::new(StorageConfig::InMemory.into_storage().unwrap());
+        let blocker = Uuid::new_v4();
+        let uuid = Uuid::new_v4();
+        let mut ops = Operations::new();
+        replica.create_task(blocker, &mut ops).unwrap();
+        let mut task = replica.create_task(uuid, &mut ops).unwrap();
+        task.set_description("Paint fence".to_string(), &mut ops).unwrap();
+        task.set_status(Status::Pending, &mut ops).unwrap();
+        task.set_value("entry", Some("1760616000".to_string()), &mut ops).unwrap();
+        task.set_value("project", Some("Home".to_string()), &mut ops).unwrap();
+        task.set_value("estimate", Some("3".to_string()), &mut ops).unwrap();
+        task.add_tag(&Tag::try_from("diy").unwrap(), &mut ops).unwrap();
+        task.add_annotation(
+            Annotation { entry: DateTime::from_timestamp(1_760_619_600, 0).unwrap(), description: "Green".to_string() },
+            &mut ops,
+        )
+        .unwrap();
+        task.add_dependency(blocker, &mut ops).unwrap();
+        replica.commit_operations(ops).unwrap();
+
+        let exported: Vec<Value> = serde_json::from_str(&export(&mut replica, &Filter::All).unwrap()).unwrap();
+        let task = exported.iter().find(|t| t["uuid"] == uuid.to_string()).unwrap();
+        assert_eq!(task["description"], "Paint fence");
+        assert_eq!(task["status"], "pending");
+        assert_eq!(task["entry"], "20251016T120000Z");
+        assert_eq!(task["project"], "Home");
+        assert_eq!(task["estimate"], "3");
+        assert_eq!(task["tags"], json!(["diy"]));
+        assert_eq!(task["annotations"], json!([{"entry": "20251016T130000Z", "description": "Green"}]));
+        assert_eq!(task["depends"], json!([blocker.to_string()]));
+        assert!(task.get("udas").is_none());
+        assert!(task["id"].as_u64().unwrap() > 0);
+    }
+
+    #[test]
+    fn test_import_reports_each_task() {
+        let mut replica = Replica::new(StorageConfig::InMemory.into_storage().unwrap());
+        let a = Uuid::new_v4();
+        let b = Uuid::new_v4();
+        let document = json!([
+            {"id": 1, "uuid": a.to_string(), "description": "Paint fence", "status": "pending",
+             "entry": "20251016T120000Z", "project": "Home", "estimate": 3, "urgency": 4.2,
+             "tags": ["diy"], "depends": b.to_string(),
+             "annotations": [{"entry": "20251016T130000Z", "description": "Green"}]},
+            {"uuid": b.to_string(), "description": "Buy paint", "status": "waiting",
+             "wait": "20301016T120000Z"},
+            {"uuid": Uuid::new_v4().to_string(), "description": "Odd", "status": "someday"},
+            {"uuid": Uuid::new_v4().to_string(), "description": "Raw", "dep_x": "1"},
+            {"description": "No uuid"},
+            {"uuid": a.to_string(), "description": "Again"},
+        ]);
+        let tasks = parse_export(&document.to_string()).unwrap();
+        let mut ops = Operations::new();
+        let report = import(&mut replica, &tasks, &mut ops).unwrap();
+        replica.commit_operations(ops).unwrap();
+        assert_eq!(report.created, vec![a, b]);
+        let reasons: Vec<&str> = report.skipped.iter().map(|(_, _, r)| r.as_str()).collect();
+        assert_eq!(reasons, vec!["unknown status \"someday\"", "unexpected 'dep_x': use 'depends'", "missing uuid", "repeats an earlier task's uuid"]);
+
+        let task = replica.get_task_data(a).unwrap().unwrap();
+        assert_eq!(task.get("entry"), Some("1760616000"));
+        assert_eq!(task.get("estimate"), Some("3"));
+        assert_eq!(task.get("annotation_1760619600"), Some("Green"));
+        assert!(task.has("tag_diy") && task.has(format!("dep_{}", b)) && !task.has("urgency"));
+        assert_eq!(replica.get_task_data(b).unwrap().unwrap().get("status"), Some("pending"));
+
+        // Re-importing our own export changes nothing; an edit updates.
+        let tasks = parse_export(&export(&mut replica, &Filter::All).unwrap()).unwrap();
+        let mut ops = Operations::new();
+        let report = import(&mut replica, &tasks, &mut ops).unwrap();
+        assert!(report.created.is_empty() && report.updated.is_empty());
+        assert_eq!(report.skipped.len(), 2);
+        let lines = r#"{"uuid": "UUID", "description": "Buy green paint", "status": "pending"}"#.replace("UUID", &b.to_string());
+        let mut ops = Operations::new();
+        let report = import(&mut replica, &parse_export(&lines).unwrap(), &mut ops).unwrap();
+        replica.commit_operations(ops).unwrap();
+        assert_eq!(report.updated, vec![b]);
+        assert!(!replica.get_task_data(b).unwrap().unwrap().has("wait"));
+    }
+}
@@ -0,0 +1,350 @@
+//! Task specs, placeholder substitution and the stored template registry.
+//!
+//! A task spec is a JSON object in the layout task JSON is read back in
+//! (see `task_to_json`), every field optional:
+//!
+//! ```json
+//! {"description": "Weekly review {{today}}",
+//!  "tags": ["review"],
+//!  "annotations": [{"description": "See notes"}],
+//!  "udas": {"project": "Admin", "due": "{{date:tomorrow}}", "priority": null}}
+//! ```
+//!
+//! Applied to a task, `description` replaces the description, `tags` and
+//! `annotations` replace the task's user tags and annotations, and each
+//! `udas` entry sets (or, when `null`, removes) one property. An
+//! annotation without an `entry` is stamped with the current time.
+//!
+//! String values may contain placeholders: `{{name}}` for a caller-
+//! supplied parameter, `{{today}}` (the UTC date, `YYYY-MM-DD`), `{{now}}`
+//! (epoch seconds) and `{{date:EXPR}}` (epoch seconds of any date
+//! [`crate::dates::parse_date`] accepts, e.g. `{{date:tomorrow}}`).
+//!
+//! Templates are named specs stored in the binding's own database in the
+//! data directory (see [`crate::journal`]). They are local to the device
+//! and not synced.
+
+use crate::dates;
+use chrono::{DateTime, Duration, Utc};
+use rusqlite::{params, Connection, OptionalExtension};
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use taskchampion::{Annotation, Operations, Tag, Task};
+
+/// Task properties a spec sets through its own fields, or that the
+/// binding manages, and so may not appear among its `udas`.
+const RESERVED_PROPERTIES: &[&str] = &["description", "status", "entry", "modified", "end"];
+
+/// Why a spec, or its substitution, was rejected.
+#[derive(Debug, Clone, PartialEq)]
+pub enum SpecError {
+    /// A tag fails TaskChampion's tag-name validation.
+    InvalidTag(String),
+    /// Anything else: malformed JSON, a reserved property, a bad
+    /// annotation entry or placeholder.
+    Invalid(String),
+}
+
+impl fmt::Display for SpecError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SpecError::InvalidTag(message) | SpecError::Invalid(message) => f.write_str(message),
+        }
+    }
+}
+
+impl From<String> for SpecError {
+    fn from(message: String) -> SpecError {
+        SpecError::Invalid(message)
+    }
+}
+
+fn check_tag(tag: &str) -> Result<(), SpecError> {
+    Tag::try_from(tag)
+        .map(drop)
+        .map_err(|e| SpecError::InvalidTag(format!("Invalid tag '{}': {}", tag, e)))
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct TaskSpec {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tags: Option<Vec<String>>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub annotations: Option<Vec<AnnotationSpec>>,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub udas: BTreeMap<String, Option<String>>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct AnnotationSpec {
+    pub description: String,
+    /// Epoch seconds, as annotations are read back.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub entry: Option<String>,
+}
+
+impl TaskSpec {
+    /// Parse and validate a spec.
+    pub fn parse(json: &str) -> Result<TaskSpec, SpecError> {
+        let spec: TaskSpec =
+            serde_json::from_str(json).map_err(|e| format!("Invalid task JSON: {}", e))?;
+        for key in spec.udas.keys() {
+            if RESERVED_PROPERTIES.contains(&key.as_str())
+                || key.starts_with("tag_")
+                || key.starts_with("annotation_")
+            {
+                return Err(format!("Property '{}' cannot be set through udas", key).into());
+            }
+        }
+        // Tags and entries holding placeholders are checked once substituted.
+        for tag in spec.tags.iter().flatten().filter(|t| !t.contains("{{")) {
+            check_tag(tag)?;
+        }
+        for entry in spec.annotations.iter().flatten().filter_map(|a| a.entry.as_deref()) {
+            if !entry.contains("{{") && dates::stored_timestamp(entry).is_none() {
+                return Err(format!("Invalid annotation entry '{}'; expected epoch seconds", entry).into());
+            }
+        }
+        for value in spec.strings() {
+            check_placeholders(value)?;
+        }
+        Ok(spec)
+    }
+
+    fn strings(&self) -> impl Iterator<Item = &String> {
+        self.description
+            .iter()
+            .chain(self.tags.iter().flatten())
+            .chain(self.annotations.iter().flatten().flat_map(|a| std::iter::once(&a.description).chain(&a.entry)))
+            .chain(self.udas.values().flatten())
+    }
+
+    /// Replace every placeholder, with `params` supplying named ones, and
+    /// check the tags that held one.
+    pub fn substitute(&self, params: &HashMap<String, String>, now: DateTime<Utc>) -> Result<TaskSpec, SpecError> {
+        let sub = |s: &String| substitute(s, params, now);
+        let spec = TaskSpec {
+            description: self.description.as_ref().map(sub).transpose()?,
+            tags: self.tags.as_ref().map(|tags| tags.iter().map(sub).collect()).transpose()?,
+            annotations: self
+                .annotations
+                .as_ref()
+                .map(|annotations| {
+                    annotations
+                        .iter()
+                        .map(|a| {
+                            Ok(AnnotationSpec {
+                                description: sub(&a.description)?,
+                                entry: a.entry.as_ref().map(sub).transpose()?,
+                            })
+                        })
+                        .collect::<Result<Vec<_>, String>>()
+                })
+                .transpose()?,
+            udas: self
+                .udas
+                .iter()
+                .map(|(k, v)| Ok((k.clone(), v.as_ref().map(sub).transpose()?)))
+                .collect::<Result<_, String>>()?,
+        };
+        for tag in spec.tags.iter().flatten() {
+            check_tag(tag)?;
+        }
+        Ok(spec)
+    }
+
+    /// Write the spec onto `task`. Does not touch `modified`.
+    pub fn apply(&self, task: &mut Task, now: DateTime<Utc>, ops: &mut Operations) -> Result<(), String> {
+        if let Some(description) = &self.description {
+            task.set_description(description.clone(), ops)
+                .map_err(|e| format!("Failed to set task description: {}", e))?;
+        }
+        if let Some(tags) = &self.tags {
+            let tags = tags
+                .iter()
+                .map(|t| Tag::try_from(t.as_str()).map_err(|e| format!("Invalid tag '{}': {}", t, e)))
+                .collect::<Result<Vec<Tag>, String>>()?;
+            let existing: Vec<Tag> = task.get_tags().filter(|t| t.is_user()).collect();
+            for tag in existing.iter().filter(|t| !tags.contains(t)) {
+                task.remove_tag(tag, ops)
+                    .map_err(|e| format!("Failed to remove tag from task: {}", e))?;
+            }
+            for tag in &tags {
+                task.add_tag(tag, ops)
+                    .map_err(|e| format!("Failed to add tag to task: {}", e))?;
+            }
+        }
+        if let Some(annotations) = &self.annotations {
+            let existing: Vec<DateTime<Utc>> = task.get_annotations().map(|a| a.entry).collect();
+            for entry in existing {
+                task.remove_annotation(entry, ops)
+                    .map_err(|e| format!("Failed to remove annotation from task: {}", e))?;
+            }
+            // Annotations are keyed by their entry time, so stamped ones
+            // are spread a second apart to keep them all.
+            for (i, annotation) in annotations.iter().enumerate() {
+                let entry = match &annotation.entry {
+                    Some(entry) => dates::stored_timestamp(entry)
+                        .ok_or_else(|| format!("Invalid annotation entry '{}'; expected epoch seconds", entry))?,
+                    None => now + Duration::seconds(i as i64),
+                };
+                let annotation = Annotation { entry, description: annotation.description.clone() };
+                task.add_annotation(annotation, ops)
+                    .map_err(|e| format!("Failed to add annotation to task: {}", e))?;
+            }
+        }
+        for (key, value) in &self.udas {
+            task.set_value(key, value.clone(), ops)
+                .map_err(|e| format!("Failed to set task value: {}", e))?;
+        }
+        Ok(())
+    }
+}
+
+/// The placeholders in `value`, without their braces.
+fn placeholders(value: &str) -> Result<Vec<&str>, String> {
+    let mut found = Vec::new();
+    let mut rest = value;
+    while let Some(start) = rest.find("{{") {
+        let after = &rest[start + 2..];
+        let end = after
+            .find("}}")
+            .ok_or_else(|| format!("Unterminated placeholder in '{}'", value))?;
+        found.push(after[..end].trim());
+        rest = &after[end + 2..];
+    }
+    Ok(found)
+}
+
+fn check_placeholders(value: &str) -> Result<(), String> {
+    for name in placeholders(value)? {
+        if name.is_empty() {
+            return Err(format!("Empty placeholder in '{}'", value));
+        }
+        if let Some(expr) = name.strip_prefix("date:") {
+            dates::parse_date(expr).map_err(|e| format!("Invalid placeholder '{{{{{}}}}}': {}", name, e))?;
+        }
+    }
+    Ok(())
+}
+
+fn substitute(value: &str, params: &HashMap<String, String>, now: DateTime<Utc>) -> Result<String, String> {
+    let mut out = String::with_capacity(value.len());
+    let mut rest = value;
+    for name in placeholders(value)? {
+        let start = rest.find("{{").expect("placeholder was found");
+        let end = start + rest[start..].find("}}").expect("placeholder was terminated") + 2;
+        out.push_str(&rest[..start]);
+        let replacement = if let Some(value) = params.get(name) {
+            value.clone()
+        } else if let Some(expr) = name.strip_prefix("date:") {
+            dates::parse_date(expr)
+                .map_err(|e| format!("Invalid placeholder '{{{{{}}}}}': {}", name, e))?
+                .timestamp()
+                .to_string()
+        } else {
+            match name {
+                "today" => now.format("%Y-%m-%d").to_string(),
+                "now" => now.timestamp().to_string(),
+                _ => return Err(format!("No value for placeholder '{{{{{}}}}}'", name)),
+            }
+        };
+        out.push_str(&replacement);
+        rest = &rest[end..];
+    }
+    out.push_str(rest);
+    Ok(out)
+}
+
+/// Parse placeholder parameters: a JSON object of strings.
+pub fn parse_params(json: &str) -> Result<HashMap<String, String>, String> {
+    serde_json::from_str(json).map_err(|e| format!("Invalid template parameters: {}", e))
+}
+
+/// Store `spec` as template `name`, replacing any template of that name.
+pub fn save(conn: &Connection, name: &str, spec: &TaskSpec) -> Result<(), String> {
+    let json = serde_json::to_string(spec).map_err(|e| format!("Failed to serialize template: {}", e))?;
+    conn.execute(
+        "INSERT OR REPLACE INTO jni.task_templates (name, spec) VALUES (?1, ?2)",
+        params![name, json],
+    )
+    .map_err(|e| format!("Failed to save template: {}", e))?;
+    Ok(())
+}
+
+/// The template named `name`, if any.
+pub fn get(conn: &Connection, name: &str) -> Result<Option<TaskSpec>, String> {
+    let json: Option<String> = conn
+        .query_row("SELECT spec FROM jni.task_templates WHERE name = ?1", [name], |row| row.get(0))
+        .optional()
+        .map_err(|e| format!("Failed to read template: {}", e))?;
+    json.map(|json| TaskSpec::parse(&json).map_err(|e| e.to_string())).transpose()
+}
+
+/// Every template, by name.
+pub fn list(conn: &Connection) -> Result<Vec<(String, TaskSpec)>, String> {
+    let mut stmt = conn
+        .prepare("SELECT name, spec FROM jni.task_templates ORDER BY name")
+        .map_err(|e| format!("Failed to read templates: {}", e))?;
+    let rows = stmt
+        .query_map([], |row| Ok((row.get::<_, String>(0)?, row.get::<_, String>(1)?)))
+        .map_err(|e| format!("Failed to read templates: {}", e))?;
+    let mut templates = Vec::new();
+    for row in rows {
+        let (name, json) = row.map_err(|e| format!("Failed to read templates: {}", e))?;
+        templates.push((name, TaskSpec::parse(&json).map_err(|e| e.to_string())?));
+    }
+    Ok(templates)
+}
+
+/// Remove template `name`. Returns false if there was none.
+pub fn delete(conn: &Connection, name: &str) -> Result<bool, String> {
+    let removed = conn
+        .execute("DELETE FROM jni.task_templates WHERE name = ?1", [name])
+        .map_err(|e| format!("Failed to delete template: {}", e))?;
+    Ok(removed > 0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_rejects_bad_specs() {
+        assert!(TaskSpec::parse(r#"{"description": "ok", "udas": {"project": null}}"#).is_ok());
+        assert!(TaskSpec::parse(r#"{"colour": "red"}"#).is_err());
+        assert!(TaskSpec::parse(r#"{"udas": {"status": "completed"}}"#).is_err());
+        assert!(TaskSpec::parse(r#"{"udas": {"tag_home": ""}}"#).is_err());
+        assert!(matches!(TaskSpec::parse(r#"{"tags": ["two words"]}"#), Err(SpecError::InvalidTag(_))));
+        assert!(TaskSpec::parse(r#"{"description": "Call {{who"}"#).is_err());
+        assert!(TaskSpec::parse(r#"{"description": "{{date:someday}}"}"#).is_err());
+    }
+
+    #[test]
+    fn test_substitute_placeholders() {
+        let spec = TaskSpec::parse(
+            r#"{"description": "Review {{team}} on {{today}}",
+                "tags": ["{{team}}"],
+                "udas": {"due": "{{date:2026-10-20}}", "created": "{{now}}"}}"#,
+        )
+        .unwrap();
+        let now = DateTime::from_timestamp(1_760_616_000, 0).unwrap();
+        let params = HashMap::from([("team".to_string(), "ops".to_string())]);
+        let spec = spec.substitute(&params, now).unwrap();
+        assert_eq!(spec.description.as_deref(), Some("Review ops on 2025-10-16"));
+        assert_eq!(spec.tags, Some(vec!["ops".to_string()]));
+        assert_eq!(spec.udas["due"].as_deref(), Some("1792454400"));
+        assert_eq!(spec.udas["created"].as_deref(), Some("1760616000"));
+
+        let missing = TaskSpec::parse(r#"{"description": "Call {{who}}"}"#).unwrap();
+        assert!(matches!(missing.substitute(&HashMap::new(), now), Err(SpecError::Invalid(_))));
+        let spaced = HashMap::from([("team".to_string(), "two words".to_string())]);
+        let tagged = TaskSpec::parse(r#"{"tags": ["{{team}}"]}"#).unwrap();
+        assert!(matches!(tagged.substitute(&spaced, now), Err(SpecError::InvalidTag(_))));
+    }
+}
@@ -0,0 +1,352 @@
+//! The todo.txt format (<https://github.com/todotxt/todo.txt>): one task
+//! per line.
+//!
+//! ```text
+//! x 2026-10-16 2026-10-01 Call the bank +Finance @phone due:2026-10-20 pri:A
+//! (B) 2026-10-02 Renew passport +Admin @town due:2026-11-01
+//! ```
+//!
+//! | todo.txt                          | Task                                |
+//! |-----------------------------------|-------------------------------------|
+//! | leading `x`                       | status completed                    |
+//! | completion date (after `x`)       | `end`                               |
+//! | `(A)` / `(B)` / `(C)`             | priority `H` / `M` / `L`            |
+//! | `(D)` ... `(Z)`                   | `todotxt_priority` UDA, no priority |
+//! | creation date                     | `entry`                             |
+//! | first `+project`                  | `project`                           |
+//! | further `+project`s               | `todotxt_projects` UDA              |
+//! | `@context`                        | tag                                 |
+//! | `due:YYYY-MM-DD`                  | `due`                               |
+//! | `t:YYYY-MM-DD` (threshold)        | `wait`                              |
+//! | other `key:value`                 | UDA, value as written               |
+//!
+//! A word is only read as `key:value` when the key starts with a letter,
+//! names no tag, annotation or dependency (`tag_`, `annotation_`,
+//! `dep_`) and is no URI scheme, and the value holds no further colon,
+//! so `10:30`, `mailto:a@b` and `https://...` stay in the description.
+//!
+//! Dates are calendar days, read as midnight UTC and written as the UTC
+//! date, so a time of day does not survive. A completed task's priority
+//! is written as `pri:A`, the usual convention, since todo.txt puts the
+//! completion marker where the priority would go. Export writes pending
+//! and completed tasks only; todo.txt has no deleted state or
+//! recurrence templates. Attributes with no todo.txt form (annotations,
+//! dependencies, values containing spaces) are left out.
+//!
+//! todo.txt has no task identity. An imported line gets a UUID derived
+//! from its text, and a line whose task already exists is skipped, so
+//! importing the same file twice adds its tasks once.
+
+use crate::dates::stored_timestamp;
+use crate::filter::Filter;
+use crate::jni_bindings::{exported_tasks, raw_taskmap};
+use crate::taskwarrior::{store_imported, ImportReport, ImportedTask};
+use chrono::{DateTime, NaiveDate, Utc};
+use std::collections::HashMap;
+use taskchampion::{Operations, Replica, Tag, Task};
+use uuid::Uuid;
+
+/// UDA holding a priority below `(C)`, which has no TaskWarrior
+/// equivalent.
+const PRIORITY_UDA: &str = "todotxt_priority";
+
+/// UDA holding every `+project` after the first, space-separated.
+const PROJECTS_UDA: &str = "todotxt_projects";
+
+/// Attributes with a dedicated todo.txt form, or none at all; never
+/// written or read as `key:value`.
+const RESERVED: &[&str] = &[
+    "description", "status", "entry", "modified", "end", "priority", "project", "due", "wait", "t", "pri",
+    PRIORITY_UDA, PROJECTS_UDA,
+];
+
+/// Key prefixes TaskChampion gives its own meaning to, whose values
+/// todo.txt has other forms for or none.
+const RESERVED_PREFIXES: &[&str] = &["tag_", "annotation_", "dep_"];
+
+/// URI schemes written without `//`, whose URIs would otherwise look
+/// like `key:value`.
+const URI_SCHEMES: &[&str] = &["mailto", "tel", "sms", "geo", "magnet", "urn"];
+
+fn format_day(date: DateTime<Utc>) -> String {
+    date.format("%Y-%m-%d").to_string()
+}
+
+fn parse_day(value: &str) -> Option<DateTime<Utc>> {
+    NaiveDate::parse_from_str(value, "%Y-%m-%d")
+        .ok()
+        .map(|d| d.and_hms_opt(0, 0, 0).unwrap().and_utc())
+}
+
+fn stored_day(task: &Task, key: &str) -> Option<String> {
+    task.get_value(key).and_then(stored_timestamp).map(format_day)
+}
+
+/// The todo.txt priority letter for a task, if it has one.
+fn priority_letter(task: &Task) -> Option<char> {
+    match task.get_value("priority") {
+        Some("H") => Some('A'),
+        Some("M") => Some('B'),
+        Some("L") => Some('C'),
+        _ => task
+            .get_value(PRIORITY_UDA)
+            .and_then(|p| p.chars().next())
+            .filter(|c| c.is_ascii_uppercase()),
+    }
+}
+
+/// The todo.txt line for one task.
+fn export_line(task: &Task) -> String {
+    let completed = task.get_value("status") == Some("completed");
+    let priority = priority_letter(task);
+    let mut parts: Vec<String> = Vec::new();
+    if completed {
+        parts.push("x".to_string());
+        if let Some(end) = stored_day(task, "end") {
+            parts.push(end);
+        }
+    } else if let Some(priority) = priority {
+        parts.push(format!("({})", priority));
+    }
+    match stored_day(task, "entry") {
+        // A creation date alone after `x` would be read back as the
+        // completion date.
+        Some(_) if completed && parts.len() == 1 => {}
+        Some(entry) => parts.push(entry),
+        None => {}
+    }
+    parts.extend(task.get_description().split_whitespace().map(str::to_string));
+
+    if let Some(project) = task.get_value("project") {
+        parts.push(format!("+{}", project.split_whitespace().collect::<Vec<_>>().join("_")));
+    }
+    if let Some(projects) = task.get_value(PROJECTS_UDA) {
+        parts.extend(projects.split_whitespace().map(|p| format!("+{}", p)));
+    }
+    let mut tags: Vec<String> = task.get_tags().filter(|t| t.is_user()).map(|t| format!("@{}", t)).collect();
+    tags.sort();
+    parts.extend(tags);
+    if let Some(due) = stored_day(task, "due") {
+        parts.push(format!("due:{}", due));
+    }
+    if let Some(wait) = stored_day(task, "wait") {
+        parts.push(format!("t:{}", wait));
+    }
+
+    let taskmap = raw_taskmap(task);
+    let mut extra: Vec<(&String, &String)> = taskmap
+        .iter()
+        .filter(|(key, value)| {
+            !RESERVED.contains(&key.as_str()) && is_uda_key(key) && is_uda_value(value) && !value.contains(char::is_whitespace)
+        })
+        .collect();
+    extra.sort();
+    parts.extend(extra.into_iter().map(|(key, value)| format!("{}:{}", key, value)));
+    if let (true, Some(priority)) = (completed, priority) {
+        parts.push(format!("pri:{}", priority));
+    }
+    parts.join(" ")
+}
+
+/// Export the pending and completed tasks matching `filter` as a
+/// todo.txt document, ordered by entry date, then UUID.
+pub fn export(replica: &mut Replica, filter: &Filter) -> Result<String, String> {
+    Ok(exported_tasks(replica, filter)?
+        .iter()
+        .filter(|task| matches!(task.get_value("status"), Some("pending") | Some("completed") | None))
+        .map(|task| export_line(task) + "\n")
+        .collect())
+}
+
+/// Whether `key` can be written as a todo.txt `key:value` and read back.
+fn is_uda_key(key: &str) -> bool {
+    key.starts_with(|c: char| c.is_ascii_alphabetic())
+        && key.chars().all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+        && !RESERVED_PREFIXES.iter().any(|prefix| key.starts_with(prefix))
+        && !URI_SCHEMES.contains(&key)
+}
+
+/// Whether `value` can be written after a todo.txt `key:` and read back.
+fn is_uda_value(value: &str) -> bool {
+    !value.is_empty() && !value.contains(':') && !value.starts_with("//")
+}
+
+/// The TaskChampion key/value map for one line.
+fn parse_line(line: &str, now: DateTime<Utc>) -> ImportedTask {
+    let uuid = Uuid::new_v5(&Uuid::NAMESPACE_URL, format!("todotxt:{}", line).as_bytes());
+    let mut taskmap: HashMap<String, String> = HashMap::new();
+    let mut words = line.split_whitespace().peekable();
+
+    let completed = words.next_if_eq(&"x").is_some();
+    let mut priority = None;
+    if !completed {
+        priority = words.next_if(|w| is_priority(w)).and_then(|w| w.chars().nth(1));
+    }
+    let mut next_day = || words.next_if(|w| parse_day(w).is_some()).and_then(parse_day);
+    // After `x` come the completion date, then the creation date.
+    let (end, entry) = match (completed, next_day()) {
+        (true, Some(end)) => (Some(end), next_day()),
+        (_, first) => (None, first),
+    };
+
+    let mut description = Vec::new();
+    let mut projects = Vec::new();
+    for word in words {
+        if let Some(project) = word.strip_prefix('+').filter(|p| !p.is_empty()) {
+            projects.push(project.to_string());
+        } else if let Some(context) = word.strip_prefix('@').filter(|c| !c.is_empty()) {
+            Tag::try_from(context).map_err(|e| format!("invalid context '{}': {}", context, e))?;
+            taskmap.insert(format!("tag_{}", context), String::new());
+        } else if let Some((key, value)) = word.split_once(':').filter(|(k, v)| is_uda_key(k) && is_uda_value(v)) {
+            match key {
+                "due" | "t" => {
+                    let date = parse_day(value).ok_or_else(|| format!("invalid date '{}'", word))?;
+                    let key = if key == "due" { "due" } else { "wait" };
+                    taskmap.insert(key.to_string(), date.timestamp().to_string());
+                }
+                "pri" if value.len() == 1 && value.chars().all(|c| c.is_ascii_uppercase()) => {
+                    priority = priority.or(value.chars().next());
+                }
+                key if RESERVED.contains(&key) => return Err(format!("'{}' cannot be set from todo.txt", key)),
+                key => drop(taskmap.insert(key.to_string(), value.to_string())),
+            }
+        } else {
+            description.push(word);
+        }
+    }
+    if description.is_empty() {
+        return Err("missing description".to_string());
+    }
+    taskmap.insert("description".to_string(), description.join(" "));
+    taskmap.insert("entry".to_string(), entry.unwrap_or(now).timestamp().to_string());
+    taskmap.insert("modified".to_string(), now.timestamp().to_string());
+    if completed {
+        taskmap.insert("status".to_string(), "completed".to_string());
+        taskmap.insert("end".to_string(), end.unwrap_or(now).timestamp().to_string());
+    } else {
+        taskmap.insert("status".to_string(), "pending".to_string());
+    }
+    match priority {
+        Some('A') => drop(taskmap.insert("priority".to_string(), "H".to_string())),
+        Some('B') => drop(taskmap.insert("priority".to_string(), "M".to_string())),
+        Some('C') => drop(taskmap.insert("priority".to_string(), "L".to_string())),
+        Some(letter) => drop(taskmap.insert(PRIORITY_UDA.to_string(), letter.to_string())),
+        None => {}
+    }
+    let mut projects = projects.into_iter();
+    if let Some(project) = projects.next() {
+        taskmap.insert("project".to_string(), project);
+    }
+    let rest: Vec<String> = projects.collect();
+    if !rest.is_empty() {
+        taskmap.insert(PROJECTS_UDA.to_string(), rest.join(" "));
+    }
+    Ok((uuid, taskmap))
+}
+
+fn is_priority(word: &str) -> bool {
+    let bytes = word.as_bytes();
+    bytes.len() == 3 && bytes[0] == b'(' && bytes[1].is_ascii_uppercase() && bytes[2] == b')'
+}
+
+/// Read every non-blank line of a todo.txt document, ready for
+/// [`import`]. Lines carry no UUID for the import report.
+pub fn parse(text: &str, now: DateTime<Utc>) -> Vec<(Option<String>, ImportedTask)> {
+    text.lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty())
+        .map(|line| (None, parse_line(line, now)))
+        .collect()
+}
+
+/// Add the operations importing `tasks` to `ops`. A line whose task
+/// already exists, from an earlier import of the same text, is skipped.
+pub fn import(
+    replica: &mut Replica,
+    tasks: Vec<(Option<String>, ImportedTask)>,
+    ops: &mut Operations,
+) -> Result<ImportReport, String> {
+    let mut checked = Vec::with_capacity(tasks.len());
+    for (line, imported) in tasks {
+        let imported = match imported {
+            Ok((uuid, _)) if replica.get_task_data(uuid).map_err(|e| format!("Failed to get task: {}", e))?.is_some() => {
+                Err("already imported".to_string())
+            }
+            imported => imported,
+        };
+        checked.push((line, imported));
+    }
+    store_imported(replica, checked, ops)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use taskchampion::StorageConfig;
+
+    fn import_text(replica: &mut Replica, text: &str) -> ImportReport {
+        let mut ops = Operations::new();
+        let report = import(replica, parse(text, Utc::now()), &mut ops).unwrap();
+        replica.commit_operations(ops).unwrap();
+        report
+    }
+
+    #[test]
+    fn test_todotxt_round_trip() {
+        let text = "(A) 2026-10-01 Call the bank +Finance @phone due:2026-10-20\n\
+                    x 2026-10-16 2026-10-02 Renew passport +Admin +Travel @town pri:B\n\
+                    (D) Read https://example.com/guide +Reading t:2026-10-10 estimate:2h\n\
+                    x Water plants\n";
+        let mut replica = Replica::new(StorageConfig::InMemory.into_storage().unwrap());
+        let report = import_text(&mut replica, text);
+        assert_eq!(report.created.len(), 4);
+
+        let bank = replica.get_task_data(report.created[0]).unwrap().unwrap();
+        assert_eq!(bank.get("description"), Some("Call the bank"));
+        assert_eq!(bank.get("priority"), Some("H"));
+        assert_eq!(bank.get("project"), Some("Finance"));
+        assert_eq!(bank.get("tag_phone"), Some(""));
+        assert_eq!(bank.get("due"), Some("1792454400"));
+        assert_eq!(bank.get("entry"), Some("1790812800"));
+        let passport = replica.get_task_data(report.created[1]).unwrap().unwrap();
+        assert_eq!(passport.get("status"), Some("completed"));
+        assert_eq!(passport.get("end"), Some("1792108800"));
+        assert_eq!(passport.get("priority"), Some("M"));
+        assert_eq!(passport.get(PROJECTS_UDA), Some("Travel"));
+        let guide = replica.get_task_data(report.created[2]).unwrap().unwrap();
+        assert_eq!(guide.get("description"), Some("Read https://example.com/guide"));
+        assert_eq!(guide.get(PRIORITY_UDA), Some("D"));
+        assert_eq!(guide.get("wait"), Some("1791590400"));
+        assert_eq!(guide.get("estimate"), Some("2h"));
+
+        // Lines with creation dates come back as written.
+        let exported = export(&mut replica, &Filter::All).unwrap();
+        assert!(exported.contains("(A) 2026-10-01 Call the bank +Finance @phone due:2026-10-20\n"));
+        assert!(exported.contains("x 2026-10-16 2026-10-02 Renew passport +Admin +Travel @town pri:B\n"));
+
+        // Importing the export elsewhere reproduces it exactly; importing
+        // the same text twice adds nothing.
+        let mut copy = Replica::new(StorageConfig::InMemory.into_storage().unwrap());
+        assert_eq!(import_text(&mut copy, &exported).created.len(), 4);
+        assert_eq!(export(&mut copy, &Filter::All).unwrap(), exported);
+        let report = import_text(&mut copy, &exported);
+        assert!(report.created.is_empty());
+        assert!(report.skipped.iter().all(|(_, _, reason)| reason == "already imported"));
+    }
+
+    #[test]
+    fn test_todotxt_rejects_unusable_lines() {
+        let now = Utc::now();
+        assert_eq!(parse_line("(A) +Project @home", now).unwrap_err(), "missing description");
+        assert!(parse_line("Fix it due:soon", now).is_err());
+        assert!(parse_line("Fix it status:deleted", now).is_err());
+        assert!(parse_line("Fix it @-bad", now).is_err());
+        // Words that only look like `key:value` belong to the description.
+        let (_, taskmap) = parse_line("Call Bob at 10:30 or mailto:bob@example.com re dep_x:1", now).unwrap();
+        assert_eq!(taskmap["description"], "Call Bob at 10:30 or mailto:bob@example.com re dep_x:1");
+        assert_eq!(taskmap.len(), 4);
+        // A second date on an open task belongs to the description.
+        let (_, taskmap) = parse_line("2026-10-01 2026-10-05 retrospective", now).unwrap();
+        assert_eq!(taskmap["description"], "2026-10-05 retrospective");
+    }
+}